@@ -0,0 +1,104 @@
+//! Signed tokens for API requests. `/auth/login` issues a token naming the
+//! user, and the extractors here check it on every other request that needs
+//! one.
+use axum::async_trait;
+use axum::extract::FromRequestParts;
+use axum::http::header;
+use axum::http::request::Parts;
+use chrono::{DateTime, Utc};
+use jsonwebtoken::{Algorithm, Header, Validation};
+use serde::{Deserialize, Serialize};
+use crate::api::error::ApiError;
+use crate::config::auth_config::AuthConfig;
+use crate::config::Config;
+use crate::data::{Data, DataProvider};
+
+/// What a token says: who it is for, and when it was issued and expires, in
+/// seconds since the epoch.
+#[derive(Debug, Deserialize, Serialize, Clone, Eq, PartialEq)]
+struct Claims {
+    sub: String,
+    iat: i64,
+    exp: i64,
+}
+
+/// A token issued at `/auth/login`.
+#[derive(Debug, Deserialize, Serialize, Clone, Eq, PartialEq)]
+pub struct Token {
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// A token for `username`, valid from `now` for as long as `config` says.
+pub fn issue(config: &AuthConfig, username: &str, now: DateTime<Utc>) -> Result<Token, ApiError> {
+    let expires_at = now + config.token_ttl;
+    let claims = Claims {
+        sub: username.to_string(),
+        iat: now.timestamp(),
+        exp: expires_at.timestamp(),
+    };
+    let token = jsonwebtoken::encode(&Header::new(Algorithm::HS256), &claims, &config.encoding_key)
+        .map_err(|_| ApiError::Internal)?;
+    Ok(Token { token, expires_at })
+}
+
+/// Who `token` was issued to, if it was signed with `config`'s secret and
+/// has not expired.
+pub fn verify(config: &AuthConfig, token: &str) -> Option<String> {
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.leeway = 0;
+    jsonwebtoken::decode::<Claims>(token, &config.decoding_key, &validation)
+        .ok()
+        .map(|data| data.claims.sub)
+}
+
+/// The user a request's `Authorization: Bearer` token was issued to.
+/// Requests without a valid token are rejected with 401.
+pub struct AuthUser(pub String);
+
+#[async_trait]
+impl FromRequestParts<Config> for AuthUser {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, config: &Config) -> Result<Self, Self::Rejection> {
+        let token = parts.headers.get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split_once(' '))
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+            .map(|(_, token)| token.trim())
+            .ok_or(ApiError::Unauthorized)?;
+        verify(&config.auth, token).map(AuthUser).ok_or(ApiError::Unauthorized)
+    }
+}
+
+/// The data provider acting for the request's user, so that it only
+/// reaches what they own or were invited to.
+pub struct UserData(pub DataProvider);
+
+#[async_trait]
+impl FromRequestParts<Config> for UserData {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, config: &Config) -> Result<Self, Self::Rejection> {
+        let AuthUser(user) = AuthUser::from_request_parts(parts, config).await?;
+        Ok(UserData(config.data.provider.with_user(user)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_issue_and_verify() {
+        let config = AuthConfig::new();
+        let token = issue(&config, "alice", Utc::now()).unwrap();
+        assert_eq!(verify(&config, &token.token), Some("alice".to_string()));
+        assert_eq!(verify(&AuthConfig::new(), &token.token), None);
+
+        let expired = issue(&config, "alice", Utc::now() - config.token_ttl - Duration::seconds(1)).unwrap();
+        assert_eq!(verify(&config, &expired.token), None);
+        assert_eq!(verify(&config, "not a token"), None);
+    }
+}
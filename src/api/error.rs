@@ -0,0 +1,251 @@
+use std::fmt;
+use axum::{
+    extract::rejection::{JsonRejection, PathRejection, QueryRejection},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use crate::data::DataError;
+use crate::models::bill::BillError;
+use crate::models::currency::MoneyError;
+use crate::models::exchange::ExchangeError;
+use crate::models::validate::FieldError;
+
+/// The JSON body of every error response. `code` is stable and meant for
+/// programs; `message` is meant for people and may change. A request that
+/// fails validation lists every field at fault in `errors`.
+#[derive(Debug, Deserialize, Serialize, Clone, Eq, PartialEq)]
+pub struct ErrorBody {
+    pub code: String,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+}
+
+/// Anything a handler can fail with.
+#[derive(Debug)]
+pub enum ApiError {
+    /// No route matches the request.
+    RouteNotFound,
+    InvalidUuid,
+    InvalidBody(JsonRejection),
+    InvalidPath(PathRejection),
+    InvalidQuery(QueryRejection),
+    /// A patch is neither `application/merge-patch+json` nor
+    /// `application/json-patch+json`.
+    UnsupportedMediaType,
+    /// The body of a patch could not be parsed.
+    InvalidPatch(String),
+    /// A change to a bill was sent without `If-Match`.
+    PreconditionRequired,
+    /// `If-Match` does not name a version of the bill.
+    PreconditionFailed,
+    /// The request has no bearer token, or one that is not valid.
+    Unauthorized,
+    /// No account has that username and password.
+    InvalidCredentials,
+    /// The user is signed in but may not do that.
+    Forbidden,
+    Data(DataError),
+    Money(MoneyError),
+    Exchange(ExchangeError),
+    /// A handler panicked or could not be run.
+    Internal,
+}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::RouteNotFound => StatusCode::NOT_FOUND,
+            ApiError::InvalidUuid => StatusCode::BAD_REQUEST,
+            ApiError::InvalidBody(rejection) => rejection.status(),
+            ApiError::InvalidPath(rejection) => rejection.status(),
+            ApiError::InvalidQuery(rejection) => rejection.status(),
+            ApiError::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ApiError::InvalidPatch(_) => StatusCode::BAD_REQUEST,
+            ApiError::PreconditionRequired => StatusCode::PRECONDITION_REQUIRED,
+            ApiError::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+            ApiError::Unauthorized | ApiError::InvalidCredentials => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden => StatusCode::FORBIDDEN,
+            ApiError::Data(err) => match err {
+                DataError::BillNotFound
+                | DataError::ItemNotFound
+                | DataError::AdjustmentNotFound
+                | DataError::RevisionNotFound
+                | DataError::GroupNotFound
+                | DataError::PaymentNotFound
+                | DataError::ParticipantNotFound
+                | DataError::UserNotFound
+                | DataError::MemberNotFound => StatusCode::NOT_FOUND,
+                DataError::Forbidden => StatusCode::FORBIDDEN,
+                DataError::VersionMismatch { .. } => StatusCode::PRECONDITION_FAILED,
+                DataError::Money(_) => StatusCode::UNPROCESSABLE_ENTITY,
+                DataError::Conflict(_) => StatusCode::CONFLICT,
+                DataError::Validation(_) | DataError::Invalid(_) => StatusCode::UNPROCESSABLE_ENTITY,
+                DataError::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            ApiError::Money(_) | ApiError::Exchange(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::RouteNotFound => "route_not_found",
+            ApiError::InvalidUuid => "invalid_uuid",
+            ApiError::InvalidBody(_) => "invalid_body",
+            ApiError::InvalidPath(_) => "invalid_path",
+            ApiError::InvalidQuery(_) => "invalid_query",
+            ApiError::UnsupportedMediaType => "unsupported_media_type",
+            ApiError::InvalidPatch(_) => "invalid_patch",
+            ApiError::PreconditionRequired => "precondition_required",
+            ApiError::PreconditionFailed => "precondition_failed",
+            ApiError::Unauthorized => "unauthorized",
+            ApiError::InvalidCredentials => "invalid_credentials",
+            ApiError::Forbidden => "forbidden",
+            ApiError::Data(err) => match err {
+                DataError::BillNotFound => "bill_not_found",
+                DataError::ItemNotFound => "item_not_found",
+                DataError::AdjustmentNotFound => "adjustment_not_found",
+                DataError::RevisionNotFound => "revision_not_found",
+                DataError::GroupNotFound => "group_not_found",
+                DataError::PaymentNotFound => "payment_not_found",
+                DataError::ParticipantNotFound => "participant_not_found",
+                DataError::UserNotFound => "user_not_found",
+                DataError::MemberNotFound => "member_not_found",
+                DataError::Forbidden => "forbidden",
+                DataError::VersionMismatch { .. } => "version_mismatch",
+                DataError::Money(err) => money_code(err),
+                DataError::Conflict(_) => "conflict",
+                DataError::Validation(_) => "validation_failed",
+                DataError::Invalid(_) => "invalid",
+                DataError::Storage(_) => "storage_error",
+            },
+            ApiError::Money(err) => money_code(err),
+            ApiError::Exchange(err) => match err {
+                ExchangeError::NoRate { .. } => "no_exchange_rate",
+                ExchangeError::DuplicateRate { .. } => "duplicate_exchange_rate",
+                ExchangeError::InvalidRate(_) => "invalid_exchange_rate",
+                ExchangeError::Overflow => "amount_out_of_range",
+                ExchangeError::Money(err) => money_code(err),
+            },
+            ApiError::Internal => "internal_error",
+        }
+    }
+}
+
+fn money_code(err: &MoneyError) -> &'static str {
+    match err {
+        MoneyError::UnknownCurrency(_) => "unknown_currency",
+        MoneyError::CurrencyMismatch { .. } => "currency_mismatch",
+        MoneyError::InvalidAmount(_) => "invalid_amount",
+        MoneyError::Overflow => "amount_out_of_range",
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ApiError::RouteNotFound => write!(f, "404 Not Found"),
+            ApiError::InvalidUuid => write!(f, "Invalid UUID"),
+            ApiError::InvalidBody(rejection) => write!(f, "{}", rejection.body_text()),
+            ApiError::InvalidPath(rejection) => write!(f, "{}", rejection.body_text()),
+            ApiError::InvalidQuery(rejection) => write!(f, "{}", rejection.body_text()),
+            ApiError::UnsupportedMediaType => {
+                write!(f, "Expected application/merge-patch+json or application/json-patch+json")
+            },
+            ApiError::InvalidPatch(message) => write!(f, "Invalid patch: {}", message),
+            ApiError::PreconditionRequired => write!(f, "Changing a bill requires an If-Match header with its ETag"),
+            ApiError::PreconditionFailed => write!(f, "If-Match does not match any version of the bill"),
+            ApiError::Unauthorized => write!(f, "Sign in at /auth/login and send the token as Authorization: Bearer"),
+            ApiError::InvalidCredentials => write!(f, "Wrong username or password"),
+            ApiError::Forbidden => write!(f, "Only an admin can do that"),
+            ApiError::Data(err) => write!(f, "{}", err),
+            ApiError::Money(err) => write!(f, "{}", err),
+            ApiError::Exchange(err) => write!(f, "{}", err),
+            ApiError::Internal => write!(f, "Internal server error"),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let errors = match &self {
+            ApiError::Data(DataError::Validation(err)) => err.errors.clone(),
+            _ => vec![],
+        };
+        let body = ErrorBody {
+            code: self.code().to_string(),
+            message: self.to_string(),
+            errors,
+        };
+        let mut response = (self.status(), axum::Json(body)).into_response();
+        if let ApiError::Unauthorized = self {
+            response.headers_mut().insert(header::WWW_AUTHENTICATE, header::HeaderValue::from_static("Bearer"));
+        }
+        response
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        ApiError::InvalidBody(rejection)
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        ApiError::InvalidPath(rejection)
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiError::InvalidQuery(rejection)
+    }
+}
+
+impl From<DataError> for ApiError {
+    fn from(err: DataError) -> Self {
+        ApiError::Data(err)
+    }
+}
+
+impl From<BillError> for ApiError {
+    fn from(err: BillError) -> Self {
+        ApiError::Data(err.into())
+    }
+}
+
+impl From<MoneyError> for ApiError {
+    fn from(err: MoneyError) -> Self {
+        ApiError::Money(err)
+    }
+}
+
+impl From<ExchangeError> for ApiError {
+    fn from(err: ExchangeError) -> Self {
+        ApiError::Exchange(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::currency::Currency;
+
+    #[test]
+    fn test_status_and_code() {
+        let err = ApiError::from(DataError::BillNotFound);
+        assert_eq!((err.status(), err.code()), (StatusCode::NOT_FOUND, "bill_not_found"));
+        let err = ApiError::from(DataError::Storage("disk I/O error".to_string()));
+        assert_eq!((err.status(), err.code()), (StatusCode::INTERNAL_SERVER_ERROR, "storage_error"));
+        let err = ApiError::from(BillError::Money(MoneyError::CurrencyMismatch {
+            expected: Currency::USD,
+            found: Currency::EUR,
+        }));
+        assert_eq!((err.status(), err.code()), (StatusCode::UNPROCESSABLE_ENTITY, "currency_mismatch"));
+    }
+}
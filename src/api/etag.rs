@@ -0,0 +1,99 @@
+//! Bill versions on the wire: `ETag` and `Last-Modified` on the way out,
+//! `If-Match` on the way in.
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue};
+use crate::api::error::ApiError;
+use crate::models::bill::BillWithId;
+
+/// The `ETag` header for a bill at `version`, e.g. `"3"`.
+pub fn etag(version: u64) -> [(HeaderName, HeaderValue); 1] {
+    let value = HeaderValue::from_str(&format!("\"{}\"", version)).unwrap();
+    [(header::ETAG, value)]
+}
+
+/// The `ETag` and `Last-Modified` headers for a stored bill.
+pub fn validators(stored: &BillWithId) -> [(HeaderName, HeaderValue); 2] {
+    let [etag] = etag(stored.version);
+    let last_modified = stored.updated_at.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+    [etag, (header::LAST_MODIFIED, HeaderValue::from_str(&last_modified).unwrap())]
+}
+
+/// The version a request's `If-Match` header asks for. `*` matches any
+/// version, so it gives `None`. Changing a bill without `If-Match` is not
+/// allowed, and a header that cannot name one of our versions, such as a
+/// weak tag, can never match.
+pub fn if_match(headers: &HeaderMap) -> Result<Option<u64>, ApiError> {
+    let value = headers.get(header::IF_MATCH).ok_or(ApiError::PreconditionRequired)?;
+    let value = value.to_str().map_err(|_| ApiError::PreconditionFailed)?.trim();
+    if value == "*" {
+        return Ok(None);
+    }
+    value.strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .and_then(|version| version.parse().ok())
+        .map(Some)
+        .ok_or(ApiError::PreconditionFailed)
+}
+
+/// Like `if_match`, for changes that do not need the header: without it
+/// the change applies to whatever version is current.
+pub fn optional_if_match(headers: &HeaderMap) -> Result<Option<u64>, ApiError> {
+    if headers.contains_key(header::IF_MATCH) {
+        if_match(headers)
+    } else {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(if_match: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_MATCH, HeaderValue::from_str(if_match).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_if_match() {
+        assert_eq!(if_match(&headers("\"3\"")).unwrap(), Some(3));
+        assert_eq!(if_match(&headers("*")).unwrap(), None);
+        assert!(matches!(if_match(&headers("W/\"3\"")), Err(ApiError::PreconditionFailed)));
+        assert!(matches!(if_match(&headers("3")), Err(ApiError::PreconditionFailed)));
+        assert!(matches!(if_match(&HeaderMap::new()), Err(ApiError::PreconditionRequired)));
+    }
+
+    #[test]
+    fn test_optional_if_match() {
+        assert_eq!(optional_if_match(&headers("\"3\"")).unwrap(), Some(3));
+        assert_eq!(optional_if_match(&HeaderMap::new()).unwrap(), None);
+        assert!(matches!(optional_if_match(&headers("W/\"3\"")), Err(ApiError::PreconditionFailed)));
+    }
+
+    #[test]
+    fn test_etag() {
+        assert_eq!(etag(12)[0].1, "\"12\"");
+    }
+
+    #[test]
+    fn test_validators() {
+        use chrono::{TimeZone, Utc};
+        use uuid::Uuid;
+        use crate::models::bill::Bill;
+        use crate::models::currency::Currency;
+
+        let time = Utc.with_ymd_and_hms(1994, 11, 6, 8, 49, 37).unwrap();
+        let stored = BillWithId {
+            id: Uuid::new_v4(),
+            version: 3,
+            created_at: time,
+            updated_at: time,
+            owner: None,
+            members: vec![],
+            bill: Bill::new("test".to_string(), Currency::USD),
+        };
+        let [etag, last_modified] = validators(&stored);
+        assert_eq!(etag.1, "\"3\"");
+        assert_eq!(last_modified, (header::LAST_MODIFIED, HeaderValue::from_static("Sun, 06 Nov 1994 08:49:37 GMT")));
+    }
+}
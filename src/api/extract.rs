@@ -0,0 +1,45 @@
+//! Extractors that reject bad requests with an `ApiError`, so clients get
+//! the same JSON error body as for every other failure.
+use axum::async_trait;
+use axum::body::Bytes;
+use axum::extract::{FromRequest, Request};
+use axum::http::header;
+use axum_macros::{FromRequest, FromRequestParts};
+use crate::api::error::ApiError;
+use crate::models::patch::BillPatch;
+
+#[derive(FromRequest)]
+#[from_request(via(axum::Json), rejection(ApiError))]
+pub struct Json<T>(pub T);
+
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(ApiError))]
+pub struct Path<T>(pub T);
+
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(ApiError))]
+pub struct Query<T>(pub T);
+
+/// A `BillPatch`, read as a merge patch or a JSON Patch according to the
+/// request's `Content-Type`.
+pub struct Patch(pub BillPatch);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequest<S> for Patch {
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let content_type = req.headers().get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .map(|value| value.trim().to_ascii_lowercase());
+        let body = Bytes::from_request(req, state).await
+            .map_err(|rejection| ApiError::InvalidPatch(rejection.body_text()))?;
+        let patch = match content_type.as_deref() {
+            Some("application/merge-patch+json") => serde_json::from_slice(&body).map(BillPatch::Merge),
+            Some("application/json-patch+json") => serde_json::from_slice(&body).map(BillPatch::Json),
+            _ => return Err(ApiError::UnsupportedMediaType),
+        };
+        patch.map(Patch).map_err(|err| ApiError::InvalidPatch(err.to_string()))
+    }
+}
@@ -0,0 +1,46 @@
+use axum::extract::State;
+use crate::api::auth::{self, Token};
+use crate::api::error::ApiError;
+use crate::api::extract;
+use crate::api::handlers::run_blocking;
+use crate::config::Config;
+use crate::data::{Data, DataError};
+use crate::models::user::{Credentials, User};
+
+/// Creates an account. Returns its username.
+pub async fn register(
+    State(config): State<Config>,
+    extract::Json(credentials): extract::Json<Credentials>,
+) -> Result<axum::Json<String>, ApiError> {
+    run_blocking(move || {
+        credentials.validate().map_err(DataError::from)?;
+        let user = User::register(&credentials).map_err(|_| ApiError::Internal)?;
+        config.data.provider.add_user(&user)?;
+        Ok(axum::Json(user.username))
+    }).await
+}
+
+/// Issues a token for the other routes. An unknown username and a wrong
+/// password fail the same way and take as long, so that nobody can tell
+/// which accounts exist.
+pub async fn login(
+    State(config): State<Config>,
+    extract::Json(credentials): extract::Json<Credentials>,
+) -> Result<axum::Json<Token>, ApiError> {
+    run_blocking(move || {
+        let user = match config.data.provider.get_user(&credentials.username) {
+            Ok(user) => user,
+            Err(DataError::UserNotFound) => {
+                if let Some(nobody) = User::nobody() {
+                    nobody.verify(&credentials.password);
+                }
+                return Err(ApiError::InvalidCredentials);
+            },
+            Err(err) => return Err(err.into()),
+        };
+        if !user.verify(&credentials.password) {
+            return Err(ApiError::InvalidCredentials);
+        }
+        Ok(axum::Json(auth::issue(&config.auth, &user.username, chrono::Utc::now())?))
+    }).await
+}
@@ -1,11 +1,7 @@
-use axum::{
-    Json,
-    http::StatusCode,
-    response::IntoResponse,
-};
+use crate::api::error::ApiError;
 
-pub async fn fallback() -> impl IntoResponse {
-    (StatusCode::NOT_FOUND, Json("404 Not Found")).into_response()
+pub async fn fallback() -> ApiError {
+    ApiError::RouteNotFound
 }
 
 pub async fn hello() -> String {
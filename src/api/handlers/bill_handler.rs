@@ -1,99 +1,271 @@
-use axum::{
-    extract,
-    extract::{Path, State},
-    response::IntoResponse,
-    http::StatusCode,
-};
-use std::thread;
+use std::collections::HashMap;
+use axum::extract::State;
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue};
+use serde::Deserialize;
+use std::sync::PoisonError;
 use uuid::Uuid;
+use crate::api::auth::UserData;
+use crate::api::error::ApiError;
+use crate::api::etag::{etag, if_match, optional_if_match, validators};
+use crate::api::extract::{self, Path, Query};
+use crate::api::handlers::run_blocking;
 use crate::data::Data;
+use crate::data::history::Revision;
+use crate::data::query::BillQuery;
 use crate::config::Config;
-use crate::models::bill::Bill;
+use crate::models::bill::{Bill, BillWithId};
+use crate::models::breakdown::{Breakdown, Settlement};
+use crate::models::currency::Currency;
+use crate::models::item::LineItem;
+use crate::models::payment::for_bill;
+use crate::models::settlement::SettlementPlan;
 
-pub async fn get_bills_route(State(config): State<Config>) -> impl IntoResponse {
-    thread::spawn(move || {
-        let res = config.data.provider.get_bills();
-        axum::Json(res)
-    }).join().unwrap()
+#[derive(Deserialize)]
+pub struct NewBillParams {
+    currency: Option<Currency>,
+}
+
+fn parse_uuid(id: &str) -> Result<Uuid, ApiError> {
+    Uuid::parse_str(id).map_err(|_| ApiError::InvalidUuid)
+}
+
+/// How many bills a page holds unless the client asks for fewer.
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 200;
+
+/// Lists a page of bills. If there are more, the `Link` header points to the
+/// next page.
+pub async fn get_bills_route(
+    UserData(data): UserData,
+    Query(mut query): Query<BillQuery>,
+) -> Result<(HeaderMap, axum::Json<Vec<BillWithId>>), ApiError> {
+    run_blocking(move || {
+        query.limit = Some(query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE));
+        let page = data.query_bills(&query)?;
+        let mut headers = HeaderMap::new();
+        if let Some(next) = page.next {
+            query.cursor = Some(next);
+            let next_query = serde_urlencoded::to_string(&query).map_err(|_| ApiError::Internal)?;
+            let link = format!("</bills?{}>; rel=\"next\"", next_query);
+            headers.insert(header::LINK, HeaderValue::from_str(&link).map_err(|_| ApiError::Internal)?);
+        }
+        Ok((headers, axum::Json(page.bills)))
+    }).await
 }
 
 pub async fn get_bill_from_id(
     Path(id): Path<String>,
-    State(config): State<Config>
-) -> impl IntoResponse {
-    thread::spawn(move || {
-        let uuid = Uuid::parse_str(&id);
-        match uuid {
-            Ok(uuid) => {
-                let res = config.data.provider.get_bill(uuid);
-                match res {
-                    Some(bill) => (StatusCode::OK, axum::Json(bill)).into_response(),
-                    None => (StatusCode::NOT_FOUND, axum::Json("Bill not found")).into_response()
-                }
-            },
-            Err(_) => (StatusCode::BAD_REQUEST, axum::Json("Invalid UUID")).into_response()
-        }
-    }).join().unwrap()
+    UserData(data): UserData
+) -> Result<([(HeaderName, HeaderValue); 2], axum::Json<Bill>), ApiError> {
+    run_blocking(move || {
+        let uuid = parse_uuid(&id)?;
+        let stored = data.get_versioned_bill(uuid)?;
+        Ok((validators(&stored), axum::Json(stored.bill)))
+    }).await
 }
 
 pub async fn new_empty_bill(
-    State(config): State<Config>,
+    UserData(data): UserData,
+    Query(params): Query<NewBillParams>,
     extract::Json(name): extract::Json<String>,
-) -> impl IntoResponse {
-    thread::spawn(move || {
-        let bill = Bill::new(name);
-        let res = config.data.provider.add_bill(&bill);
-        axum::Json(res)
-    }).join().unwrap()
+) -> Result<axum::Json<Uuid>, ApiError> {
+    run_blocking(move || {
+        let bill = Bill::new(name, params.currency.unwrap_or_default());
+        let res = data.add_bill(&bill)?;
+        Ok(axum::Json(res))
+    }).await
 }
 
 pub async fn create_bill(
-    State(config): State<Config>,
-    extract::Json(bill): extract::Json<crate::models::bill::Bill>,
-) -> impl IntoResponse {
-    thread::spawn(move || {
-        let res = config.data.provider.add_bill(&bill);
-        axum::Json(res)
-    }).join().unwrap()
+    UserData(data): UserData,
+    extract::Json(bill): extract::Json<Bill>,
+) -> Result<axum::Json<Uuid>, ApiError> {
+    run_blocking(move || {
+        bill.check_currency()?;
+        let res = data.add_bill(&bill)?;
+        Ok(axum::Json(res))
+    }).await
 }
 
 
 pub async fn update_bill(
     Path(id): Path<String>,
-    State(config): State<Config>,
-    extract::Json(bill): extract::Json<crate::models::bill::Bill>,
-) -> impl IntoResponse {
-    thread::spawn(move || {
-        let uuid = Uuid::parse_str(&id);
-        match uuid {
-            Ok(uuid) => {
-                let res = config.data.provider.update_bill(uuid, &bill);
-                match res {
-                    Ok(uuid) => (StatusCode::OK, axum::Json(uuid)).into_response(),
-                    Err(_) => (StatusCode::NOT_FOUND, axum::Json("Bill not found")).into_response()
-                }
-            },
-            Err(_) => (StatusCode::BAD_REQUEST, axum::Json("Invalid UUID")).into_response()
-        }
-    }).join().unwrap()
+    UserData(data): UserData,
+    headers: HeaderMap,
+    extract::Json(bill): extract::Json<Bill>,
+) -> Result<([(HeaderName, HeaderValue); 1], axum::Json<Uuid>), ApiError> {
+    run_blocking(move || {
+        let uuid = parse_uuid(&id)?;
+        let expected_version = if_match(&headers)?;
+        bill.check_currency()?;
+        let version = data.update_bill(uuid, &bill, expected_version)?;
+        Ok((etag(version), axum::Json(uuid)))
+    }).await
+}
+
+
+/// Applies a merge patch or JSON Patch to the bill and returns the result.
+pub async fn patch_bill(
+    Path(id): Path<String>,
+    UserData(data): UserData,
+    headers: HeaderMap,
+    extract::Patch(patch): extract::Patch,
+) -> Result<([(HeaderName, HeaderValue); 1], axum::Json<Bill>), ApiError> {
+    run_blocking(move || {
+        let uuid = parse_uuid(&id)?;
+        let expected_version = if_match(&headers)?;
+        let patched = data.patch_bill(uuid, &patch, expected_version)?;
+        Ok((etag(patched.version), axum::Json(patched.bill)))
+    }).await
+}
+
+
+pub async fn get_history(
+    Path(id): Path<String>,
+    UserData(data): UserData,
+) -> Result<axum::Json<Vec<Revision>>, ApiError> {
+    run_blocking(move || {
+        let uuid = parse_uuid(&id)?;
+        let history = data.get_history(uuid)?;
+        Ok(axum::Json(history))
+    }).await
+}
+
+
+/// Puts the bill back as it was at an earlier version and returns it.
+pub async fn revert_bill(
+    Path((id, version)): Path<(String, u64)>,
+    UserData(data): UserData,
+    headers: HeaderMap,
+) -> Result<([(HeaderName, HeaderValue); 2], axum::Json<Bill>), ApiError> {
+    run_blocking(move || {
+        let uuid = parse_uuid(&id)?;
+        let expected_version = if_match(&headers)?;
+        let stored = data.revert_bill(uuid, version, expected_version)?;
+        Ok((validators(&stored), axum::Json(stored.bill)))
+    }).await
 }
 
 
 pub async fn delete_bill(
     Path(id): Path<String>,
-    State(config): State<Config>
-) -> impl IntoResponse {
-    thread::spawn(move || {
-        let uuid = Uuid::parse_str(&id);
-        match uuid {
-            Ok(uuid) => {
-                let res = config.data.provider.delete_bill(uuid);
-                match res {
-                    Ok(uuid) => (StatusCode::OK, axum::Json(uuid)).into_response(),
-                    Err(_) => (StatusCode::NOT_FOUND, axum::Json("Bill not found")).into_response()
-                }
-            },
-            Err(_) => (StatusCode::BAD_REQUEST, axum::Json("Invalid UUID")).into_response()
-        }
-    }).join().unwrap()
+    UserData(data): UserData,
+    headers: HeaderMap,
+) -> Result<axum::Json<Uuid>, ApiError> {
+    run_blocking(move || {
+        let uuid = parse_uuid(&id)?;
+        let expected_version = if_match(&headers)?;
+        let res = data.delete_bill(uuid, expected_version)?;
+        Ok(axum::Json(res))
+    }).await
+}
+
+
+pub async fn get_items(
+    Path(id): Path<String>,
+    UserData(data): UserData
+) -> Result<axum::Json<HashMap<u16, LineItem>>, ApiError> {
+    run_blocking(move || {
+        let uuid = parse_uuid(&id)?;
+        let items = data.get_items(uuid)?;
+        Ok(axum::Json(items))
+    }).await
+}
+
+
+/// Adds an item to the bill. With `If-Match`, only to that version of it.
+pub async fn add_item(
+    Path(id): Path<String>,
+    UserData(data): UserData,
+    headers: HeaderMap,
+    extract::Json(item): extract::Json<LineItem>,
+) -> Result<([(HeaderName, HeaderValue); 1], axum::Json<u16>), ApiError> {
+    run_blocking(move || {
+        let uuid = parse_uuid(&id)?;
+        let expected_version = optional_if_match(&headers)?;
+        let (item_id, version) = data.add_item(uuid, &item, expected_version)?;
+        Ok((etag(version), axum::Json(item_id)))
+    }).await
+}
+
+
+pub async fn update_item(
+    Path((id, item_id)): Path<(String, u16)>,
+    UserData(data): UserData,
+    headers: HeaderMap,
+    extract::Json(item): extract::Json<LineItem>,
+) -> Result<([(HeaderName, HeaderValue); 1], axum::Json<u16>), ApiError> {
+    run_blocking(move || {
+        let uuid = parse_uuid(&id)?;
+        let expected_version = optional_if_match(&headers)?;
+        let (item_id, version) = data.update_item(uuid, item_id, &item, expected_version)?;
+        Ok((etag(version), axum::Json(item_id)))
+    }).await
+}
+
+
+pub async fn delete_item(
+    Path((id, item_id)): Path<(String, u16)>,
+    UserData(data): UserData,
+    headers: HeaderMap,
+) -> Result<([(HeaderName, HeaderValue); 1], axum::Json<u16>), ApiError> {
+    run_blocking(move || {
+        let uuid = parse_uuid(&id)?;
+        let expected_version = optional_if_match(&headers)?;
+        let (item_id, version) = data.delete_item(uuid, item_id, expected_version)?;
+        Ok((etag(version), axum::Json(item_id)))
+    }).await
+}
+
+
+pub async fn get_bill_for(
+    Path((id, orderer)): Path<(String, String)>,
+    UserData(data): UserData
+) -> Result<axum::Json<Bill>, ApiError> {
+    run_blocking(move || {
+        let uuid = parse_uuid(&id)?;
+        let bill = data.get_bill(uuid)?;
+        Ok(axum::Json(bill.get_bill_for(&orderer)?))
+    }).await
+}
+
+
+pub async fn get_breakdown(
+    Path(id): Path<String>,
+    UserData(data): UserData
+) -> Result<axum::Json<Breakdown>, ApiError> {
+    run_blocking(move || {
+        let uuid = parse_uuid(&id)?;
+        let bill = data.get_bill(uuid)?;
+        Ok(axum::Json(bill.get_breakdown()?))
+    }).await
+}
+
+
+/// Who pays whom, and how much, to settle the bill, counting the payments
+/// already made towards it.
+pub async fn get_settlement_plan(
+    Path(id): Path<String>,
+    UserData(data): UserData
+) -> Result<axum::Json<SettlementPlan>, ApiError> {
+    run_blocking(move || {
+        let uuid = parse_uuid(&id)?;
+        let bill = data.get_bill(uuid)?;
+        let payments = data.get_payments()?;
+        Ok(axum::Json(bill.get_settlement_plan_with(for_bill(&payments, uuid))?))
+    }).await
+}
+
+
+pub async fn get_settlement_for(
+    Path((id, orderer, currency)): Path<(String, String, Currency)>,
+    State(config): State<Config>,
+    UserData(data): UserData,
+) -> Result<axum::Json<Settlement>, ApiError> {
+    run_blocking(move || {
+        let uuid = parse_uuid(&id)?;
+        let bill = data.get_bill(uuid)?;
+        let rates = config.rates.table.read().unwrap_or_else(PoisonError::into_inner);
+        Ok(axum::Json(bill.get_settlement_for(&orderer, currency, &rates)?))
+    }).await
 }
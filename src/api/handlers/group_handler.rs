@@ -0,0 +1,101 @@
+use uuid::Uuid;
+use crate::api::auth::UserData;
+use crate::api::error::ApiError;
+use crate::api::extract::{self, Path};
+use crate::api::handlers::run_blocking;
+use crate::data::{Data, DataError};
+use crate::models::group::{balance_sheets, BalanceSheet, Group, GroupWithId};
+use crate::models::payment::{for_bill, for_group, Payment};
+
+fn parse_uuid(id: &str) -> Result<Uuid, ApiError> {
+    Uuid::parse_str(id).map_err(|_| ApiError::InvalidUuid)
+}
+
+pub async fn get_groups(
+    UserData(data): UserData,
+) -> Result<axum::Json<Vec<GroupWithId>>, ApiError> {
+    run_blocking(move || {
+        let groups = data.get_groups()?;
+        Ok(axum::Json(groups))
+    }).await
+}
+
+pub async fn create_group(
+    UserData(data): UserData,
+    extract::Json(group): extract::Json<Group>,
+) -> Result<axum::Json<Uuid>, ApiError> {
+    run_blocking(move || {
+        let res = data.add_group(&group)?;
+        Ok(axum::Json(res))
+    }).await
+}
+
+
+pub async fn get_group(
+    Path(id): Path<String>,
+    UserData(data): UserData,
+) -> Result<axum::Json<Group>, ApiError> {
+    run_blocking(move || {
+        let uuid = parse_uuid(&id)?;
+        let group = data.get_group(uuid)?;
+        Ok(axum::Json(group))
+    }).await
+}
+
+
+pub async fn update_group(
+    Path(id): Path<String>,
+    UserData(data): UserData,
+    extract::Json(group): extract::Json<Group>,
+) -> Result<axum::Json<Uuid>, ApiError> {
+    run_blocking(move || {
+        let uuid = parse_uuid(&id)?;
+        let res = data.update_group(uuid, &group)?;
+        Ok(axum::Json(res))
+    }).await
+}
+
+
+pub async fn delete_group(
+    Path(id): Path<String>,
+    UserData(data): UserData,
+) -> Result<axum::Json<Uuid>, ApiError> {
+    run_blocking(move || {
+        let uuid = parse_uuid(&id)?;
+        let res = data.delete_group(uuid)?;
+        Ok(axum::Json(res))
+    }).await
+}
+
+
+/// Everyone's net balance across the group's bills, counting the payments
+/// made towards the bills and the group, and the fewest transfers that
+/// settle them, one sheet per currency.
+pub async fn get_balances(
+    Path(id): Path<String>,
+    UserData(data): UserData,
+) -> Result<axum::Json<Vec<BalanceSheet>>, ApiError> {
+    run_blocking(move || {
+        let uuid = parse_uuid(&id)?;
+        let group = data.get_group(uuid)?;
+        let payments = data.get_payments()?;
+        let mut plans = Vec::with_capacity(group.bills.len());
+        for bill in &group.bills {
+            match data.get_bill(*bill) {
+                Ok(stored) => plans.push(
+                    stored.get_settlement_plan_with(for_bill(&payments, *bill))?
+                        .renamed(|person| group.participants.translate(stored.participants(), person))
+                ),
+                // deleted since the group was loaded
+                Err(DataError::BillNotFound) => {},
+                Err(err) => return Err(err.into()),
+            }
+        }
+        let group_payments = for_group(&payments, uuid).map(|payment| Payment {
+            from: group.participants.reference(&payment.from),
+            to: group.participants.reference(&payment.to),
+            ..payment.clone()
+        }).collect::<Vec<Payment>>();
+        Ok(axum::Json(balance_sheets(&group.members, &plans, &group_payments)))
+    }).await
+}
@@ -0,0 +1,71 @@
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::api::auth::UserData;
+use crate::api::error::ApiError;
+use crate::api::etag::{etag, optional_if_match};
+use crate::api::extract::Path;
+use crate::api::handlers::run_blocking;
+use crate::data::Data;
+use crate::models::bill::BillWithId;
+
+/// Who can see a bill: its owner, and whoever they shared it with.
+#[derive(Debug, Deserialize, Serialize, Clone, Eq, PartialEq)]
+pub struct Members {
+    pub owner: Option<String>,
+    pub members: Vec<String>,
+}
+
+impl From<BillWithId> for Members {
+    fn from(stored: BillWithId) -> Self {
+        Self {
+            owner: stored.owner,
+            members: stored.members,
+        }
+    }
+}
+
+fn parse_uuid(id: &str) -> Result<Uuid, ApiError> {
+    Uuid::parse_str(id).map_err(|_| ApiError::InvalidUuid)
+}
+
+pub async fn get_members(
+    Path(id): Path<String>,
+    UserData(data): UserData,
+) -> Result<axum::Json<Members>, ApiError> {
+    run_blocking(move || {
+        let uuid = parse_uuid(&id)?;
+        let stored = data.get_versioned_bill(uuid)?;
+        Ok(axum::Json(Members::from(stored)))
+    }).await
+}
+
+/// Shares the bill with another account. Only the owner may.
+pub async fn invite_member(
+    Path((id, username)): Path<(String, String)>,
+    UserData(data): UserData,
+    headers: HeaderMap,
+) -> Result<([(HeaderName, HeaderValue); 1], axum::Json<Members>), ApiError> {
+    run_blocking(move || {
+        let uuid = parse_uuid(&id)?;
+        let expected_version = optional_if_match(&headers)?;
+        let stored = data.invite_member(uuid, &username, expected_version)?;
+        Ok((etag(stored.version), axum::Json(Members::from(stored))))
+    }).await
+}
+
+
+/// Stops sharing the bill with someone. The owner may remove anyone, and
+/// members may remove themselves.
+pub async fn remove_member(
+    Path((id, username)): Path<(String, String)>,
+    UserData(data): UserData,
+    headers: HeaderMap,
+) -> Result<([(HeaderName, HeaderValue); 1], axum::Json<Members>), ApiError> {
+    run_blocking(move || {
+        let uuid = parse_uuid(&id)?;
+        let expected_version = optional_if_match(&headers)?;
+        let stored = data.remove_member(uuid, &username, expected_version)?;
+        Ok((etag(stored.version), axum::Json(Members::from(stored))))
+    }).await
+}
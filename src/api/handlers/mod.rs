@@ -1,2 +1,35 @@
+pub mod auth_handler;
 pub mod basic_handler;
 pub mod bill_handler;
+pub mod group_handler;
+pub mod member_handler;
+pub mod participant_handler;
+pub mod payment_handler;
+pub mod rates_handler;
+
+use tokio::task;
+use crate::api::error::ApiError;
+
+/// Runs a synchronous data call on tokio's blocking thread pool, so that a
+/// slow backend does not hold up a worker thread. A panic in `f` becomes an
+/// internal error rather than taking the handler down with it.
+pub(crate) async fn run_blocking<F, T>(f: F) -> Result<T, ApiError>
+where
+    F: FnOnce() -> Result<T, ApiError> + Send + 'static,
+    T: Send + 'static,
+{
+    task::spawn_blocking(f).await.unwrap_or(Err(ApiError::Internal))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run_blocking() {
+        assert_eq!(run_blocking(|| Ok(1)).await.unwrap(), 1);
+
+        let res = run_blocking(|| -> Result<(), ApiError> { panic!("backend failed") }).await;
+        assert!(matches!(res, Err(ApiError::Internal)));
+    }
+}
@@ -0,0 +1,164 @@
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use serde::Deserialize;
+use uuid::Uuid;
+use crate::api::auth::UserData;
+use crate::api::error::ApiError;
+use crate::api::etag::{etag, optional_if_match};
+use crate::api::extract::{self, Path};
+use crate::api::handlers::run_blocking;
+use crate::data::Data;
+use crate::models::participant::{Participant, ParticipantChange, Participants};
+
+/// Who to merge a participant into.
+#[derive(Deserialize)]
+pub struct MergeBody {
+    into: Uuid,
+}
+
+fn parse_uuid(id: &str) -> Result<Uuid, ApiError> {
+    Uuid::parse_str(id).map_err(|_| ApiError::InvalidUuid)
+}
+
+pub async fn get_bill_participants(
+    Path(id): Path<String>,
+    UserData(data): UserData,
+) -> Result<axum::Json<Participants>, ApiError> {
+    run_blocking(move || {
+        let uuid = parse_uuid(&id)?;
+        let bill = data.get_bill(uuid)?;
+        Ok(axum::Json(bill.participants().clone()))
+    }).await
+}
+
+/// Registers someone on the bill; whatever on it already goes by their name
+/// or one of their aliases now refers to them.
+pub async fn add_bill_participant(
+    Path(id): Path<String>,
+    UserData(data): UserData,
+    headers: HeaderMap,
+    extract::Json(participant): extract::Json<Participant>,
+) -> Result<([(HeaderName, HeaderValue); 1], axum::Json<Uuid>), ApiError> {
+    run_blocking(move || {
+        let uuid = parse_uuid(&id)?;
+        let participant_id = Uuid::new_v4();
+        let expected_version = optional_if_match(&headers)?;
+        let stored = data.change_participants(uuid, &ParticipantChange::Added { id: participant_id, participant }, expected_version)?;
+        Ok((etag(stored.version), axum::Json(participant_id)))
+    }).await
+}
+
+
+pub async fn update_bill_participant(
+    Path((id, participant_id)): Path<(String, String)>,
+    UserData(data): UserData,
+    headers: HeaderMap,
+    extract::Json(participant): extract::Json<Participant>,
+) -> Result<([(HeaderName, HeaderValue); 1], axum::Json<Uuid>), ApiError> {
+    run_blocking(move || {
+        let uuid = parse_uuid(&id)?;
+        let participant_id = parse_uuid(&participant_id)?;
+        let expected_version = optional_if_match(&headers)?;
+        let stored = data.change_participants(uuid, &ParticipantChange::Updated { id: participant_id, participant }, expected_version)?;
+        Ok((etag(stored.version), axum::Json(participant_id)))
+    }).await
+}
+
+
+pub async fn delete_bill_participant(
+    Path((id, participant_id)): Path<(String, String)>,
+    UserData(data): UserData,
+    headers: HeaderMap,
+) -> Result<([(HeaderName, HeaderValue); 1], axum::Json<Uuid>), ApiError> {
+    run_blocking(move || {
+        let uuid = parse_uuid(&id)?;
+        let participant_id = parse_uuid(&participant_id)?;
+        let expected_version = optional_if_match(&headers)?;
+        let stored = data.change_participants(uuid, &ParticipantChange::Removed { id: participant_id }, expected_version)?;
+        Ok((etag(stored.version), axum::Json(participant_id)))
+    }).await
+}
+
+
+/// Merges one participant into another, who keeps their name, aliases and
+/// id as aliases. Returns the id of whoever is left.
+pub async fn merge_bill_participant(
+    Path((id, participant_id)): Path<(String, String)>,
+    UserData(data): UserData,
+    headers: HeaderMap,
+    extract::Json(body): extract::Json<MergeBody>,
+) -> Result<([(HeaderName, HeaderValue); 1], axum::Json<Uuid>), ApiError> {
+    run_blocking(move || {
+        let uuid = parse_uuid(&id)?;
+        let from = parse_uuid(&participant_id)?;
+        let expected_version = optional_if_match(&headers)?;
+        let stored = data.change_participants(uuid, &ParticipantChange::Merged { from, into: body.into }, expected_version)?;
+        Ok((etag(stored.version), axum::Json(body.into)))
+    }).await
+}
+
+
+pub async fn get_group_participants(
+    Path(id): Path<String>,
+    UserData(data): UserData,
+) -> Result<axum::Json<Participants>, ApiError> {
+    run_blocking(move || {
+        let uuid = parse_uuid(&id)?;
+        let group = data.get_group(uuid)?;
+        Ok(axum::Json(group.participants))
+    }).await
+}
+
+
+pub async fn add_group_participant(
+    Path(id): Path<String>,
+    UserData(data): UserData,
+    extract::Json(participant): extract::Json<Participant>,
+) -> Result<axum::Json<Uuid>, ApiError> {
+    run_blocking(move || {
+        let uuid = parse_uuid(&id)?;
+        let participant_id = Uuid::new_v4();
+        data.change_group_participants(uuid, &ParticipantChange::Added { id: participant_id, participant })?;
+        Ok(axum::Json(participant_id))
+    }).await
+}
+
+
+pub async fn update_group_participant(
+    Path((id, participant_id)): Path<(String, String)>,
+    UserData(data): UserData,
+    extract::Json(participant): extract::Json<Participant>,
+) -> Result<axum::Json<Uuid>, ApiError> {
+    run_blocking(move || {
+        let uuid = parse_uuid(&id)?;
+        let participant_id = parse_uuid(&participant_id)?;
+        data.change_group_participants(uuid, &ParticipantChange::Updated { id: participant_id, participant })?;
+        Ok(axum::Json(participant_id))
+    }).await
+}
+
+
+pub async fn delete_group_participant(
+    Path((id, participant_id)): Path<(String, String)>,
+    UserData(data): UserData,
+) -> Result<axum::Json<Uuid>, ApiError> {
+    run_blocking(move || {
+        let uuid = parse_uuid(&id)?;
+        let participant_id = parse_uuid(&participant_id)?;
+        data.change_group_participants(uuid, &ParticipantChange::Removed { id: participant_id })?;
+        Ok(axum::Json(participant_id))
+    }).await
+}
+
+
+pub async fn merge_group_participant(
+    Path((id, participant_id)): Path<(String, String)>,
+    UserData(data): UserData,
+    extract::Json(body): extract::Json<MergeBody>,
+) -> Result<axum::Json<Uuid>, ApiError> {
+    run_blocking(move || {
+        let uuid = parse_uuid(&id)?;
+        let from = parse_uuid(&participant_id)?;
+        data.change_group_participants(uuid, &ParticipantChange::Merged { from, into: body.into })?;
+        Ok(axum::Json(body.into))
+    }).await
+}
@@ -0,0 +1,70 @@
+use serde::Deserialize;
+use uuid::Uuid;
+use crate::api::auth::UserData;
+use crate::api::error::ApiError;
+use crate::api::extract::{self, Path, Query};
+use crate::api::handlers::run_blocking;
+use crate::data::Data;
+use crate::models::payment::{Payment, PaymentWithId, Reference};
+
+/// Which payments to list. Without either, every payment.
+#[derive(Deserialize)]
+pub struct PaymentParams {
+    bill: Option<Uuid>,
+    group: Option<Uuid>,
+}
+
+fn parse_uuid(id: &str) -> Result<Uuid, ApiError> {
+    Uuid::parse_str(id).map_err(|_| ApiError::InvalidUuid)
+}
+
+/// Every payment, oldest first, or only those towards one bill or group.
+pub async fn get_payments(
+    Query(params): Query<PaymentParams>,
+    UserData(data): UserData,
+) -> Result<axum::Json<Vec<PaymentWithId>>, ApiError> {
+    run_blocking(move || {
+        let mut payments = data.get_payments()?;
+        if let Some(bill) = params.bill {
+            payments.retain(|stored| stored.payment.reference == Some(Reference::Bill(bill)));
+        }
+        if let Some(group) = params.group {
+            payments.retain(|stored| stored.payment.reference == Some(Reference::Group(group)));
+        }
+        Ok(axum::Json(payments))
+    }).await
+}
+
+pub async fn create_payment(
+    UserData(data): UserData,
+    extract::Json(payment): extract::Json<Payment>,
+) -> Result<axum::Json<Uuid>, ApiError> {
+    run_blocking(move || {
+        let res = data.add_payment(&payment)?;
+        Ok(axum::Json(res))
+    }).await
+}
+
+
+pub async fn get_payment(
+    Path(id): Path<String>,
+    UserData(data): UserData,
+) -> Result<axum::Json<Payment>, ApiError> {
+    run_blocking(move || {
+        let uuid = parse_uuid(&id)?;
+        let payment = data.get_payment(uuid)?;
+        Ok(axum::Json(payment))
+    }).await
+}
+
+
+pub async fn delete_payment(
+    Path(id): Path<String>,
+    UserData(data): UserData,
+) -> Result<axum::Json<Uuid>, ApiError> {
+    run_blocking(move || {
+        let uuid = parse_uuid(&id)?;
+        let res = data.delete_payment(uuid)?;
+        Ok(axum::Json(res))
+    }).await
+}
@@ -0,0 +1,31 @@
+use axum::{
+    extract::State,
+    response::IntoResponse,
+    http::StatusCode,
+};
+use std::sync::PoisonError;
+use crate::api::auth::AuthUser;
+use crate::api::error::ApiError;
+use crate::api::extract;
+use crate::config::Config;
+use crate::models::exchange::RateTable;
+
+pub async fn get_rates(State(config): State<Config>) -> impl IntoResponse {
+    let table = config.rates.table.read().unwrap_or_else(PoisonError::into_inner).clone();
+    axum::Json(table)
+}
+
+/// Replaces the whole rate table. Partial updates are not supported, so a
+/// table never mixes rates quoted at different times. The table is shared by
+/// everyone, so only the admins named in the auth config may replace it.
+pub async fn put_rates(
+    AuthUser(user): AuthUser,
+    State(config): State<Config>,
+    extract::Json(table): extract::Json<RateTable>,
+) -> Result<impl IntoResponse, ApiError> {
+    if !config.auth.is_admin(&user) {
+        return Err(ApiError::Forbidden);
+    }
+    *config.rates.table.write().unwrap_or_else(PoisonError::into_inner) = table.clone();
+    Ok((StatusCode::OK, axum::Json(table)))
+}
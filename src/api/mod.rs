@@ -1,2 +1,6 @@
+pub mod auth;
+pub mod error;
+pub mod etag;
+pub mod extract;
 pub mod routes;
 pub mod handlers;
@@ -1,8 +1,14 @@
+use crate::api::handlers::auth_handler;
 use crate::api::handlers::basic_handler;
 use crate::api::handlers::bill_handler;
+use crate::api::handlers::group_handler;
+use crate::api::handlers::member_handler;
+use crate::api::handlers::participant_handler;
+use crate::api::handlers::payment_handler;
+use crate::api::handlers::rates_handler;
 use crate::config::Config;
 use axum::{
-    routing::{get, post},
+    routing::{get, post, put},
     Router,
 };
 
@@ -12,15 +18,77 @@ pub fn routes(config: Config) -> Router {
         .fallback(basic_handler::fallback)
         .route("/",
             get(basic_handler::hello)
+        ).route("/auth/register",
+            post(auth_handler::register)
+        ).route("/auth/login",
+            post(auth_handler::login)
         ).route("/bills",
             get(bill_handler::get_bills_route)
         ).route("/bill/:id",
             get(bill_handler::get_bill_from_id)
                 .delete(bill_handler::delete_bill)
                 .put(bill_handler::update_bill)
+                .patch(bill_handler::patch_bill)
+        ).route("/bill/:id/items",
+            get(bill_handler::get_items)
+                .post(bill_handler::add_item)
+        ).route("/bill/:id/items/:item_id",
+            put(bill_handler::update_item)
+                .delete(bill_handler::delete_item)
+        ).route("/bill/:id/for/:orderer",
+            get(bill_handler::get_bill_for)
+        ).route("/bill/:id/for/:orderer/in/:currency",
+            get(bill_handler::get_settlement_for)
+        ).route("/bill/:id/history",
+            get(bill_handler::get_history)
+        ).route("/bill/:id/revert/:version",
+            post(bill_handler::revert_bill)
+        ).route("/bill/:id/breakdown",
+            get(bill_handler::get_breakdown)
+        ).route("/bill/:id/settlement",
+            get(bill_handler::get_settlement_plan)
+        ).route("/bill/:id/participants",
+            get(participant_handler::get_bill_participants)
+                .post(participant_handler::add_bill_participant)
+        ).route("/bill/:id/participants/:participant_id",
+            put(participant_handler::update_bill_participant)
+                .delete(participant_handler::delete_bill_participant)
+        ).route("/bill/:id/participants/:participant_id/merge",
+            post(participant_handler::merge_bill_participant)
+        ).route("/bill/:id/members",
+            get(member_handler::get_members)
+        ).route("/bill/:id/members/:username",
+            put(member_handler::invite_member)
+                .delete(member_handler::remove_member)
         ).route("/bill/insert",
             post(bill_handler::create_bill)
         ).route("/bill/new",
             post(bill_handler::new_empty_bill)
+        ).route("/groups",
+            get(group_handler::get_groups)
+                .post(group_handler::create_group)
+        ).route("/groups/:id",
+            get(group_handler::get_group)
+                .put(group_handler::update_group)
+                .delete(group_handler::delete_group)
+        ).route("/groups/:id/balances",
+            get(group_handler::get_balances)
+        ).route("/groups/:id/participants",
+            get(participant_handler::get_group_participants)
+                .post(participant_handler::add_group_participant)
+        ).route("/groups/:id/participants/:participant_id",
+            put(participant_handler::update_group_participant)
+                .delete(participant_handler::delete_group_participant)
+        ).route("/groups/:id/participants/:participant_id/merge",
+            post(participant_handler::merge_group_participant)
+        ).route("/payments",
+            get(payment_handler::get_payments)
+                .post(payment_handler::create_payment)
+        ).route("/payments/:id",
+            get(payment_handler::get_payment)
+                .delete(payment_handler::delete_payment)
+        ).route("/rates",
+            get(rates_handler::get_rates)
+                .put(rates_handler::put_rates)
         ).with_state(config)
 }
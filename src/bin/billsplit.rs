@@ -0,0 +1,9 @@
+use clap::Parser;
+use billsplit::cli::{self, Cli};
+
+pub fn main() {
+    if let Err(err) = cli::run(Cli::parse()) {
+        eprintln!("billsplit: {}", err);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,406 @@
+//! The `billsplit` command-line tool, which manages bills directly in the
+//! configured data provider without going through the HTTP server.
+pub mod table;
+
+use std::fmt;
+use std::io::{self, Write};
+use clap::{Parser, Subcommand};
+use serde::Serialize;
+use uuid::Uuid;
+use crate::config::{Config, ConfigError};
+use crate::config::settings::ConfigArgs;
+use crate::data::{Data, DataError, DataProvider};
+use crate::data::query::BillQuery;
+use crate::models::bill::Bill;
+use crate::models::breakdown::Share;
+use crate::models::currency::{Currency, Money, MoneyError};
+use crate::models::item::{LineItem, Portion};
+use crate::models::participant::Participants;
+use table::Table;
+
+/// Manages bills without a running server.
+#[derive(Debug, Parser)]
+#[command(name = "billsplit", version)]
+pub struct Cli {
+    /// Print JSON instead of tables.
+    #[arg(long, global = true)]
+    pub json: bool,
+    #[command(flatten)]
+    pub config: ConfigArgs,
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Create, list, show and delete bills.
+    #[command(subcommand)]
+    Bill(BillCommand),
+    /// Add, edit and remove line items.
+    #[command(subcommand)]
+    Item(ItemCommand),
+    /// Show what everyone owes on a bill.
+    Split {
+        bill: Uuid,
+        /// Only show this person's share.
+        #[arg(long = "for", value_name = "PERSON")]
+        orderer: Option<String>,
+    },
+    /// Give every bill, group and payment that has no owner, such as those
+    /// stored before there were accounts, to a user.
+    AssignOwner { username: String },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum BillCommand {
+    Create {
+        name: String,
+        #[arg(long, default_value_t = Currency::default())]
+        currency: Currency,
+        /// The total actually paid, e.g. 54.20, if it differs from the items.
+        #[arg(long)]
+        total: Option<String>,
+    },
+    List,
+    Show {
+        bill: Uuid,
+    },
+    Delete {
+        bill: Uuid,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ItemCommand {
+    Add {
+        bill: Uuid,
+        name: String,
+        /// The price in the bill's currency, e.g. 12.50.
+        price: String,
+        /// Who ordered the item; repeat to share it equally. Without it the
+        /// item is shared by everyone on the bill.
+        #[arg(long = "for", value_name = "PERSON")]
+        orderers: Vec<String>,
+    },
+    Edit {
+        bill: Uuid,
+        item: u16,
+        #[arg(long)]
+        name: Option<String>,
+        #[arg(long)]
+        price: Option<String>,
+        /// Replaces who ordered the item; repeat to share it equally.
+        #[arg(long = "for", value_name = "PERSON")]
+        orderers: Vec<String>,
+        /// Make the item shared by everyone on the bill.
+        #[arg(long, conflicts_with = "orderers")]
+        everyone: bool,
+    },
+    Rm {
+        bill: Uuid,
+        item: u16,
+    },
+}
+
+#[derive(Debug)]
+pub enum CliError {
+    Config(ConfigError),
+    Data(DataError),
+    Money(MoneyError),
+    Io(io::Error),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CliError::Config(err) => write!(f, "{}", err),
+            CliError::Data(err) => write!(f, "{}", err),
+            CliError::Money(err) => write!(f, "{}", err),
+            CliError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+impl From<ConfigError> for CliError {
+    fn from(err: ConfigError) -> Self {
+        CliError::Config(err)
+    }
+}
+
+impl From<DataError> for CliError {
+    fn from(err: DataError) -> Self {
+        CliError::Data(err)
+    }
+}
+
+impl From<MoneyError> for CliError {
+    fn from(err: MoneyError) -> Self {
+        CliError::Money(err)
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(err: io::Error) -> Self {
+        CliError::Io(err)
+    }
+}
+
+/// Loads the configuration from `cli` and runs its command.
+pub fn run(cli: Cli) -> Result<(), CliError> {
+    let config = Config::load(&cli.config)?;
+    if let DataProvider::Memory(_) = config.data.provider {
+        eprintln!("billsplit: using the memory provider, so nothing is kept after this command");
+    }
+    let data = config.data.provider.with_actor(std::env::var("USER").ok());
+    execute(&data, &cli.command, cli.json, &mut io::stdout().lock())
+}
+
+/// Runs `command` against `data`, writing the result to `out`.
+pub fn execute(data: &impl Data, command: &Command, json: bool, out: &mut impl Write) -> Result<(), CliError> {
+    match command {
+        Command::Bill(BillCommand::Create { name, currency, total }) => {
+            let mut bill = Bill::new(name.clone(), *currency);
+            if let Some(total) = total {
+                bill.set_total(Some(Money::parse(total, *currency)?)).map_err(DataError::from)?;
+            }
+            let id = data.add_bill(&bill)?;
+            print(out, json, &id, || id.to_string())
+        },
+        Command::Bill(BillCommand::List) => {
+            let bills = data.query_bills(&BillQuery::default())?.bills;
+            print(out, json, &bills, || {
+                let mut table = Table::new(&["ID", "NAME", "ITEMS", "TOTAL"]);
+                for bill in &bills {
+                    table.row(vec![
+                        bill.id.to_string(),
+                        bill.bill.name.clone(),
+                        bill.bill.items().len().to_string(),
+                        show_total(&bill.bill),
+                    ]);
+                }
+                table.to_string()
+            })
+        },
+        Command::Bill(BillCommand::Show { bill: id }) => {
+            let bill = data.get_bill(*id)?;
+            print(out, json, &bill, || show_bill(&bill, bill.participants()))
+        },
+        Command::Bill(BillCommand::Delete { bill }) => {
+            let id = data.delete_bill(*bill, None)?;
+            print(out, json, &id, || format!("Deleted {}", id))
+        },
+        Command::Item(ItemCommand::Add { bill, name, price, orderers }) => {
+            let currency = data.get_bill(*bill)?.currency();
+            let item = LineItem::shared(
+                name.clone(),
+                Money::parse(price, currency)?,
+                orderers.iter().map(|orderer| (orderer.clone(), Portion::Equal)).collect(),
+            );
+            let (item_id, _) = data.add_item(*bill, &item, None)?;
+            print(out, json, &item_id, || item_id.to_string())
+        },
+        Command::Item(ItemCommand::Edit { bill, item: item_id, name, price, orderers, everyone }) => {
+            let existing = data.get_bill(*bill)?;
+            let currency = existing.currency();
+            let mut item = existing.items().get(item_id).cloned().ok_or(DataError::ItemNotFound)?;
+            if let Some(name) = name {
+                item.name = name.clone();
+            }
+            if let Some(price) = price {
+                item.price = Money::parse(price, currency)?;
+            }
+            if *everyone {
+                item.orderers.clear();
+            } else if !orderers.is_empty() {
+                item.orderers = orderers.iter().map(|orderer| (orderer.clone(), Portion::Equal)).collect();
+            }
+            let (item_id, _) = data.update_item(*bill, *item_id, &item, None)?;
+            print(out, json, &item, || format!("Updated item {}", item_id))
+        },
+        Command::Item(ItemCommand::Rm { bill, item }) => {
+            let (item_id, _) = data.delete_item(*bill, *item, None)?;
+            print(out, json, &item_id, || format!("Removed item {}", item_id))
+        },
+        Command::Split { bill, orderer: Some(orderer) } => {
+            let bill = data.get_bill(*bill)?;
+            // shares are keyed by participant id once someone is registered
+            let orderer = bill.participants().reference(orderer);
+            let share = bill.get_breakdown()?.shares.into_iter().find(|share| share.orderer == orderer);
+            let share = match share {
+                Some(share) => share,
+                None => Share {
+                    bill: bill.get_bill_for(&orderer)?,
+                    orderer,
+                    owed: Money::zero(bill.currency()),
+                    rounding: 0,
+                },
+            };
+            let participants = bill.participants();
+            print(out, json, &share, || {
+                let name = participants.name(&share.orderer);
+                format!("{}\n{} owes {}", show_bill(&share.bill, participants), name, share.owed)
+            })
+        },
+        Command::Split { bill, orderer: None } => {
+            let bill = data.get_bill(*bill)?;
+            let breakdown = bill.get_breakdown()?;
+            print(out, json, &breakdown, || {
+                let mut table = Table::new(&["PERSON", "OWES"]);
+                for share in &breakdown.shares {
+                    table.row(vec![bill.participants().name(&share.orderer).to_string(), share.owed.to_string()]);
+                }
+                if !breakdown.unassigned.is_zero() {
+                    table.row(vec!["(unassigned)".to_string(), breakdown.unassigned.to_string()]);
+                }
+                table.row(vec!["TOTAL".to_string(), breakdown.total.to_string()]);
+                table.to_string()
+            })
+        },
+        Command::AssignOwner { username } => {
+            let assigned = data.assign_owner(username)?;
+            print(out, json, &assigned, || {
+                format!(
+                    "Gave {}: bills {}, groups {}, payments {}",
+                    username, assigned.bills, assigned.groups, assigned.payments,
+                )
+            })
+        },
+    }
+}
+
+fn print<T: Serialize>(out: &mut impl Write, json: bool, value: &T, text: impl FnOnce() -> String) -> Result<(), CliError> {
+    if json {
+        let value = serde_json::to_string_pretty(value).map_err(io::Error::from)?;
+        writeln!(out, "{}", value)?;
+    } else {
+        writeln!(out, "{}", text())?;
+    }
+    Ok(())
+}
+
+/// `bill` as a table, with orderers shown by their names in `participants`.
+fn show_bill(bill: &Bill, participants: &Participants) -> String {
+    let mut ids = bill.items().keys().collect::<Vec<&u16>>();
+    ids.sort();
+    let mut table = Table::new(&["ID", "ITEM", "PRICE", "ORDERED BY"]);
+    for id in ids {
+        let item = &bill.items()[id];
+        let orderers = match item.orderers.is_empty() {
+            true => "everyone".to_string(),
+            false => item.orderers.keys()
+                .map(|orderer| participants.name(orderer))
+                .collect::<Vec<&str>>()
+                .join(", "),
+        };
+        table.row(vec![id.to_string(), item.name.clone(), item.price.to_string(), orderers]);
+    }
+    for adjustment in bill.adjustments() {
+        let amount = adjustment.kind.amount().map_or("".to_string(), |amount| amount.to_string());
+        table.row(vec!["".to_string(), adjustment.name.clone(), amount, "".to_string()]);
+    }
+    format!("{}\n{}\nTotal: {}", bill.name, table, show_total(bill))
+}
+
+/// The bill's total, or why it could not be worked out.
+fn show_total(bill: &Bill) -> String {
+    match bill.calculate_total() {
+        Ok(total) => total.to_string(),
+        Err(err) => err.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::memory::Memory;
+    use crate::models::participant::{Participant, ParticipantChange};
+    use crate::models::user::User;
+
+    fn run_command(data: &Memory, args: &[&str]) -> Result<String, CliError> {
+        let cli = Cli::try_parse_from(std::iter::once("billsplit").chain(args.iter().copied())).unwrap();
+        let mut out = Vec::new();
+        execute(data, &cli.command, cli.json, &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_bill_and_items() {
+        let data = Memory::new();
+        let id = run_command(&data, &["bill", "create", "dinner", "--currency", "EUR"]).unwrap();
+        let id = id.trim();
+        assert_eq!(run_command(&data, &["item", "add", id, "pasta", "12.50", "--for", "alice"]).unwrap(), "0\n");
+        assert_eq!(run_command(&data, &["item", "add", id, "wine", "20", "--for", "alice", "--for", "bob"]).unwrap(), "1\n");
+        run_command(&data, &["item", "edit", id, "0", "--price", "13.00"]).unwrap();
+
+        let bill = data.get_bill(Uuid::parse_str(id).unwrap()).unwrap();
+        assert_eq!(bill.items()[&0].price, Money::new(1300, Currency::EUR));
+        assert_eq!(bill.items()[&1].orderers.len(), 2);
+
+        let split = run_command(&data, &["split", id]).unwrap();
+        assert_eq!(split, "PERSON  OWES\nalice   23.00 EUR\nbob     10.00 EUR\nTOTAL   33.00 EUR\n");
+
+        let share = run_command(&data, &["--json", "split", id, "--for", "bob"]).unwrap();
+        let share: serde_json::Value = serde_json::from_str(&share).unwrap();
+        assert_eq!(share["owed"]["amount"], "10.00");
+
+        run_command(&data, &["item", "rm", id, "1"]).unwrap();
+        assert!(matches!(
+            run_command(&data, &["item", "rm", id, "1"]),
+            Err(CliError::Data(DataError::ItemNotFound))
+        ));
+        assert!(matches!(
+            run_command(&data, &["item", "add", id, "soup", "1.234"]),
+            Err(CliError::Money(MoneyError::InvalidAmount(_)))
+        ));
+
+        assert!(run_command(&data, &["bill", "list"]).unwrap().contains("dinner"));
+        run_command(&data, &["bill", "delete", id]).unwrap();
+        assert!(matches!(
+            run_command(&data, &["bill", "show", id]),
+            Err(CliError::Data(DataError::BillNotFound))
+        ));
+    }
+    #[test]
+    fn test_split_with_participants() {
+        let data = Memory::new();
+        let id = run_command(&data, &["bill", "create", "dinner"]).unwrap();
+        let id = id.trim();
+        run_command(&data, &["item", "add", id, "pasta", "12.50", "--for", "alice"]).unwrap();
+        run_command(&data, &["item", "add", id, "wine", "20", "--for", "alice", "--for", "bob"]).unwrap();
+
+        let mut bob = Participant::new("Bob".to_string());
+        bob.aliases = vec!["Robert".to_string()];
+        let change = ParticipantChange::Added { id: Uuid::new_v4(), participant: bob };
+        data.change_participants(Uuid::parse_str(id).unwrap(), &change, None).unwrap();
+
+        // shares are ordered by participant id, so only the rows are compared
+        let split = run_command(&data, &["split", id]).unwrap();
+        let mut rows = split.lines().skip(1).collect::<Vec<&str>>();
+        rows.sort();
+        assert_eq!(rows, vec!["Bob     10.00 USD", "TOTAL   32.50 USD", "alice   22.50 USD"]);
+        for person in ["Bob", "robert"] {
+            let share = run_command(&data, &["split", id, "--for", person]).unwrap();
+            assert!(share.contains("wine  10.00 USD  Bob"), "{}", share);
+            assert!(share.ends_with("Bob owes 10.00 USD\n"), "{}", share);
+        }
+        let share = run_command(&data, &["split", id, "--for", "carol"]).unwrap();
+        assert!(share.ends_with("carol owes 0.00 USD\n"), "{}", share);
+    }
+
+    #[test]
+    fn test_assign_owner() {
+        let data = Memory::new();
+        let id = run_command(&data, &["bill", "create", "dinner"]).unwrap();
+        let id = Uuid::parse_str(id.trim()).unwrap();
+        assert!(matches!(
+            run_command(&data, &["assign-owner", "alice"]),
+            Err(CliError::Data(DataError::UserNotFound))
+        ));
+        data.add_user(&User { username: "alice".to_string(), password_hash: "hash".to_string() }).unwrap();
+        let out = run_command(&data, &["assign-owner", "alice"]).unwrap();
+        assert_eq!(out, "Gave alice: bills 1, groups 0, payments 0\n");
+        assert!(data.with_user("alice".to_string()).get_bill(id).is_ok());
+    }
+}
@@ -0,0 +1,56 @@
+use std::fmt;
+
+/// A plain-text table with columns padded to line up.
+pub struct Table {
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(headers: &[&str]) -> Self {
+        Self {
+            rows: vec![headers.iter().map(|header| header.to_string()).collect()],
+        }
+    }
+
+    pub fn row(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let columns = self.rows.iter().map(Vec::len).max().unwrap_or(0);
+        let widths = (0..columns).map(|column| {
+            self.rows.iter()
+                .filter_map(|row| row.get(column))
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or(0)
+        }).collect::<Vec<usize>>();
+
+        for (index, row) in self.rows.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            let line = row.iter().zip(&widths)
+                .map(|(cell, width)| format!("{:width$}", cell, width = width))
+                .collect::<Vec<String>>()
+                .join("  ");
+            write!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let mut table = Table::new(&["NAME", "OWES"]);
+        table.row(vec!["alice".to_string(), "1.00 USD".to_string()]);
+        table.row(vec!["bo".to_string(), "".to_string()]);
+        assert_eq!(table.to_string(), "NAME   OWES\nalice  1.00 USD\nbo");
+    }
+}
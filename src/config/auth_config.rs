@@ -0,0 +1,49 @@
+use chrono::Duration;
+use jsonwebtoken::{DecodingKey, EncodingKey};
+use uuid::Uuid;
+
+/// How long a token from `/auth/login` lasts unless told otherwise.
+pub const DEFAULT_TOKEN_TTL_SECONDS: u64 = 24 * 60 * 60;
+
+/// The shortest secret tokens may be signed with, in bytes.
+pub const MIN_SECRET_LENGTH: usize = 32;
+
+/// How the API signs and checks the tokens it issues.
+#[derive(Clone)]
+pub struct AuthConfig {
+    pub encoding_key: EncodingKey,
+    pub decoding_key: DecodingKey,
+    pub token_ttl: Duration,
+    /// Usernames that may change what every user shares, such as the
+    /// exchange rate table.
+    pub admins: Vec<String>,
+}
+
+impl AuthConfig {
+    /// Signs tokens with a random secret, so they stop working when the
+    /// server restarts. Set a secret to keep people signed in across
+    /// restarts, or to run more than one server.
+    pub fn new() -> Self {
+        let secret = [Uuid::new_v4().into_bytes(), Uuid::new_v4().into_bytes()].concat();
+        Self::with_secret(&secret)
+    }
+
+    pub fn with_secret(secret: &[u8]) -> Self {
+        Self {
+            encoding_key: EncodingKey::from_secret(secret),
+            decoding_key: DecodingKey::from_secret(secret),
+            token_ttl: Duration::seconds(DEFAULT_TOKEN_TTL_SECONDS as i64),
+            admins: vec![],
+        }
+    }
+
+    pub fn is_admin(&self, username: &str) -> bool {
+        self.admins.iter().any(|admin| admin == username)
+    }
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,5 +1,8 @@
-use crate::data::DataProvider;
+use crate::data::{DataError, DataProvider};
 use crate::data::memory::Memory;
+use crate::data::events::EventStore;
+use crate::data::sqlite::Sqlite;
+use std::path::Path;
 
 #[derive(Clone)]
 pub struct DataConfig {
@@ -12,4 +15,25 @@ impl DataConfig {
             provider: DataProvider::Memory(Memory::new())
         }
     }
+
+    /// Stores bills in the SQLite database at `path`, creating it if needed.
+    pub fn sqlite(path: impl AsRef<Path>) -> Result<Self, DataError> {
+        Ok(Self {
+            provider: DataProvider::Sqlite(Sqlite::open(path)?)
+        })
+    }
+
+    /// Stores bills as a log of events in the SQLite database at `path`,
+    /// creating it if needed.
+    pub fn events(path: impl AsRef<Path>) -> Result<Self, DataError> {
+        Ok(Self {
+            provider: DataProvider::Events(EventStore::open(path)?)
+        })
+    }
+}
+
+impl Default for DataConfig {
+    fn default() -> Self {
+        Self::new()
+    }
 }
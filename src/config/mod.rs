@@ -1,10 +1,50 @@
+pub mod auth_config;
 pub mod data_config;
+pub mod rates_config;
 pub mod server_config;
+pub mod settings;
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use crate::data::DataError;
+use settings::{ConfigArgs, ProviderKind, Settings};
+
+/// The file read when no configuration file is given, if it exists.
+const DEFAULT_CONFIG_FILE: &str = "billsplit.toml";
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read { path: PathBuf, message: String },
+    Parse { path: PathBuf, message: String },
+    InvalidEnv { name: String, value: String, message: String },
+    Invalid(String),
+    Data(DataError),
+    Rates(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Read { path, message } => write!(f, "Could not read {}: {}", path.display(), message),
+            ConfigError::Parse { path, message } => write!(f, "Invalid configuration in {}: {}", path.display(), message),
+            ConfigError::InvalidEnv { name, value, message } => {
+                write!(f, "Invalid {} {:?}: {}", name, value, message)
+            },
+            ConfigError::Invalid(message) => write!(f, "Invalid configuration: {}", message),
+            ConfigError::Data(err) => write!(f, "Could not open the data provider: {}", err),
+            ConfigError::Rates(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
 
 #[derive(Clone)]
 pub struct Config {
     pub data: data_config::DataConfig,
-    pub app: server_config::ServerConfig
+    pub app: server_config::ServerConfig,
+    pub rates: rates_config::RatesConfig,
+    pub auth: auth_config::AuthConfig,
 }
 
 
@@ -12,7 +52,153 @@ impl Config {
     pub fn new() -> Config {
         Config {
             data: data_config::DataConfig::new(),
-            app: server_config::ServerConfig::new()
+            app: server_config::ServerConfig::new(),
+            rates: rates_config::RatesConfig::new(),
+            auth: auth_config::AuthConfig::new(),
+        }
+    }
+
+    /// Builds the configuration from, in increasing order of precedence, the
+    /// defaults, a TOML file, `BILLSPLIT_*` environment variables and `args`.
+    pub fn load(args: &ConfigArgs) -> Result<Config, ConfigError> {
+        Self::load_with(args, |name| std::env::var(name).ok())
+    }
+
+    /// `load`, reading environment variables through `var`.
+    pub fn load_with(args: &ConfigArgs, var: impl Fn(&str) -> Option<String>) -> Result<Config, ConfigError> {
+        let file = match args.config.clone().or_else(|| var("BILLSPLIT_CONFIG").map(PathBuf::from)) {
+            Some(path) => Settings::from_file(&path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => Settings::from_file(Path::new(DEFAULT_CONFIG_FILE))?,
+            None => Settings::default(),
+        };
+        let settings = file
+            .merge(Settings::from_env(var)?)
+            .merge(args.settings());
+        Self::from_settings(settings)
+    }
+
+    /// Fills in defaults for anything `settings` leaves out, then checks the
+    /// result and opens the data provider and rate table.
+    pub fn from_settings(settings: Settings) -> Result<Config, ConfigError> {
+        let mut config = Config::new();
+
+        if let Some(host) = settings.server.host {
+            if host.trim().is_empty() || host.contains(char::is_whitespace) {
+                return Err(ConfigError::Invalid(format!("host {:?} is not a host name or address", host)));
+            }
+            config.app.host = host;
+        }
+        if let Some(port) = settings.server.port {
+            config.app.port = port;
+        }
+
+        let provider = settings.data.provider.unwrap_or(match settings.data.path {
+            Some(_) => ProviderKind::Sqlite,
+            None => ProviderKind::Memory,
+        });
+        config.data = match (provider, settings.data.path) {
+            (ProviderKind::Memory, _) => data_config::DataConfig::new(),
+            (ProviderKind::Sqlite, Some(path)) => data_config::DataConfig::sqlite(path).map_err(ConfigError::Data)?,
+            (ProviderKind::Sqlite, None) => {
+                return Err(ConfigError::Invalid("the sqlite provider needs a database path".to_string()));
+            },
+            (ProviderKind::Events, Some(path)) => data_config::DataConfig::events(path).map_err(ConfigError::Data)?,
+            (ProviderKind::Events, None) => {
+                return Err(ConfigError::Invalid("the events provider needs a database path".to_string()));
+            },
+        };
+
+        if let Some(path) = settings.rates.file {
+            config.rates = rates_config::RatesConfig::from_file(path).map_err(ConfigError::Rates)?;
+        }
+
+        if let Some(secret) = settings.auth.secret {
+            if secret.len() < auth_config::MIN_SECRET_LENGTH {
+                return Err(ConfigError::Invalid(format!(
+                    "the auth secret must be at least {} bytes",
+                    auth_config::MIN_SECRET_LENGTH,
+                )));
+            }
+            config.auth = auth_config::AuthConfig::with_secret(secret.as_bytes());
+        }
+        if let Some(seconds) = settings.auth.token_ttl {
+            if seconds == 0 || seconds > i64::MAX as u64 / 1000 {
+                return Err(ConfigError::Invalid(format!("a token cannot last {} seconds", seconds)));
+            }
+            config.auth.token_ttl = chrono::Duration::seconds(seconds as i64);
+        }
+        if let Some(admins) = settings.auth.admins {
+            config.auth.admins = admins;
         }
+        Ok(config)
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::DataProvider;
+
+    #[test]
+    fn test_load_defaults() {
+        let config = Config::load_with(&ConfigArgs::default(), |_| None).unwrap();
+        assert_eq!((config.app.host.as_str(), config.app.port), ("0.0.0.0", 3000));
+        assert!(matches!(config.data.provider, DataProvider::Memory(_)));
+    }
+
+    #[test]
+    fn test_load_layers() {
+        let path = std::env::temp_dir().join(format!("billsplit-config-{}.toml", std::process::id()));
+        std::fs::write(&path, "[server]\nhost = \"127.0.0.1\"\nport = 8000\n").unwrap();
+        let args = ConfigArgs {
+            config: Some(path.clone()),
+            port: Some(8002),
+            ..ConfigArgs::default()
+        };
+        let env = |name: &str| match name {
+            "BILLSPLIT_PORT" => Some("8001".to_string()),
+            "BILLSPLIT_HOST" => Some("localhost".to_string()),
+            _ => None,
+        };
+        let config = Config::load_with(&args, env).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((config.app.host.as_str(), config.app.port), ("localhost", 8002));
+
+        let err = Config::load_with(&args, env).err().unwrap();
+        assert!(matches!(err, ConfigError::Read { .. }));
+    }
+
+    #[test]
+    fn test_invalid_settings() {
+        let mut settings = Settings::default();
+        settings.data.provider = Some(ProviderKind::Sqlite);
+        assert_eq!(
+            Config::from_settings(settings).err().unwrap().to_string(),
+            "Invalid configuration: the sqlite provider needs a database path"
+        );
+
+        let mut settings = Settings::default();
+        settings.data.provider = Some(ProviderKind::Events);
+        assert_eq!(
+            Config::from_settings(settings).err().unwrap().to_string(),
+            "Invalid configuration: the events provider needs a database path"
+        );
+
+        let mut settings = Settings::default();
+        settings.server.host = Some(" ".to_string());
+        assert!(Config::from_settings(settings).is_err());
+
+        let mut settings = Settings::default();
+        settings.auth.secret = Some("short".to_string());
+        assert_eq!(
+            Config::from_settings(settings).err().unwrap().to_string(),
+            "Invalid configuration: the auth secret must be at least 32 bytes"
+        );
     }
 }
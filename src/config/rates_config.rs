@@ -0,0 +1,57 @@
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use crate::models::exchange::RateTable;
+
+/// The exchange rates used to settle bills in other currencies. Shared
+/// between handlers so that rates uploaded through the API apply everywhere.
+#[derive(Clone)]
+pub struct RatesConfig {
+    pub table: Arc<RwLock<RateTable>>,
+}
+
+impl RatesConfig {
+    pub fn new() -> Self {
+        Self {
+            table: Arc::new(RwLock::new(RateTable::empty())),
+        }
+    }
+
+    /// Loads a rate table from a JSON file, in the same format `PUT /rates`
+    /// accepts.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)
+            .map_err(|err| format!("Could not read {}: {}", path.display(), err))?;
+        let table = serde_json::from_str::<RateTable>(&contents)
+            .map_err(|err| format!("Invalid rates in {}: {}", path.display(), err))?;
+        Ok(Self {
+            table: Arc::new(RwLock::new(table)),
+        })
+    }
+}
+
+impl Default for RatesConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::currency::{Currency, Money};
+
+    #[test]
+    fn test_from_file() {
+        let path = std::env::temp_dir().join(format!("billsplit-rates-{}.json", std::process::id()));
+        fs::write(&path, r#"{"as_of": "2024-03-01T12:00:00Z", "rates": [{"from": "EUR", "to": "USD", "rate": "1.1"}]}"#).unwrap();
+        let config = RatesConfig::from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let table = config.table.read().unwrap();
+        let conversion = table.convert(Money::new(1000, Currency::EUR), Currency::USD).unwrap();
+        assert_eq!(conversion.converted, Money::new(1100, Currency::USD));
+        assert!(RatesConfig::from_file(&path).is_err());
+    }
+}
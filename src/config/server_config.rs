@@ -12,3 +12,9 @@ impl ServerConfig {
         }
     }
 }
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
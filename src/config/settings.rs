@@ -0,0 +1,288 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use clap::{Args, ValueEnum};
+use serde::Deserialize;
+use crate::config::ConfigError;
+
+/// Which `DataProvider` to store bills in.
+#[derive(Debug, Deserialize, Clone, Copy, Eq, PartialEq, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderKind {
+    Memory,
+    Sqlite,
+    Events,
+}
+
+impl FromStr for ProviderKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "memory" => Ok(ProviderKind::Memory),
+            "sqlite" => Ok(ProviderKind::Sqlite),
+            "events" => Ok(ProviderKind::Events),
+            _ => Err("expected memory, sqlite or events".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Default, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ServerSettings {
+    pub host: Option<String>,
+    pub port: Option<u16>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct DataSettings {
+    pub provider: Option<ProviderKind>,
+    /// The database file, for the sqlite and events providers.
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RatesSettings {
+    /// A JSON rate table to load at startup.
+    pub file: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AuthSettings {
+    /// What tokens are signed with. Tokens outlive a restart only if this
+    /// is set; it is never read from the command line, where other users
+    /// could see it.
+    pub secret: Option<String>,
+    /// How many seconds a token lasts.
+    pub token_ttl: Option<u64>,
+    /// Usernames that may replace the exchange rate table. In the
+    /// environment, a comma-separated list.
+    pub admins: Option<Vec<String>>,
+}
+
+/// One layer of configuration, such as a TOML file or the environment.
+/// Everything is optional, so a layer only overrides what it sets.
+///
+/// A TOML file looks like:
+///
+/// ```toml
+/// [server]
+/// host = "127.0.0.1"
+/// port = 8080
+///
+/// [data]
+/// provider = "sqlite"
+/// path = "bills.db"
+///
+/// [rates]
+/// file = "rates.json"
+///
+/// [auth]
+/// secret = "at least 32 bytes of something random"
+/// token_ttl = 86400
+/// admins = ["alice"]
+/// ```
+#[derive(Debug, Deserialize, Clone, Default, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    #[serde(default)]
+    pub server: ServerSettings,
+    #[serde(default)]
+    pub data: DataSettings,
+    #[serde(default)]
+    pub rates: RatesSettings,
+    #[serde(default)]
+    pub auth: AuthSettings,
+}
+
+impl Settings {
+    pub fn from_toml(path: &Path, contents: &str) -> Result<Self, ConfigError> {
+        toml::from_str(contents).map_err(|err| ConfigError::Parse {
+            path: path.to_path_buf(),
+            message: err.message().to_string(),
+        })
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let contents = fs::read_to_string(path).map_err(|err| ConfigError::Read {
+            path: path.to_path_buf(),
+            message: err.to_string(),
+        })?;
+        Self::from_toml(path, &contents)
+    }
+
+    /// Reads the `BILLSPLIT_*` variables through `var`, which returns a
+    /// variable's value if it is set.
+    pub fn from_env(var: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        Ok(Self {
+            server: ServerSettings {
+                host: var("BILLSPLIT_HOST"),
+                port: parse_env(&var, "BILLSPLIT_PORT")?,
+            },
+            data: DataSettings {
+                provider: parse_env(&var, "BILLSPLIT_DATA_PROVIDER")?,
+                path: var("BILLSPLIT_DATABASE").map(PathBuf::from),
+            },
+            rates: RatesSettings {
+                file: var("BILLSPLIT_RATES_FILE").map(PathBuf::from),
+            },
+            auth: AuthSettings {
+                secret: var("BILLSPLIT_AUTH_SECRET"),
+                token_ttl: parse_env(&var, "BILLSPLIT_TOKEN_TTL")?,
+                admins: var("BILLSPLIT_ADMINS").map(|admins| {
+                    admins.split(',')
+                        .map(str::trim)
+                        .filter(|admin| !admin.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                }),
+            },
+        })
+    }
+
+    /// These settings with anything set in `other` taking precedence.
+    pub fn merge(self, other: Settings) -> Settings {
+        Settings {
+            server: ServerSettings {
+                host: other.server.host.or(self.server.host),
+                port: other.server.port.or(self.server.port),
+            },
+            data: DataSettings {
+                provider: other.data.provider.or(self.data.provider),
+                path: other.data.path.or(self.data.path),
+            },
+            rates: RatesSettings {
+                file: other.rates.file.or(self.rates.file),
+            },
+            auth: AuthSettings {
+                secret: other.auth.secret.or(self.auth.secret),
+                token_ttl: other.auth.token_ttl.or(self.auth.token_ttl),
+                admins: other.auth.admins.or(self.auth.admins),
+            },
+        }
+    }
+}
+
+fn parse_env<T>(var: &impl Fn(&str) -> Option<String>, name: &str) -> Result<Option<T>, ConfigError>
+where
+    T: FromStr,
+    T::Err: ToString,
+{
+    match var(name) {
+        Some(value) => value.parse().map(Some).map_err(|err: T::Err| ConfigError::InvalidEnv {
+            name: name.to_string(),
+            value,
+            message: err.to_string(),
+        }),
+        None => Ok(None),
+    }
+}
+
+/// Command-line flags for configuration, shared by every binary.
+#[derive(Debug, Args, Clone, Default)]
+pub struct ConfigArgs {
+    /// TOML configuration file [env: BILLSPLIT_CONFIG] [default: billsplit.toml, if present]
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+    /// Address to listen on [env: BILLSPLIT_HOST] [default: 0.0.0.0]
+    #[arg(long)]
+    pub host: Option<String>,
+    /// Port to listen on [env: BILLSPLIT_PORT] [default: 3000]
+    #[arg(long)]
+    pub port: Option<u16>,
+    /// Where to store bills [env: BILLSPLIT_DATA_PROVIDER] [default: sqlite if a database is given, else memory]
+    #[arg(long, value_enum)]
+    pub data_provider: Option<ProviderKind>,
+    /// SQLite database file, for the sqlite and events providers [env: BILLSPLIT_DATABASE]
+    #[arg(long, value_name = "FILE")]
+    pub database: Option<PathBuf>,
+    /// JSON exchange rate table to load [env: BILLSPLIT_RATES_FILE]
+    #[arg(long, value_name = "FILE")]
+    pub rates_file: Option<PathBuf>,
+}
+
+impl ConfigArgs {
+    pub fn settings(&self) -> Settings {
+        Settings {
+            server: ServerSettings {
+                host: self.host.clone(),
+                port: self.port,
+            },
+            data: DataSettings {
+                provider: self.data_provider,
+                path: self.database.clone(),
+            },
+            rates: RatesSettings {
+                file: self.rates_file.clone(),
+            },
+            auth: AuthSettings::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars = vars.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect::<HashMap<String, String>>();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn test_from_toml() {
+        let settings = Settings::from_toml(
+            Path::new("billsplit.toml"),
+            "[server]\nport = 8080\n\n[data]\nprovider = \"sqlite\"\npath = \"bills.db\"\n",
+        ).unwrap();
+        assert_eq!(settings.server, ServerSettings { host: None, port: Some(8080) });
+        assert_eq!(settings.data.provider, Some(ProviderKind::Sqlite));
+        assert_eq!(settings.data.path, Some(PathBuf::from("bills.db")));
+
+        let err = Settings::from_toml(Path::new("billsplit.toml"), "[server]\nport = \"high\"\n").unwrap_err();
+        assert!(matches!(err, ConfigError::Parse { .. }));
+        assert!(Settings::from_toml(Path::new("billsplit.toml"), "[server]\nhots = \"x\"\n").is_err());
+    }
+
+    #[test]
+    fn test_from_env() {
+        let settings = Settings::from_env(env(&[
+            ("BILLSPLIT_HOST", "127.0.0.1"),
+            ("BILLSPLIT_PORT", "8081"),
+            ("BILLSPLIT_DATA_PROVIDER", "memory"),
+        ])).unwrap();
+        assert_eq!(settings.server.host, Some("127.0.0.1".to_string()));
+        assert_eq!(settings.server.port, Some(8081));
+        assert_eq!(settings.data.provider, Some(ProviderKind::Memory));
+
+        let settings = Settings::from_env(env(&[("BILLSPLIT_ADMINS", "alice, bob,")])).unwrap();
+        assert_eq!(settings.auth.admins, Some(vec!["alice".to_string(), "bob".to_string()]));
+
+        let err = Settings::from_env(env(&[("BILLSPLIT_PORT", "70000")])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid BILLSPLIT_PORT \"70000\": number too large to fit in target type"
+        );
+        assert!(Settings::from_env(env(&[("BILLSPLIT_DATA_PROVIDER", "postgres")])).is_err());
+    }
+
+    #[test]
+    fn test_merge() {
+        let file = Settings {
+            server: ServerSettings { host: Some("file".to_string()), port: Some(1) },
+            ..Settings::default()
+        };
+        let cli = Settings {
+            server: ServerSettings { host: None, port: Some(2) },
+            ..Settings::default()
+        };
+        let settings = file.merge(cli);
+        assert_eq!(settings.server, ServerSettings { host: Some("file".to_string()), port: Some(2) });
+    }
+}
@@ -0,0 +1,151 @@
+//! Who may see and change what. A store acting for a user (see
+//! `Data::with_user`) only reaches bills the user owns or was invited to,
+//! and groups and payments they recorded; one acting for nobody reaches
+//! everything, as the command line does.
+//!
+//! Anything a user may not see is reported as not found, so that nobody can
+//! tell which ids are taken.
+//!
+//! Bills, groups and payments stored before there were accounts, or added
+//! from the command line, have no owner, so no user reaches them until
+//! `Data::assign_owner` gives them to someone.
+use uuid::Uuid;
+use crate::data::DataError;
+use crate::models::bill::BillWithId;
+use crate::models::payment::{Payment, Reference};
+use crate::models::validate::MAX_MEMBERS;
+
+/// Whether `user` may see and change `stored`.
+pub(crate) fn can_see(stored: &BillWithId, user: &Option<String>) -> bool {
+    match user {
+        None => true,
+        Some(user) => stored.owner.as_ref() == Some(user) || stored.members.contains(user),
+    }
+}
+
+/// Whether `user` may see and change what `owner` recorded.
+pub(crate) fn owns(owner: &Option<String>, user: &Option<String>) -> bool {
+    user.is_none() || owner == user
+}
+
+/// Fails unless `user` owns `stored`, e.g. to delete it.
+pub(crate) fn check_owner(stored: &BillWithId, user: &Option<String>) -> Result<(), DataError> {
+    match owns(&stored.owner, user) {
+        true => Ok(()),
+        false => Err(DataError::Forbidden),
+    }
+}
+
+/// Whether `user` may see a payment `owner` recorded: whoever recorded it
+/// may, and so may anyone who can see the bill or group it is towards.
+pub(crate) fn can_see_payment(
+    owner: &Option<String>,
+    payment: &Payment,
+    user: &Option<String>,
+    bill_visible: impl Fn(Uuid) -> Result<bool, DataError>,
+    group_visible: impl Fn(Uuid) -> Result<bool, DataError>,
+) -> Result<bool, DataError> {
+    if owns(owner, user) {
+        return Ok(true);
+    }
+    match payment.reference {
+        Some(Reference::Bill(id)) => bill_visible(id),
+        Some(Reference::Group(id)) => group_visible(id),
+        None => Ok(false),
+    }
+}
+
+/// Fails unless the store acts for nobody, the only one that may give away
+/// what nobody owns.
+pub(crate) fn check_nobody(user: &Option<String>) -> Result<(), DataError> {
+    match user {
+        None => Ok(()),
+        Some(_) => Err(DataError::Forbidden),
+    }
+}
+
+/// Makes `username` the owner of `stored`, which has none.
+pub(crate) fn assign(stored: &mut BillWithId, username: &str) {
+    stored.members.retain(|member| member != username);
+    stored.owner = Some(username.to_string());
+}
+
+/// Shares `stored` with `username`. Only the owner may; `exists` tells
+/// whether there is an account by that name.
+pub(crate) fn invite(
+    stored: &mut BillWithId,
+    username: &str,
+    user: &Option<String>,
+    exists: impl FnOnce(&str) -> Result<bool, DataError>,
+) -> Result<(), DataError> {
+    check_owner(stored, user)?;
+    if stored.owner.as_deref() == Some(username) || stored.members.iter().any(|member| member == username) {
+        return Err(DataError::Conflict(format!("{} can already see the bill", username)));
+    }
+    if stored.members.len() >= MAX_MEMBERS {
+        return Err(DataError::Invalid(format!("A bill can be shared with at most {} people", MAX_MEMBERS)));
+    }
+    if !exists(username)? {
+        return Err(DataError::UserNotFound);
+    }
+    stored.members.push(username.to_string());
+    Ok(())
+}
+
+/// Stops sharing `stored` with `username`. The owner may remove anyone, and
+/// anyone may remove themselves.
+pub(crate) fn uninvite(stored: &mut BillWithId, username: &str, user: &Option<String>) -> Result<(), DataError> {
+    if user.as_deref() != Some(username) {
+        check_owner(stored, user)?;
+    }
+    let index = stored.members.iter().position(|member| member == username).ok_or(DataError::MemberNotFound)?;
+    stored.members.remove(index);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::new_stored;
+    use crate::models::bill::Bill;
+    use crate::models::currency::Currency;
+
+    fn user(name: &str) -> Option<String> {
+        Some(name.to_string())
+    }
+
+    #[test]
+    fn test_invite() {
+        let mut stored = new_stored(Uuid::new_v4(), Bill::new("dinner".to_string(), Currency::USD));
+        stored.owner = user("alice");
+        assert!(can_see(&stored, &None));
+        assert!(can_see(&stored, &user("alice")));
+        assert!(!can_see(&stored, &user("bob")));
+
+        let exists = |username: &str| Ok(username != "nobody");
+        assert_eq!(invite(&mut stored, "carol", &user("bob"), exists), Err(DataError::Forbidden));
+        assert_eq!(invite(&mut stored, "nobody", &user("alice"), exists), Err(DataError::UserNotFound));
+        invite(&mut stored, "bob", &user("alice"), exists).unwrap();
+        assert!(can_see(&stored, &user("bob")));
+        assert!(matches!(invite(&mut stored, "bob", &user("alice"), exists), Err(DataError::Conflict(_))));
+        assert!(matches!(invite(&mut stored, "alice", &None, exists), Err(DataError::Conflict(_))));
+
+        // members can see the bill, but only the owner can share it
+        assert_eq!(check_owner(&stored, &user("bob")), Err(DataError::Forbidden));
+        assert_eq!(invite(&mut stored, "carol", &user("bob"), exists), Err(DataError::Forbidden));
+        assert_eq!(uninvite(&mut stored, "carol", &user("alice")), Err(DataError::MemberNotFound));
+        uninvite(&mut stored, "bob", &user("bob")).unwrap();
+        assert!(!can_see(&stored, &user("bob")));
+    }
+
+    #[test]
+    fn test_assign() {
+        let mut stored = new_stored(Uuid::new_v4(), Bill::new("dinner".to_string(), Currency::USD));
+        stored.members = vec!["alice".to_string(), "bob".to_string()];
+        assert_eq!(check_nobody(&user("alice")), Err(DataError::Forbidden));
+        check_nobody(&None).unwrap();
+        assign(&mut stored, "alice");
+        assert_eq!(stored.owner, user("alice"));
+        assert_eq!(stored.members, vec!["bob".to_string()]);
+    }
+}
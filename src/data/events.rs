@@ -0,0 +1,1005 @@
+//! Bills stored as an append-only log of what happened to them. A bill's
+//! current state is rebuilt by folding its events, starting from its latest
+//! snapshot.
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::data::{
+    check_payment, check_version, new_stored, prepare_bill, prepare_group, now, sort_payments, touch, Assigned, Data,
+    DataError,
+};
+use crate::data::access::{self, can_see, can_see_payment, check_owner, owns};
+use crate::data::history::{self, Change, Revision};
+use crate::data::sqlite::{
+    delete_group, from_json, invalid_column, load_group, load_groups, load_user, migrate, save_group, save_user, to_json,
+};
+use crate::models::bill::{Bill, BillError, BillWithId};
+use crate::models::group::{Group, GroupWithId};
+use crate::models::item::LineItem;
+use crate::models::participant::ParticipantChange;
+use crate::models::patch::{BillPatch, PatchError};
+use crate::models::payment::{Payment, PaymentWithId, Reference};
+use crate::models::user::User;
+
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE events (
+        bill_id TEXT NOT NULL,
+        version INTEGER NOT NULL,
+        at INTEGER NOT NULL,
+        actor TEXT,
+        kind TEXT NOT NULL,
+        event TEXT NOT NULL,
+        PRIMARY KEY (bill_id, version)
+    );
+    CREATE TABLE snapshots (
+        bill_id TEXT NOT NULL,
+        version INTEGER NOT NULL,
+        state TEXT NOT NULL,
+        PRIMARY KEY (bill_id, version)
+    );",
+    // Groups are kept as they are, in the same tables as the sqlite provider.
+    "CREATE TABLE groups (
+        id TEXT PRIMARY KEY NOT NULL,
+        name TEXT NOT NULL
+    );
+    CREATE TABLE group_members (
+        group_id TEXT NOT NULL REFERENCES groups(id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        PRIMARY KEY (group_id, position)
+    );
+    CREATE TABLE group_bills (
+        group_id TEXT NOT NULL REFERENCES groups(id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        bill_id TEXT NOT NULL,
+        PRIMARY KEY (group_id, position)
+    );
+    CREATE INDEX group_bills_bill_id ON group_bills (bill_id);",
+    // Payments have a log of their own, one row per event.
+    "CREATE TABLE payment_events (
+        payment_id TEXT NOT NULL,
+        at INTEGER NOT NULL,
+        actor TEXT,
+        kind TEXT NOT NULL,
+        event TEXT NOT NULL,
+        PRIMARY KEY (payment_id, kind)
+    );",
+    "CREATE TABLE group_participants (
+        group_id TEXT NOT NULL REFERENCES groups(id) ON DELETE CASCADE,
+        id TEXT NOT NULL,
+        name TEXT NOT NULL,
+        aliases TEXT NOT NULL,
+        PRIMARY KEY (group_id, id)
+    );",
+    // Bills and payments record their owner in the events that create them;
+    // those logged before have none until `assign_owner` gives them one.
+    "ALTER TABLE groups ADD COLUMN owner TEXT;
+    CREATE TABLE users (
+        username TEXT PRIMARY KEY NOT NULL,
+        password_hash TEXT NOT NULL
+    );",
+];
+
+/// How many events a bill gets between snapshots unless told otherwise.
+pub const DEFAULT_SNAPSHOT_INTERVAL: u64 = 100;
+
+/// Something that happened to a bill. Externally tagged, since an internal
+/// tag would make serde buffer the bill and lose its integer item ids.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Event {
+    BillCreated {
+        bill: Bill,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        owner: Option<String>,
+    },
+    BillReplaced { bill: Bill },
+    BillPatched { patch: BillPatch },
+    ItemAdded { item_id: u16, item: LineItem },
+    ItemUpdated { item_id: u16, item: LineItem },
+    ItemDeleted { item_id: u16 },
+    BillReverted { to: u64, bill: Bill },
+    ParticipantsChanged { change: ParticipantChange },
+    MemberInvited { username: String },
+    MemberRemoved { username: String },
+    OwnerAssigned { username: String },
+    BillDeleted,
+}
+
+impl Event {
+    fn kind(&self) -> &'static str {
+        match self {
+            Event::BillCreated { .. } => "bill_created",
+            Event::BillReplaced { .. } => "bill_replaced",
+            Event::BillPatched { .. } => "bill_patched",
+            Event::ItemAdded { .. } => "item_added",
+            Event::ItemUpdated { .. } => "item_updated",
+            Event::ItemDeleted { .. } => "item_deleted",
+            Event::BillReverted { .. } => "bill_reverted",
+            Event::ParticipantsChanged { .. } => "participants_changed",
+            Event::MemberInvited { .. } => "member_invited",
+            Event::MemberRemoved { .. } => "member_removed",
+            Event::OwnerAssigned { .. } => "owner_assigned",
+            Event::BillDeleted => "bill_deleted",
+        }
+    }
+
+    fn change(&self) -> Option<Change> {
+        match self {
+            Event::BillCreated { .. } => Some(Change::Created),
+            Event::BillReplaced { .. } => Some(Change::Replaced),
+            Event::BillPatched { .. } => Some(Change::Patched),
+            Event::ItemAdded { item_id, .. } => Some(Change::ItemAdded { item_id: *item_id }),
+            Event::ItemUpdated { item_id, .. } => Some(Change::ItemUpdated { item_id: *item_id }),
+            Event::ItemDeleted { item_id } => Some(Change::ItemDeleted { item_id: *item_id }),
+            Event::BillReverted { to, .. } => Some(Change::Reverted { to: *to }),
+            Event::ParticipantsChanged { change } => Some(Change::from(change)),
+            Event::MemberInvited { username } => Some(Change::MemberInvited { username: username.clone() }),
+            Event::MemberRemoved { username } => Some(Change::MemberRemoved { username: username.clone() }),
+            Event::OwnerAssigned { username } => Some(Change::OwnerAssigned { username: username.clone() }),
+            Event::BillDeleted => None,
+        }
+    }
+}
+
+/// Something that happened to a payment. A payment is recorded once and
+/// deleted at most once; it never changes in between, though one recorded
+/// by nobody may be given an owner once.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentEvent {
+    PaymentRecorded {
+        payment: Payment,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        owner: Option<String>,
+    },
+    OwnerAssigned { username: String },
+    PaymentDeleted,
+}
+
+impl PaymentEvent {
+    fn kind(&self) -> &'static str {
+        match self {
+            PaymentEvent::PaymentRecorded { .. } => "payment_recorded",
+            PaymentEvent::OwnerAssigned { .. } => "owner_assigned",
+            PaymentEvent::PaymentDeleted => "payment_deleted",
+        }
+    }
+}
+
+/// An event as it was logged: the version of the bill it made, when, and
+/// who caused it.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Recorded {
+    pub version: u64,
+    pub at: DateTime<Utc>,
+    pub actor: Option<String>,
+    pub event: Event,
+}
+
+/// Applies one event to a bill's state; `None` is a bill that does not
+/// exist yet or has been deleted. Events were checked before they were
+/// logged, so one that does not apply means the log is corrupt.
+pub fn fold(id: Uuid, state: Option<BillWithId>, recorded: &Recorded) -> Result<Option<BillWithId>, DataError> {
+    let corrupt = || DataError::Storage(format!("event {} of bill {} does not apply", recorded.version, id));
+    let mut stored = match (state, &recorded.event) {
+        (None, Event::BillCreated { bill, owner }) => {
+            let mut stored = new_stored(id, bill.clone());
+            stored.owner = owner.clone();
+            stored.bill.resolve_participants().map_err(|_| corrupt())?;
+            stored.created_at = recorded.at;
+            stored.updated_at = recorded.at;
+            return Ok(Some(stored));
+        },
+        (Some(_), Event::BillDeleted) => return Ok(None),
+        (Some(stored), _) if stored.version + 1 == recorded.version => stored,
+        _ => return Err(corrupt()),
+    };
+    match &recorded.event {
+        Event::BillReplaced { bill } | Event::BillReverted { bill, .. } => stored.bill = bill.clone(),
+        Event::BillPatched { patch } => stored.bill = patch.apply(&stored.bill).map_err(|_| corrupt())?,
+        Event::ItemAdded { item_id, item } => {
+            if stored.bill.add_item(item.clone()).ok() != Some(*item_id) {
+                return Err(corrupt());
+            }
+        },
+        Event::ItemUpdated { item_id, item } => {
+            stored.bill.update_item(*item_id, item.clone()).map_err(|_| corrupt())?;
+        },
+        Event::ItemDeleted { item_id } => {
+            stored.bill.delete_item(*item_id).map_err(|_| corrupt())?;
+        },
+        Event::ParticipantsChanged { change } => {
+            stored.bill.change_participants(change.clone()).map_err(|_| corrupt())?;
+        },
+        Event::MemberInvited { username } => {
+            if stored.members.contains(username) {
+                return Err(corrupt());
+            }
+            stored.members.push(username.clone());
+        },
+        Event::MemberRemoved { username } => {
+            let index = stored.members.iter().position(|member| member == username).ok_or_else(corrupt)?;
+            stored.members.remove(index);
+        },
+        Event::OwnerAssigned { username } => {
+            if stored.owner.is_some() {
+                return Err(corrupt());
+            }
+            access::assign(&mut stored, username);
+        },
+        Event::BillCreated { .. } | Event::BillDeleted => return Err(corrupt()),
+    }
+    // Writes resolve references after the change, so replaying does too.
+    stored.bill.resolve_participants().map_err(|_| corrupt())?;
+    stored.version = recorded.version;
+    stored.updated_at = recorded.at;
+    Ok(Some(stored))
+}
+
+/// Bills stored as events in a SQLite database. Every change appends an
+/// event, and nothing is ever rewritten or removed: deleting a bill appends
+/// `BillDeleted`. Every `snapshot_interval` versions the bill's state is
+/// saved too, so loading it only folds the events since.
+#[derive(Clone)]
+pub struct EventStore {
+    connection: Arc<Mutex<Connection>>,
+    actor: Option<String>,
+    user: Option<String>,
+    snapshot_interval: u64,
+}
+
+impl EventStore {
+    /// Opens or creates the event log at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, DataError> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    /// An event log that only lives as long as this provider.
+    pub fn in_memory() -> Result<Self, DataError> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(mut connection: Connection) -> Result<Self, DataError> {
+        connection.pragma_update(None, "foreign_keys", true)?;
+        migrate(&mut connection, MIGRATIONS)?;
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+            actor: None,
+            user: None,
+            snapshot_interval: DEFAULT_SNAPSHOT_INTERVAL,
+        })
+    }
+
+    /// This store, snapshotting bills every `interval` versions.
+    pub fn with_snapshot_interval(mut self, interval: u64) -> Self {
+        self.snapshot_interval = interval.max(1);
+        self
+    }
+
+    /// Locks the connection. Every change runs in a transaction that rolls
+    /// back if it is dropped part way, so a poisoned lock is still safe to use.
+    fn connection(&self) -> MutexGuard<'_, Connection> {
+        self.connection.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Changes a copy of the bill and, if that works, logs the event
+    /// `change` returns for it. Returns the change's result and the changed
+    /// bill.
+    fn append<T, E>(
+        &self,
+        id: Uuid,
+        expected_version: Option<u64>,
+        change: impl FnOnce(&mut Bill) -> Result<(T, Event), E>,
+    ) -> Result<(T, BillWithId), DataError>
+    where
+        DataError: From<E>,
+    {
+        self.append_stored(id, expected_version, |_, stored| change(&mut stored.bill))
+    }
+
+    /// `append`, for changes to who the bill is shared with as well as to
+    /// the bill itself. `change` may read other tables through the
+    /// connection it is given.
+    fn append_stored<T, E>(
+        &self,
+        id: Uuid,
+        expected_version: Option<u64>,
+        change: impl FnOnce(&Connection, &mut BillWithId) -> Result<(T, Event), E>,
+    ) -> Result<(T, BillWithId), DataError>
+    where
+        DataError: From<E>,
+    {
+        let mut connection = self.connection();
+        let transaction = connection.transaction()?;
+        let mut stored = load_visible_bill(&transaction, id, &self.user)?;
+        check_version(expected_version, stored.version)?;
+        let (res, event) = change(&transaction, &mut stored)?;
+        match event {
+            // there are no bills to cascade from, so groups let go of it here
+            Event::BillDeleted => {
+                transaction.execute("DELETE FROM group_bills WHERE bill_id = ?1", params![id.to_string()])?;
+            },
+            _ => prepare_bill(&mut stored.bill)?,
+        }
+        touch(&mut stored);
+        // a deleted bill has no state to snapshot
+        let snapshot = event != Event::BillDeleted && stored.version % self.snapshot_interval == 0;
+        save_event(&transaction, id, &Recorded {
+            version: stored.version,
+            at: stored.updated_at,
+            actor: self.actor.clone(),
+            event,
+        })?;
+        if snapshot {
+            transaction.execute(
+                "INSERT INTO snapshots (bill_id, version, state) VALUES (?1, ?2, ?3)",
+                params![id.to_string(), stored.version, to_json(&stored)],
+            )?;
+        }
+        transaction.commit()?;
+        Ok((res, stored))
+    }
+}
+
+fn save_event(connection: &Connection, id: Uuid, recorded: &Recorded) -> rusqlite::Result<()> {
+    connection.execute(
+        "INSERT INTO events (bill_id, version, at, actor, kind, event) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            id.to_string(),
+            recorded.version,
+            recorded.at.timestamp_micros(),
+            recorded.actor,
+            recorded.event.kind(),
+            to_json(&recorded.event),
+        ],
+    )?;
+    Ok(())
+}
+
+/// The bill's events after `version`, in order.
+fn load_events(connection: &Connection, id: Uuid, after_version: u64) -> rusqlite::Result<Vec<Recorded>> {
+    let mut statement = connection.prepare(
+        "SELECT version, at, actor, event FROM events WHERE bill_id = ?1 AND version > ?2 ORDER BY version"
+    )?;
+    let rows = statement.query_map(params![id.to_string(), after_version], |row| {
+        let at = row.get::<_, i64>(1)?;
+        Ok(Recorded {
+            version: row.get(0)?,
+            at: DateTime::from_timestamp_micros(at).ok_or_else(|| invalid_column(1, &at.to_string()))?,
+            actor: row.get(2)?,
+            event: from_json(3, row.get(3)?)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// The bill as of its latest event, from its latest snapshot and the events
+/// since.
+fn load_bill(connection: &Connection, id: Uuid) -> Result<Option<BillWithId>, DataError> {
+    let snapshot = connection.query_row(
+        "SELECT state FROM snapshots WHERE bill_id = ?1 ORDER BY version DESC LIMIT 1",
+        params![id.to_string()],
+        |row| from_json::<BillWithId>(0, row.get(0)?),
+    ).optional()?;
+    let after_version = snapshot.as_ref().map_or(0, |stored| stored.version);
+    let mut state = snapshot;
+    for recorded in load_events(connection, id, after_version)? {
+        state = fold(id, state, &recorded)?;
+    }
+    Ok(state)
+}
+
+fn load_visible_bill(connection: &Connection, id: Uuid, user: &Option<String>) -> Result<BillWithId, DataError> {
+    load_bill(connection, id)?
+        .filter(|stored| can_see(stored, user))
+        .ok_or(DataError::BillNotFound)
+}
+
+fn save_payment_event(connection: &Connection, id: Uuid, actor: &Option<String>, event: &PaymentEvent) -> rusqlite::Result<()> {
+    connection.execute(
+        "INSERT INTO payment_events (payment_id, at, actor, kind, event) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![id.to_string(), now().timestamp_micros(), actor, event.kind(), to_json(event)],
+    )?;
+    Ok(())
+}
+
+/// Every payment recorded and not deleted since that `user` may see,
+/// optionally only the one with id `only`, in the order they were recorded,
+/// each with whoever owns it. The log keeps what each payment first
+/// referred to; references to bills or groups deleted since are dropped
+/// here.
+fn load_payments(
+    connection: &Connection,
+    only: Option<Uuid>,
+    user: &Option<String>,
+) -> Result<Vec<(Option<String>, PaymentWithId)>, DataError> {
+    let mut statement = connection.prepare(
+        "SELECT recorded.payment_id, recorded.event, assigned.event FROM payment_events recorded
+        LEFT JOIN payment_events assigned
+            ON assigned.payment_id = recorded.payment_id AND assigned.kind = 'owner_assigned'
+        WHERE recorded.kind = 'payment_recorded' AND (?1 IS NULL OR recorded.payment_id = ?1)
+        AND recorded.payment_id NOT IN (SELECT payment_id FROM payment_events WHERE kind = 'payment_deleted')
+        ORDER BY recorded.rowid"
+    )?;
+    let rows = statement.query_map(params![only.map(|id| id.to_string())], |row| {
+        let id = row.get::<_, String>(0)?;
+        let id = Uuid::parse_str(&id).map_err(|_| invalid_column(0, &id))?;
+        let assigned = row.get::<_, Option<String>>(2)?.map(|event| from_json::<PaymentEvent>(2, event)).transpose()?;
+        Ok((id, from_json::<PaymentEvent>(1, row.get(1)?)?, assigned))
+    })?.collect::<rusqlite::Result<Vec<(Uuid, PaymentEvent, Option<PaymentEvent>)>>>()?;
+
+    let mut payments = Vec::with_capacity(rows.len());
+    for (id, event, assigned) in rows {
+        let PaymentEvent::PaymentRecorded { mut payment, mut owner } = event else {
+            return Err(DataError::Storage(format!("payment {} was not recorded as one", id)));
+        };
+        match assigned {
+            Some(PaymentEvent::OwnerAssigned { username }) if owner.is_none() => owner = Some(username),
+            Some(_) => return Err(DataError::Storage(format!("payment {} was given an owner it cannot have", id))),
+            None => {},
+        }
+        let exists = match payment.reference {
+            Some(Reference::Bill(bill)) => bill_exists(connection, bill)?,
+            Some(Reference::Group(group)) => load_group(connection, group, &None)?.is_some(),
+            None => true,
+        };
+        if !exists {
+            payment.reference = None;
+        }
+        let visible = can_see_payment(
+            &owner,
+            &payment,
+            user,
+            |bill| Ok(load_visible_bill(connection, bill, user).is_ok()),
+            |group| Ok(load_group(connection, group, user)?.is_some()),
+        )?;
+        if visible {
+            payments.push((owner, PaymentWithId { id, payment }));
+        }
+    }
+    Ok(payments)
+}
+
+/// Whether the bill was created and has not been deleted since.
+fn bill_exists(connection: &Connection, id: Uuid) -> Result<bool, DataError> {
+    Ok(connection.query_row(
+        "SELECT EXISTS (SELECT 1 FROM events WHERE bill_id = ?1 AND kind = 'bill_created')
+        AND NOT EXISTS (SELECT 1 FROM events WHERE bill_id = ?1 AND kind = 'bill_deleted')",
+        params![id.to_string()],
+        |row| row.get(0),
+    )?)
+}
+
+impl Data for EventStore {
+    fn with_actor(&self, actor: Option<String>) -> Self {
+        Self {
+            actor,
+            ..self.clone()
+        }
+    }
+
+    fn with_user(&self, user: String) -> Self {
+        Self {
+            actor: Some(user.clone()),
+            user: Some(user),
+            ..self.clone()
+        }
+    }
+
+    fn add_bill(&self, bill: &Bill) -> Result<Uuid, DataError> {
+        let mut bill = bill.clone();
+        prepare_bill(&mut bill)?;
+        let connection = self.connection();
+        let id = Uuid::new_v4();
+        let stored = new_stored(id, bill);
+        save_event(&connection, id, &Recorded {
+            version: stored.version,
+            at: stored.created_at,
+            actor: self.actor.clone(),
+            event: Event::BillCreated { bill: stored.bill.clone(), owner: self.user.clone() },
+        })?;
+        Ok(id)
+    }
+
+    fn delete_bill(&self, id: Uuid, expected_version: Option<u64>) -> Result<Uuid, DataError> {
+        self.append_stored(id, expected_version, |_, stored| {
+            check_owner(stored, &self.user)?;
+            Ok::<_, DataError>(((), Event::BillDeleted))
+        })?;
+        Ok(id)
+    }
+
+    fn get_versioned_bill(&self, id: Uuid) -> Result<BillWithId, DataError> {
+        let connection = self.connection();
+        load_visible_bill(&connection, id, &self.user)
+    }
+
+    fn get_bills(&self) -> Result<Vec<BillWithId>, DataError> {
+        let connection = self.connection();
+        let mut statement = connection.prepare(
+            "SELECT bill_id FROM events WHERE kind = 'bill_created'
+            AND bill_id NOT IN (SELECT bill_id FROM events WHERE kind = 'bill_deleted')
+            ORDER BY at, bill_id"
+        )?;
+        let ids = statement.query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        let mut bills = Vec::with_capacity(ids.len());
+        for id in ids {
+            let id = Uuid::parse_str(&id).map_err(|err| DataError::Storage(err.to_string()))?;
+            if let Some(stored) = load_bill(&connection, id)?.filter(|stored| can_see(stored, &self.user)) {
+                bills.push(stored);
+            }
+        }
+        Ok(bills)
+    }
+
+    fn update_bill(&self, id: Uuid, bill: &Bill, expected_version: Option<u64>) -> Result<u64, DataError> {
+        let ((), stored) = self.append(id, expected_version, |existing_bill| {
+            *existing_bill = bill.clone();
+            Ok::<_, DataError>(((), Event::BillReplaced { bill: bill.clone() }))
+        })?;
+        Ok(stored.version)
+    }
+
+    fn patch_bill(&self, id: Uuid, patch: &BillPatch, expected_version: Option<u64>) -> Result<BillWithId, DataError> {
+        let ((), stored) = self.append(id, expected_version, |bill| {
+            *bill = patch.apply(bill)?;
+            Ok::<_, PatchError>(((), Event::BillPatched { patch: patch.clone() }))
+        })?;
+        Ok(stored)
+    }
+
+    fn add_item(&self, bill_id: Uuid, item: &LineItem, expected_version: Option<u64>) -> Result<(u16, u64), DataError> {
+        self.append(bill_id, expected_version, |bill| {
+            let item_id = bill.add_item(item.clone())?;
+            Ok::<_, BillError>((item_id, Event::ItemAdded { item_id, item: item.clone() }))
+        }).map(|(item_id, stored)| (item_id, stored.version))
+    }
+
+    fn get_items(&self, bill_id: Uuid) -> Result<HashMap<u16, LineItem>, DataError> {
+        self.get_bill(bill_id).map(|bill| bill.items().clone())
+    }
+
+    fn update_item(&self, bill_id: Uuid, item_id: u16, item: &LineItem, expected_version: Option<u64>) -> Result<(u16, u64), DataError> {
+        self.append(bill_id, expected_version, |bill| {
+            let item_id = bill.update_item(item_id, item.clone())?;
+            Ok::<_, BillError>((item_id, Event::ItemUpdated { item_id, item: item.clone() }))
+        }).map(|(item_id, stored)| (item_id, stored.version))
+    }
+
+    fn delete_item(&self, bill_id: Uuid, item_id: u16, expected_version: Option<u64>) -> Result<(u16, u64), DataError> {
+        self.append(bill_id, expected_version, |bill| {
+            let item_id = bill.delete_item(item_id)?;
+            Ok::<_, BillError>((item_id, Event::ItemDeleted { item_id }))
+        }).map(|(item_id, stored)| (item_id, stored.version))
+    }
+
+    /// Folds every event from the start, ignoring snapshots.
+    fn get_history(&self, id: Uuid) -> Result<Vec<Revision>, DataError> {
+        let connection = self.connection();
+        let mut state = None;
+        let mut revisions = vec![];
+        for recorded in load_events(&connection, id, 0)? {
+            state = fold(id, state, &recorded)?;
+            if let (Some(stored), Some(change)) = (&state, recorded.event.change()) {
+                revisions.push(Revision::record(stored, &recorded.actor, change));
+            }
+        }
+        if !state.is_some_and(|stored| can_see(&stored, &self.user)) {
+            return Err(DataError::BillNotFound);
+        }
+        Ok(history::link(revisions))
+    }
+
+    fn revert_bill(&self, id: Uuid, version: u64, expected_version: Option<u64>) -> Result<BillWithId, DataError> {
+        let revision = self.get_history(id)?.into_iter()
+            .find(|revision| revision.version == version)
+            .ok_or(DataError::RevisionNotFound)?;
+        let ((), stored) = self.append(id, expected_version, |bill| {
+            *bill = history::restore(bill, &revision);
+            Ok::<_, DataError>(((), Event::BillReverted { to: version, bill: bill.clone() }))
+        })?;
+        Ok(stored)
+    }
+
+    fn change_participants(&self, id: Uuid, change: &ParticipantChange, expected_version: Option<u64>) -> Result<BillWithId, DataError> {
+        let ((), stored) = self.append(id, expected_version, |bill| {
+            bill.change_participants(change.clone())?;
+            Ok::<_, BillError>(((), Event::ParticipantsChanged { change: change.clone() }))
+        })?;
+        Ok(stored)
+    }
+
+    fn invite_member(&self, id: Uuid, username: &str, expected_version: Option<u64>) -> Result<BillWithId, DataError> {
+        let ((), stored) = self.append_stored(id, expected_version, |connection, stored| {
+            access::invite(stored, username, &self.user, |username| match load_user(connection, username) {
+                Ok(_) => Ok(true),
+                Err(DataError::UserNotFound) => Ok(false),
+                Err(err) => Err(err),
+            })?;
+            Ok::<_, DataError>(((), Event::MemberInvited { username: username.to_string() }))
+        })?;
+        Ok(stored)
+    }
+
+    fn remove_member(&self, id: Uuid, username: &str, expected_version: Option<u64>) -> Result<BillWithId, DataError> {
+        let ((), stored) = self.append_stored(id, expected_version, |_, stored| {
+            access::uninvite(stored, username, &self.user)?;
+            Ok::<_, DataError>(((), Event::MemberRemoved { username: username.to_string() }))
+        })?;
+        Ok(stored)
+    }
+
+    fn add_group(&self, group: &Group) -> Result<Uuid, DataError> {
+        let mut connection = self.connection();
+        let transaction = connection.transaction()?;
+        let group = prepare_group(group, |bill| Ok(load_visible_bill(&transaction, bill, &self.user).is_ok()))?;
+        let id = Uuid::new_v4();
+        save_group(&transaction, id, &self.user, &group)?;
+        transaction.commit()?;
+        Ok(id)
+    }
+
+    fn get_group(&self, id: Uuid) -> Result<Group, DataError> {
+        let connection = self.connection();
+        load_group(&connection, id, &self.user)?.ok_or(DataError::GroupNotFound)
+    }
+
+    fn get_groups(&self) -> Result<Vec<GroupWithId>, DataError> {
+        let connection = self.connection();
+        Ok(load_groups(&connection, &self.user)?)
+    }
+
+    fn update_group(&self, id: Uuid, group: &Group) -> Result<Uuid, DataError> {
+        let mut connection = self.connection();
+        let transaction = connection.transaction()?;
+        load_group(&transaction, id, &self.user)?.ok_or(DataError::GroupNotFound)?;
+        let group = prepare_group(group, |bill| Ok(load_visible_bill(&transaction, bill, &self.user).is_ok()))?;
+        save_group(&transaction, id, &self.user, &group)?;
+        transaction.commit()?;
+        Ok(id)
+    }
+
+    fn delete_group(&self, id: Uuid) -> Result<Uuid, DataError> {
+        let connection = self.connection();
+        match delete_group(&connection, id, &self.user)? {
+            true => Ok(id),
+            false => Err(DataError::GroupNotFound),
+        }
+    }
+
+    fn add_payment(&self, payment: &Payment) -> Result<Uuid, DataError> {
+        let mut connection = self.connection();
+        let transaction = connection.transaction()?;
+        check_payment(
+            payment,
+            |bill| Ok(load_visible_bill(&transaction, bill, &self.user).ok().map(|stored| stored.bill.currency())),
+            |group| Ok(load_group(&transaction, group, &self.user)?.is_some()),
+        )?;
+        let id = Uuid::new_v4();
+        let event = PaymentEvent::PaymentRecorded { payment: payment.clone(), owner: self.user.clone() };
+        save_payment_event(&transaction, id, &self.actor, &event)?;
+        transaction.commit()?;
+        Ok(id)
+    }
+
+    fn get_payment(&self, id: Uuid) -> Result<Payment, DataError> {
+        let connection = self.connection();
+        load_payments(&connection, Some(id), &self.user)?.pop()
+            .map(|(_, stored)| stored.payment)
+            .ok_or(DataError::PaymentNotFound)
+    }
+
+    fn get_payments(&self) -> Result<Vec<PaymentWithId>, DataError> {
+        let connection = self.connection();
+        let mut payments = load_payments(&connection, None, &self.user)?.into_iter()
+            .map(|(_, stored)| stored)
+            .collect::<Vec<PaymentWithId>>();
+        sort_payments(&mut payments);
+        Ok(payments)
+    }
+
+    fn delete_payment(&self, id: Uuid) -> Result<Uuid, DataError> {
+        let mut connection = self.connection();
+        let transaction = connection.transaction()?;
+        let (owner, _) = load_payments(&transaction, Some(id), &self.user)?.pop().ok_or(DataError::PaymentNotFound)?;
+        if !owns(&owner, &self.user) {
+            return Err(DataError::Forbidden);
+        }
+        save_payment_event(&transaction, id, &self.actor, &PaymentEvent::PaymentDeleted)?;
+        transaction.commit()?;
+        Ok(id)
+    }
+
+    fn add_user(&self, user: &User) -> Result<(), DataError> {
+        save_user(&self.connection(), user)
+    }
+
+    fn get_user(&self, username: &str) -> Result<User, DataError> {
+        load_user(&self.connection(), username)
+    }
+
+    fn assign_owner(&self, username: &str) -> Result<Assigned, DataError> {
+        access::check_nobody(&self.user)?;
+        self.get_user(username)?;
+        let bills = self.get_bills()?.into_iter()
+            .filter(|stored| stored.owner.is_none())
+            .map(|stored| stored.id)
+            .collect::<Vec<Uuid>>();
+        for id in &bills {
+            self.append_stored(*id, None, |_, stored| {
+                access::assign(stored, username);
+                Ok::<_, DataError>(((), Event::OwnerAssigned { username: username.to_string() }))
+            })?;
+        }
+
+        let mut connection = self.connection();
+        let transaction = connection.transaction()?;
+        let groups = transaction.execute("UPDATE groups SET owner = ?1 WHERE owner IS NULL", params![username])?;
+        let payments = load_payments(&transaction, None, &None)?.into_iter()
+            .filter(|(owner, _)| owner.is_none())
+            .map(|(_, stored)| stored.id)
+            .collect::<Vec<Uuid>>();
+        let event = PaymentEvent::OwnerAssigned { username: username.to_string() };
+        for id in &payments {
+            save_payment_event(&transaction, *id, &self.actor, &event)?;
+        }
+        transaction.commit()?;
+        Ok(Assigned { bills: bills.len(), groups, payments: payments.len() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use crate::data::INITIAL_VERSION;
+    use crate::models::currency::{Currency, Money};
+    use crate::models::participant::Participant;
+
+    fn usd(amount: i64) -> Money {
+        Money::new(amount, Currency::USD)
+    }
+
+    fn event_kinds(data: &EventStore, id: Uuid) -> Vec<String> {
+        let connection = data.connection();
+        let mut statement = connection.prepare("SELECT kind FROM events WHERE bill_id = ?1 ORDER BY version").unwrap();
+        let kinds = statement.query_map(params![id.to_string()], |row| row.get(0)).unwrap();
+        kinds.collect::<rusqlite::Result<Vec<String>>>().unwrap()
+    }
+
+    #[test]
+    fn test_events() {
+        let data = EventStore::in_memory().unwrap();
+        let id = data.add_bill(&Bill::new("dinner".to_string(), Currency::USD)).unwrap();
+        let (item_id, _) = data.add_item(id, &LineItem::from("pasta".to_string(), usd(1200), None), None).unwrap();
+        data.update_item(id, item_id, &LineItem::from("pasta".to_string(), usd(1300), None), None).unwrap();
+        let patch = BillPatch::Merge(serde_json::json!({"name": "lunch"}));
+        data.patch_bill(id, &patch, Some(3)).unwrap();
+        assert_eq!(data.delete_item(id, 7, None), Err(DataError::ItemNotFound));
+
+        let stored = data.get_versioned_bill(id).unwrap();
+        assert_eq!(stored.version, 4);
+        assert_eq!(stored.bill.name, "lunch");
+        assert_eq!(stored.bill.items()[&item_id].price, usd(1300));
+        assert_eq!(event_kinds(&data, id), vec!["bill_created", "item_added", "item_updated", "bill_patched"]);
+
+        assert_eq!(data.delete_bill(id, Some(3)), Err(DataError::VersionMismatch { expected: 3, actual: 4 }));
+        data.delete_bill(id, Some(4)).unwrap();
+        assert_eq!(data.get_versioned_bill(id), Err(DataError::BillNotFound));
+        assert!(data.get_bills().unwrap().is_empty());
+        // the log keeps everything
+        assert_eq!(event_kinds(&data, id).last().map(String::as_str), Some("bill_deleted"));
+    }
+
+    #[test]
+    fn test_snapshots() {
+        let data = EventStore::in_memory().unwrap().with_snapshot_interval(3);
+        let id = data.add_bill(&Bill::new("dinner".to_string(), Currency::USD)).unwrap();
+        for price in 1..=7 {
+            data.add_item(id, &LineItem::from(format!("item {}", price), usd(price), None), None).unwrap();
+        }
+        let stored = data.get_versioned_bill(id).unwrap();
+        assert_eq!(stored.version, 8);
+        assert_eq!(stored.bill.items().len(), 7);
+
+        let connection = data.connection();
+        let versions = connection.prepare("SELECT version FROM snapshots ORDER BY version").unwrap()
+            .query_map([], |row| row.get(0)).unwrap()
+            .collect::<rusqlite::Result<Vec<u64>>>().unwrap();
+        assert_eq!(versions, vec![3, 6]);
+
+        // loading starts from the latest snapshot, so events before it are
+        // not needed
+        connection.execute("DELETE FROM events WHERE version <= 6", []).unwrap();
+        assert_eq!(load_bill(&connection, id).unwrap(), Some(stored));
+        drop(connection);
+
+        // deleting on a snapshot version must not leave a live snapshot
+        data.delete_bill(id, Some(8)).unwrap();
+        assert_eq!(data.get_versioned_bill(id), Err(DataError::BillNotFound));
+    }
+
+    #[test]
+    fn test_history() {
+        let data = EventStore::in_memory().unwrap().with_actor(Some("alice".to_string()));
+        let id = data.add_bill(&Bill::new("dinner".to_string(), Currency::USD)).unwrap();
+        let (item_id, _) = data.add_item(id, &LineItem::from("pasta".to_string(), usd(1200), None), None).unwrap();
+        data.with_actor(None).delete_item(id, item_id, None).unwrap();
+
+        let history = data.get_history(id).unwrap();
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[0].version, INITIAL_VERSION);
+        assert_eq!(history[1].actor.as_deref(), Some("alice"));
+        assert_eq!(history[2].change, Change::ItemDeleted { item_id });
+
+        let reverted = data.revert_bill(id, 2, Some(3)).unwrap();
+        assert_eq!(reverted.bill.items().len(), 1);
+        assert_eq!(data.get_versioned_bill(id).unwrap(), reverted);
+        assert_eq!(data.revert_bill(id, 9, None), Err(DataError::RevisionNotFound));
+    }
+
+    #[test]
+    fn test_fold_rejects_a_corrupt_log() {
+        let id = Uuid::new_v4();
+        let recorded = Recorded {
+            version: 2,
+            at: Utc::now(),
+            actor: None,
+            event: Event::ItemDeleted { item_id: 0 },
+        };
+        assert!(matches!(fold(id, None, &recorded), Err(DataError::Storage(_))));
+    }
+
+    #[test]
+    fn test_groups() {
+        let data = EventStore::in_memory().unwrap();
+        let bill = data.add_bill(&Bill::new("dinner".to_string(), Currency::USD)).unwrap();
+        let mut group = Group::new("team".to_string());
+        group.members = vec!["alice".to_string(), "bob".to_string()];
+        group.bills = vec![Uuid::new_v4()];
+        match data.add_group(&group) {
+            Err(DataError::Validation(err)) => assert_eq!(err.errors[0].path, "bills.0"),
+            res => panic!("expected a validation error, got {:?}", res),
+        }
+
+        group.bills = vec![bill];
+        let id = data.add_group(&group).unwrap();
+        assert_eq!(data.get_group(id).unwrap(), group);
+        group.name = "the team".to_string();
+        data.update_group(id, &group).unwrap();
+        assert_eq!(data.get_groups().unwrap(), vec![GroupWithId { id, group: group.clone() }]);
+        assert_eq!(data.update_group(Uuid::new_v4(), &group), Err(DataError::GroupNotFound));
+
+        // deleting a bill takes it out of the group
+        data.delete_bill(bill, None).unwrap();
+        assert!(data.get_group(id).unwrap().bills.is_empty());
+        assert_eq!(data.delete_group(id), Ok(id));
+        assert_eq!(data.get_group(id), Err(DataError::GroupNotFound));
+    }
+
+    #[test]
+    fn test_participants() {
+        let data = EventStore::in_memory().unwrap().with_snapshot_interval(2);
+        let mut bill = Bill::new("dinner".to_string(), Currency::USD);
+        bill.add_item(LineItem::from("pasta".to_string(), usd(1200), Some("bob".to_string()))).unwrap();
+        let id = data.add_bill(&bill).unwrap();
+        let bob = Uuid::new_v4();
+        let robert = Uuid::new_v4();
+        let added = |id, name: &str| ParticipantChange::Added { id, participant: Participant::new(name.to_string()) };
+        data.change_participants(id, &added(bob, "Bob"), None).unwrap();
+        data.change_participants(id, &added(robert, "Robert"), None).unwrap();
+        data.add_item(id, &LineItem::from("wine".to_string(), usd(800), Some("robert".to_string())), None).unwrap();
+        let stored = data.change_participants(id, &ParticipantChange::Merged { from: robert, into: bob }, None).unwrap();
+
+        // replaying the log from the last snapshot gives the same bill
+        assert_eq!(data.get_bill(id).unwrap(), stored.bill);
+        assert_eq!(stored.bill.get_orderers(), vec![bob.to_string()]);
+        assert_eq!(event_kinds(&data, id)[1], "participants_changed");
+        let history = data.get_history(id).unwrap();
+        assert_eq!(history.last().unwrap().change, Change::ParticipantsMerged { from: robert, into: bob });
+    }
+
+    #[test]
+    fn test_payments() {
+        let data = EventStore::in_memory().unwrap();
+        let bill = data.add_bill(&Bill::new("dinner".to_string(), Currency::USD)).unwrap();
+        let date = NaiveDate::from_ymd_opt(2024, 5, 2).unwrap();
+        let mut payment = Payment::new("alice".to_string(), "bob".to_string(), usd(2340), date);
+        payment.reference = Some(Reference::Bill(bill));
+        let id = data.add_payment(&payment).unwrap();
+        let earlier = Payment::new("bob".to_string(), "alice".to_string(), usd(100), date.pred_opt().unwrap());
+        let earlier_id = data.add_payment(&earlier).unwrap();
+        assert_eq!(data.get_payment(id).unwrap(), payment);
+        let ids = data.get_payments().unwrap().into_iter().map(|stored| stored.id).collect::<Vec<Uuid>>();
+        assert_eq!(ids, vec![earlier_id, id]);
+
+        // the log keeps the reference, but the bill is gone
+        data.delete_bill(bill, None).unwrap();
+        assert_eq!(data.get_payment(id).unwrap().reference, None);
+        assert_eq!(data.delete_payment(id), Ok(id));
+        assert_eq!(data.delete_payment(id), Err(DataError::PaymentNotFound));
+        assert_eq!(data.get_payment(id), Err(DataError::PaymentNotFound));
+        let connection = data.connection();
+        let kinds = connection.prepare("SELECT kind FROM payment_events WHERE payment_id = ?1 ORDER BY rowid").unwrap()
+            .query_map(params![id.to_string()], |row| row.get(0)).unwrap()
+            .collect::<rusqlite::Result<Vec<String>>>().unwrap();
+        assert_eq!(kinds, vec!["payment_recorded", "payment_deleted"]);
+    }
+
+    #[test]
+    fn test_access() {
+        let data = EventStore::in_memory().unwrap();
+        for username in ["alice", "bob", "carol"] {
+            data.add_user(&User { username: username.to_string(), password_hash: "hash".to_string() }).unwrap();
+        }
+        assert!(matches!(data.add_user(&data.get_user("alice").unwrap()), Err(DataError::Conflict(_))));
+        let alice = data.with_user("alice".to_string());
+        let bob = data.with_user("bob".to_string());
+        let id = alice.add_bill(&Bill::new("dinner".to_string(), Currency::USD)).unwrap();
+        assert_eq!(alice.get_versioned_bill(id).unwrap().owner, Some("alice".to_string()));
+        assert_eq!(bob.get_bill(id), Err(DataError::BillNotFound));
+        assert!(bob.get_bills().unwrap().is_empty());
+        assert_eq!(data.get_bills().unwrap().len(), 1);
+
+        // a bill bob can't see can't go in bob's groups or payments either
+        let mut group = Group::new("trip".to_string());
+        group.bills = vec![id];
+        assert!(matches!(bob.add_group(&group), Err(DataError::Validation(_))));
+        let date = NaiveDate::from_ymd_opt(2024, 5, 2).unwrap();
+        let mut payment = Payment::new("alice".to_string(), "bob".to_string(), Money::new(500, Currency::USD), date);
+        payment.reference = Some(Reference::Bill(id));
+        assert!(matches!(bob.add_payment(&payment), Err(DataError::Validation(_))));
+        let payment = alice.add_payment(&payment).unwrap();
+        assert_eq!(bob.get_payment(payment), Err(DataError::PaymentNotFound));
+
+        assert_eq!(bob.invite_member(id, "carol", None), Err(DataError::BillNotFound));
+        assert_eq!(alice.invite_member(id, "dave", None), Err(DataError::UserNotFound));
+        let stored = alice.invite_member(id, "bob", None).unwrap();
+        assert_eq!(stored.members, vec!["bob".to_string()]);
+        assert_eq!(bob.get_bills().unwrap().len(), 1);
+        assert!(bob.get_payment(payment).is_ok());
+        assert_eq!(bob.delete_payment(payment), Err(DataError::Forbidden));
+        assert_eq!(bob.delete_bill(id, None), Err(DataError::Forbidden));
+        bob.add_item(id, &LineItem::from("wine".to_string(), Money::new(800, Currency::USD), None), None).unwrap();
+        let group = bob.add_group(&group).unwrap();
+        assert_eq!(alice.get_group(group), Err(DataError::GroupNotFound));
+
+        assert_eq!(bob.remove_member(id, "bob", None).unwrap().members, Vec::<String>::new());
+        assert_eq!(bob.get_history(id), Err(DataError::BillNotFound));
+        assert_eq!(alice.delete_bill(id, None), Ok(id));
+    }
+
+    #[test]
+    fn test_assign_owner() {
+        let data = EventStore::in_memory().unwrap();
+        data.add_user(&User { username: "alice".to_string(), password_hash: "hash".to_string() }).unwrap();
+        let bill = data.add_bill(&Bill::new("dinner".to_string(), Currency::USD)).unwrap();
+        let group = data.add_group(&Group::new("trip".to_string())).unwrap();
+        let date = NaiveDate::from_ymd_opt(2024, 5, 2).unwrap();
+        let payment = data.add_payment(&Payment::new("bob".to_string(), "alice".to_string(), usd(500), date)).unwrap();
+        let alice = data.with_user("alice".to_string());
+        assert_eq!(alice.get_payment(payment), Err(DataError::PaymentNotFound));
+
+        assert_eq!(data.assign_owner("alice"), Ok(Assigned { bills: 1, groups: 1, payments: 1 }));
+        assert_eq!(alice.get_versioned_bill(bill).unwrap().owner, Some("alice".to_string()));
+        assert_eq!(event_kinds(&data, bill), vec!["bill_created", "owner_assigned"]);
+        assert!(alice.get_group(group).is_ok());
+        assert_eq!(alice.delete_payment(payment), Ok(payment));
+        assert_eq!(data.assign_owner("alice"), Ok(Assigned::default()));
+    }
+
+    #[test]
+    fn test_reopen() {
+        let path = std::env::temp_dir().join(format!("billsplit-events-{}.db", Uuid::new_v4()));
+        let id = EventStore::open(&path).unwrap().add_bill(&Bill::from("dinner".to_string(), usd(100))).unwrap();
+        let data = EventStore::open(&path).unwrap();
+        assert_eq!(data.get_bill(id).unwrap(), Bill::from("dinner".to_string(), usd(100)));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
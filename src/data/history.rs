@@ -0,0 +1,118 @@
+//! The record of every change made to a bill, for showing and undoing them.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::models::bill::{Bill, BillWithId};
+use crate::models::participant::ParticipantChange;
+
+/// What a change to a bill did.
+#[derive(Debug, Deserialize, Serialize, Clone, Eq, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Change {
+    Created,
+    Replaced,
+    Patched,
+    ItemAdded { item_id: u16 },
+    ItemUpdated { item_id: u16 },
+    ItemDeleted { item_id: u16 },
+    Reverted { to: u64 },
+    ParticipantAdded { participant_id: Uuid },
+    ParticipantUpdated { participant_id: Uuid },
+    ParticipantRemoved { participant_id: Uuid },
+    ParticipantsMerged { from: Uuid, into: Uuid },
+    MemberInvited { username: String },
+    MemberRemoved { username: String },
+    /// The bill, which had no owner, was given to `username`.
+    OwnerAssigned { username: String },
+}
+
+impl From<&ParticipantChange> for Change {
+    fn from(change: &ParticipantChange) -> Self {
+        match change {
+            ParticipantChange::Added { id, .. } => Change::ParticipantAdded { participant_id: *id },
+            ParticipantChange::Updated { id, .. } => Change::ParticipantUpdated { participant_id: *id },
+            ParticipantChange::Removed { id } => Change::ParticipantRemoved { participant_id: *id },
+            ParticipantChange::Merged { from, into } => Change::ParticipantsMerged { from: *from, into: *into },
+        }
+    }
+}
+
+/// One change to a bill: the version it made, when and by whom, and the
+/// bill before and after. `before` is only missing for the change that
+/// created the bill, or if that version was never recorded.
+#[derive(Debug, Deserialize, Serialize, Clone, Eq, PartialEq)]
+pub struct Revision {
+    pub version: u64,
+    pub at: DateTime<Utc>,
+    pub actor: Option<String>,
+    pub change: Change,
+    pub before: Option<Bill>,
+    pub after: Bill,
+}
+
+impl Revision {
+    /// The revision for a change that has just left the bill as `stored`.
+    /// Providers store only the state after each change; see `link`.
+    pub(crate) fn record(stored: &BillWithId, actor: &Option<String>, change: Change) -> Self {
+        Self {
+            version: stored.version,
+            at: stored.updated_at,
+            actor: actor.clone(),
+            change,
+            before: None,
+            after: stored.bill.clone(),
+        }
+    }
+}
+
+/// Fills in each revision's `before` from the one it follows. `revisions`
+/// must be in version order.
+pub(crate) fn link(mut revisions: Vec<Revision>) -> Vec<Revision> {
+    for index in 1..revisions.len() {
+        if revisions[index - 1].version + 1 == revisions[index].version {
+            revisions[index].before = Some(revisions[index - 1].after.clone());
+        }
+    }
+    revisions
+}
+
+/// `current` as it was at `version`, from that revision's bill. Item ids
+/// handed out since are not handed out again.
+pub(crate) fn restore(current: &Bill, revision: &Revision) -> Bill {
+    let mut bill = revision.after.clone();
+    bill.set_counter(bill.counter().max(current.counter()));
+    bill
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::new_stored;
+    use crate::models::currency::{Currency, Money};
+    use crate::models::item::LineItem;
+
+    #[test]
+    fn test_link_and_restore() {
+        let mut stored = new_stored(Uuid::new_v4(), Bill::new("dinner".to_string(), Currency::USD));
+        let mut revisions = vec![Revision::record(&stored, &None, Change::Created)];
+        let item_id = stored.bill.add_item(LineItem::from("pasta".to_string(), Money::new(1200, Currency::USD), None)).unwrap();
+        stored.version += 1;
+        revisions.push(Revision::record(&stored, &Some("alice".to_string()), Change::ItemAdded { item_id }));
+
+        let revisions = link(revisions);
+        assert_eq!(revisions[0].before, None);
+        assert_eq!(revisions[1].before, Some(revisions[0].after.clone()));
+        assert_eq!(revisions[1].actor.as_deref(), Some("alice"));
+
+        let mut restored = restore(&stored.bill, &revisions[0]);
+        assert!(restored.items().is_empty());
+        let next_id = restored.add_item(LineItem::from("soup".to_string(), Money::new(500, Currency::USD), None)).unwrap();
+        assert_eq!(next_id, item_id + 1);
+    }
+
+    #[test]
+    fn test_change_serde() {
+        let json = serde_json::to_value(Change::ItemDeleted { item_id: 3 }).unwrap();
+        assert_eq!(json, serde_json::json!({"kind": "item_deleted", "item_id": 3}));
+    }
+}
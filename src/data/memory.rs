@@ -1,121 +1,728 @@
 use std::collections::HashMap;
-use crate::models::bill::{Bill, BillWithId};
+use crate::models::bill::{Bill, BillError, BillWithId};
+use crate::models::group::{Group, GroupWithId};
+use crate::models::item::LineItem;
+use crate::models::participant::ParticipantChange;
+use crate::models::patch::{BillPatch, PatchError};
+use crate::models::payment::{Payment, PaymentWithId, Reference};
+use crate::models::user::User;
 use uuid::Uuid;
-use crate::data::Data;
-use std::sync::Mutex;
+use crate::data::{
+    check_payment, check_version, new_stored, prepare_bill, prepare_group, sort_payments, touch, Assigned, Data, DataError,
+};
+use crate::data::access::{self, can_see, can_see_payment, check_owner, owns};
+use crate::data::history::{self, Change, Revision};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 
-lazy_static! {
-    static ref DATA: Mutex<HashMap<Uuid, Bill>> = Mutex::new(HashMap::new());
+/// A bill and the record of its changes.
+#[derive(Clone)]
+struct Entry {
+    stored: BillWithId,
+    history: Vec<Revision>,
 }
 
+/// A group and who created it.
+#[derive(Clone)]
+struct GroupEntry {
+    owner: Option<String>,
+    group: Group,
+}
+
+/// A payment and who recorded it.
+#[derive(Clone)]
+struct PaymentEntry {
+    owner: Option<String>,
+    stored: PaymentWithId,
+}
 
+/// Bills, groups, payments and accounts kept in memory for as long as this
+/// store, or a clone of it, lives. Clones share the same bills; separate
+/// stores from `new` do not.
 #[derive(Clone)]
-pub struct Memory {}
+pub struct Memory {
+    data: Arc<RwLock<HashMap<Uuid, Entry>>>,
+    /// Locked after `data` whenever both are, so the two never deadlock.
+    groups: Arc<RwLock<HashMap<Uuid, GroupEntry>>>,
+    /// In the order they were recorded. Locked after `data` and `groups`.
+    payments: Arc<RwLock<Vec<PaymentEntry>>>,
+    /// By username. Locked after everything else.
+    users: Arc<RwLock<HashMap<String, User>>>,
+    actor: Option<String>,
+    user: Option<String>,
+}
 
 impl Memory {
     pub fn new() -> Self {
-        Self { }
+        Self {
+            data: Arc::new(RwLock::new(HashMap::new())),
+            groups: Arc::new(RwLock::new(HashMap::new())),
+            payments: Arc::new(RwLock::new(vec![])),
+            users: Arc::new(RwLock::new(HashMap::new())),
+            actor: None,
+            user: None,
+        }
+    }
+
+    // A panic while the lock was held cannot leave a bill half changed, since
+    // bills check a change before making it, so a poisoned lock is still safe
+    // to use.
+    fn read(&self) -> RwLockReadGuard<'_, HashMap<Uuid, Entry>> {
+        self.data.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, HashMap<Uuid, Entry>> {
+        self.data.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn read_groups(&self) -> RwLockReadGuard<'_, HashMap<Uuid, GroupEntry>> {
+        self.groups.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write_groups(&self) -> RwLockWriteGuard<'_, HashMap<Uuid, GroupEntry>> {
+        self.groups.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn read_payments(&self) -> RwLockReadGuard<'_, Vec<PaymentEntry>> {
+        self.payments.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write_payments(&self) -> RwLockWriteGuard<'_, Vec<PaymentEntry>> {
+        self.payments.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn read_users(&self) -> RwLockReadGuard<'_, HashMap<String, User>> {
+        self.users.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write_users(&self) -> RwLockWriteGuard<'_, HashMap<String, User>> {
+        self.users.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Drops `reference` from every payment towards it.
+    fn unlink_payments(&self, reference: Reference) {
+        for entry in self.write_payments().iter_mut() {
+            if entry.stored.payment.reference == Some(reference) {
+                entry.stored.payment.reference = None;
+            }
+        }
+    }
+
+    /// The bill `id`, if this store's user may see it.
+    fn visible<'a>(&self, data: &'a HashMap<Uuid, Entry>, id: Uuid) -> Result<&'a Entry, DataError> {
+        data.get(&id).filter(|entry| can_see(&entry.stored, &self.user)).ok_or(DataError::BillNotFound)
+    }
+
+    /// The group `id`, if this store's user may see it.
+    fn visible_group<'a>(&self, groups: &'a HashMap<Uuid, GroupEntry>, id: Uuid) -> Result<&'a GroupEntry, DataError> {
+        groups.get(&id).filter(|entry| owns(&entry.owner, &self.user)).ok_or(DataError::GroupNotFound)
+    }
+
+    /// The payments this store's user may see, in the order they were
+    /// recorded.
+    fn visible_payments(&self) -> Result<Vec<PaymentEntry>, DataError> {
+        let data = self.read();
+        let groups = self.read_groups();
+        let mut payments = vec![];
+        for entry in self.read_payments().iter() {
+            let visible = can_see_payment(
+                &entry.owner,
+                &entry.stored.payment,
+                &self.user,
+                |bill| Ok(self.visible(&data, bill).is_ok()),
+                |group| Ok(self.visible_group(&groups, group).is_ok()),
+            )?;
+            if visible {
+                payments.push(entry.clone());
+            }
+        }
+        Ok(payments)
+    }
+
+    /// Changes a bill in place, bumping its version and recording the change
+    /// `change` describes. Returns the change's result and the changed bill.
+    fn modify<T, E>(
+        &self,
+        id: Uuid,
+        expected_version: Option<u64>,
+        change: impl FnOnce(&mut Bill) -> Result<(T, Change), E>,
+    ) -> Result<(T, BillWithId), DataError>
+    where
+        DataError: From<E>,
+    {
+        self.modify_stored(id, expected_version, |stored| change(&mut stored.bill))
+    }
+
+    /// `modify`, for changes to who the bill is shared with as well as to
+    /// the bill itself.
+    fn modify_stored<T, E>(
+        &self,
+        id: Uuid,
+        expected_version: Option<u64>,
+        change: impl FnOnce(&mut BillWithId) -> Result<(T, Change), E>,
+    ) -> Result<(T, BillWithId), DataError>
+    where
+        DataError: From<E>,
+    {
+        let mut data = self.write();
+        self.visible(&data, id)?;
+        let entry = data.get_mut(&id).ok_or(DataError::BillNotFound)?;
+        check_version(expected_version, entry.stored.version)?;
+        let mut stored = entry.stored.clone();
+        let (res, change) = change(&mut stored)?;
+        prepare_bill(&mut stored.bill)?;
+        touch(&mut stored);
+        entry.stored = stored;
+        entry.history.push(Revision::record(&entry.stored, &self.actor, change));
+        Ok((res, entry.stored.clone()))
+    }
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl Data for Memory {
+    fn with_actor(&self, actor: Option<String>) -> Self {
+        Self {
+            actor,
+            ..self.clone()
+        }
+    }
+
+    fn with_user(&self, user: String) -> Self {
+        Self {
+            actor: Some(user.clone()),
+            user: Some(user),
+            ..self.clone()
+        }
+    }
+
+    fn add_bill(&self, bill: &Bill) -> Result<Uuid, DataError> {
+        let mut bill = bill.clone();
+        prepare_bill(&mut bill)?;
+        let mut data = self.write();
+        let id = Uuid::new_v4();
+        let mut stored = new_stored(id, bill);
+        stored.owner = self.user.clone();
+        let history = vec![Revision::record(&stored, &self.actor, Change::Created)];
+        data.insert(id, Entry { stored, history });
+        Ok(id)
+    }
+
+    fn delete_bill(&self, id: Uuid, expected_version: Option<u64>) -> Result<Uuid, DataError> {
+        let mut data = self.write();
+        let entry = self.visible(&data, id)?;
+        check_version(expected_version, entry.stored.version)?;
+        check_owner(&entry.stored, &self.user)?;
+        data.remove(&id);
+        for entry in self.write_groups().values_mut() {
+            entry.group.bills.retain(|bill| *bill != id);
+        }
+        self.unlink_payments(Reference::Bill(id));
+        Ok(id)
+    }
+
+    fn get_versioned_bill(&self, id: Uuid) -> Result<BillWithId, DataError> {
+        let data = self.read();
+        self.visible(&data, id).map(|entry| entry.stored.clone())
+    }
+
+    fn get_bills(&self) -> Result<Vec<BillWithId>, DataError> {
+        let data = self.read();
+        Ok(data.values()
+            .filter(|entry| can_see(&entry.stored, &self.user))
+            .map(|entry| entry.stored.clone())
+            .collect())
+    }
+
+    fn update_bill(&self, id: Uuid, bill: &Bill, expected_version: Option<u64>) -> Result<u64, DataError> {
+        let ((), stored) = self.modify(id, expected_version, |existing_bill| {
+            *existing_bill = bill.clone();
+            Ok::<_, DataError>(((), Change::Replaced))
+        })?;
+        Ok(stored.version)
+    }
+
+    fn patch_bill(&self, id: Uuid, patch: &BillPatch, expected_version: Option<u64>) -> Result<BillWithId, DataError> {
+        let ((), stored) = self.modify(id, expected_version, |bill| {
+            *bill = patch.apply(bill)?;
+            Ok::<_, PatchError>(((), Change::Patched))
+        })?;
+        Ok(stored)
+    }
+
+    fn add_item(&self, bill_id: Uuid, item: &LineItem, expected_version: Option<u64>) -> Result<(u16, u64), DataError> {
+        self.modify(bill_id, expected_version, |bill| {
+            let item_id = bill.add_item(item.clone())?;
+            Ok::<_, BillError>((item_id, Change::ItemAdded { item_id }))
+        }).map(|(item_id, stored)| (item_id, stored.version))
+    }
+
+    fn get_items(&self, bill_id: Uuid) -> Result<HashMap<u16, LineItem>, DataError> {
+        let data = self.read();
+        self.visible(&data, bill_id).map(|entry| entry.stored.bill.items().clone())
+    }
+
+    fn update_item(&self, bill_id: Uuid, item_id: u16, item: &LineItem, expected_version: Option<u64>) -> Result<(u16, u64), DataError> {
+        self.modify(bill_id, expected_version, |bill| {
+            let item_id = bill.update_item(item_id, item.clone())?;
+            Ok::<_, BillError>((item_id, Change::ItemUpdated { item_id }))
+        }).map(|(item_id, stored)| (item_id, stored.version))
+    }
+
+    fn delete_item(&self, bill_id: Uuid, item_id: u16, expected_version: Option<u64>) -> Result<(u16, u64), DataError> {
+        self.modify(bill_id, expected_version, |bill| {
+            let item_id = bill.delete_item(item_id)?;
+            Ok::<_, BillError>((item_id, Change::ItemDeleted { item_id }))
+        }).map(|(item_id, stored)| (item_id, stored.version))
+    }
+
+    fn get_history(&self, id: Uuid) -> Result<Vec<Revision>, DataError> {
+        let data = self.read();
+        let entry = self.visible(&data, id)?;
+        Ok(history::link(entry.history.clone()))
+    }
+
+    fn revert_bill(&self, id: Uuid, version: u64, expected_version: Option<u64>) -> Result<BillWithId, DataError> {
+        let revision = self.visible(&self.read(), id)?
+            .history.iter()
+            .find(|revision| revision.version == version)
+            .cloned()
+            .ok_or(DataError::RevisionNotFound)?;
+        let ((), stored) = self.modify(id, expected_version, |bill| {
+            *bill = history::restore(bill, &revision);
+            Ok::<_, DataError>(((), Change::Reverted { to: version }))
+        })?;
+        Ok(stored)
+    }
+
+    fn change_participants(&self, id: Uuid, change: &ParticipantChange, expected_version: Option<u64>) -> Result<BillWithId, DataError> {
+        let ((), stored) = self.modify(id, expected_version, |bill| {
+            bill.change_participants(change.clone())?;
+            Ok::<_, BillError>(((), Change::from(change)))
+        })?;
+        Ok(stored)
+    }
+
+    fn invite_member(&self, id: Uuid, username: &str, expected_version: Option<u64>) -> Result<BillWithId, DataError> {
+        let ((), stored) = self.modify_stored(id, expected_version, |stored| {
+            access::invite(stored, username, &self.user, |username| Ok(self.read_users().contains_key(username)))?;
+            Ok::<_, DataError>(((), Change::MemberInvited { username: username.to_string() }))
+        })?;
+        Ok(stored)
+    }
+
+    fn remove_member(&self, id: Uuid, username: &str, expected_version: Option<u64>) -> Result<BillWithId, DataError> {
+        let ((), stored) = self.modify_stored(id, expected_version, |stored| {
+            access::uninvite(stored, username, &self.user)?;
+            Ok::<_, DataError>(((), Change::MemberRemoved { username: username.to_string() }))
+        })?;
+        Ok(stored)
+    }
+
+    fn add_group(&self, group: &Group) -> Result<Uuid, DataError> {
+        let data = self.read();
+        let group = prepare_group(group, |bill| Ok(self.visible(&data, bill).is_ok()))?;
+        let id = Uuid::new_v4();
+        self.write_groups().insert(id, GroupEntry { owner: self.user.clone(), group });
+        Ok(id)
+    }
+
+    fn get_group(&self, id: Uuid) -> Result<Group, DataError> {
+        self.visible_group(&self.read_groups(), id).map(|entry| entry.group.clone())
+    }
+
+    fn get_groups(&self) -> Result<Vec<GroupWithId>, DataError> {
+        let mut groups = self.read_groups().iter()
+            .filter(|(_, entry)| owns(&entry.owner, &self.user))
+            .map(|(id, entry)| GroupWithId { id: *id, group: entry.group.clone() })
+            .collect::<Vec<GroupWithId>>();
+        groups.sort_by(|a, b| a.group.name.cmp(&b.group.name).then(a.id.cmp(&b.id)));
+        Ok(groups)
+    }
+
+    fn update_group(&self, id: Uuid, group: &Group) -> Result<Uuid, DataError> {
+        let data = self.read();
+        let mut groups = self.write_groups();
+        self.visible_group(&groups, id)?;
+        let group = prepare_group(group, |bill| Ok(self.visible(&data, bill).is_ok()))?;
+        let entry = groups.get_mut(&id).ok_or(DataError::GroupNotFound)?;
+        entry.group = group;
+        Ok(id)
+    }
 
-    fn add_bill(&self, bill: &Bill) -> Uuid {
-        let mut data = DATA.lock().unwrap();
+    fn delete_group(&self, id: Uuid) -> Result<Uuid, DataError> {
+        let mut groups = self.write_groups();
+        self.visible_group(&groups, id)?;
+        groups.remove(&id);
+        self.unlink_payments(Reference::Group(id));
+        Ok(id)
+    }
+
+    fn add_payment(&self, payment: &Payment) -> Result<Uuid, DataError> {
+        let data = self.read();
+        let groups = self.read_groups();
+        check_payment(
+            payment,
+            |bill| Ok(self.visible(&data, bill).ok().map(|entry| entry.stored.bill.currency())),
+            |group| Ok(self.visible_group(&groups, group).is_ok()),
+        )?;
         let id = Uuid::new_v4();
-        data.insert(id, bill.clone());
-        id
+        self.write_payments().push(PaymentEntry {
+            owner: self.user.clone(),
+            stored: PaymentWithId { id, payment: payment.clone() },
+        });
+        Ok(id)
+    }
+
+    fn get_payment(&self, id: Uuid) -> Result<Payment, DataError> {
+        self.visible_payments()?.into_iter()
+            .find(|entry| entry.stored.id == id)
+            .map(|entry| entry.stored.payment)
+            .ok_or(DataError::PaymentNotFound)
+    }
+
+    fn get_payments(&self) -> Result<Vec<PaymentWithId>, DataError> {
+        let mut payments = self.visible_payments()?.into_iter()
+            .map(|entry| entry.stored)
+            .collect::<Vec<PaymentWithId>>();
+        sort_payments(&mut payments);
+        Ok(payments)
     }
 
-    fn delete_bill(&self, id: Uuid) -> Result<Uuid, String> {
-        let mut data = DATA.lock().unwrap();
-        match data.remove(&id) {
-            Some(_) => Ok(id),
-            None => Err("Bill not found".to_string())
+    fn delete_payment(&self, id: Uuid) -> Result<Uuid, DataError> {
+        let entry = self.visible_payments()?.into_iter()
+            .find(|entry| entry.stored.id == id)
+            .ok_or(DataError::PaymentNotFound)?;
+        if !owns(&entry.owner, &self.user) {
+            return Err(DataError::Forbidden);
         }
+        self.write_payments().retain(|entry| entry.stored.id != id);
+        Ok(id)
     }
 
-    fn get_bill(&self, id: Uuid) -> Option<Bill> {
-        let data = DATA.lock().unwrap();
-        match data.get(&id) {
-            Some(bill) => Some(bill.clone()),
-            None => None
+    fn add_user(&self, user: &User) -> Result<(), DataError> {
+        let mut users = self.write_users();
+        if users.contains_key(&user.username) {
+            return Err(DataError::Conflict(format!("the username {} is taken", user.username)));
         }
+        users.insert(user.username.clone(), user.clone());
+        Ok(())
     }
 
-    fn get_bills(&self) -> Vec<BillWithId> {
-        let data = DATA.lock().unwrap();
-        let vec = data.iter().map(|(id, bill)| {
-            BillWithId {
-                id: *id,
-                bill: bill.clone()
-            }
-        }).collect::<Vec<BillWithId>>();
-        vec
-    }
-
-    fn update_bill(&self, id: Uuid, bill: &Bill) -> Result<Uuid, String> {
-        let mut data = DATA.lock().unwrap();
-        match data.get_mut(&id) {
-            Some(existing_bill) => {
-                *existing_bill = bill.clone();
-                Ok(id)
-            },
-            None => Err("Bill not found".to_string())
+    fn get_user(&self, username: &str) -> Result<User, DataError> {
+        self.read_users().get(username).cloned().ok_or(DataError::UserNotFound)
+    }
+
+    fn assign_owner(&self, username: &str) -> Result<Assigned, DataError> {
+        access::check_nobody(&self.user)?;
+        self.get_user(username)?;
+        let ids = self.read().values()
+            .filter(|entry| entry.stored.owner.is_none())
+            .map(|entry| entry.stored.id)
+            .collect::<Vec<Uuid>>();
+        for id in &ids {
+            self.modify_stored(*id, None, |stored| {
+                access::assign(stored, username);
+                Ok::<_, DataError>(((), Change::OwnerAssigned { username: username.to_string() }))
+            })?;
+        }
+        let mut assigned = Assigned { bills: ids.len(), ..Assigned::default() };
+        for entry in self.write_groups().values_mut().filter(|entry| entry.owner.is_none()) {
+            entry.owner = Some(username.to_string());
+            assigned.groups += 1;
         }
+        for entry in self.write_payments().iter_mut().filter(|entry| entry.owner.is_none()) {
+            entry.owner = Some(username.to_string());
+            assigned.payments += 1;
+        }
+        Ok(assigned)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::NaiveDate;
+    use crate::data::{now, INITIAL_VERSION};
+    use crate::models::currency::{Currency, Money};
+    use crate::models::patch::BillPatch;
 
     #[test]
     fn test_add_bill() {
         let data = Memory::new();
-        let bill = Bill::new("test".to_string());
-        let id = data.add_bill(&bill);
+        let bill = Bill::new("test".to_string(), Currency::USD);
+        let id = data.add_bill(&bill).unwrap();
         assert_eq!(data.get_bill(id).unwrap(), bill);
     }
 
     #[test]
     fn test_delete_bill() {
         let data = Memory::new();
-        let bill = Bill::new("test".to_string());
-        let id = data.add_bill(&bill);
-        assert_eq!(data.delete_bill(id), Ok(id));
-        assert_eq!(data.delete_bill(id), Err("Bill not found".to_string()));
+        let bill = Bill::new("test".to_string(), Currency::USD);
+        let id = data.add_bill(&bill).unwrap();
+        assert_eq!(data.delete_bill(id, Some(2)), Err(DataError::VersionMismatch { expected: 2, actual: 1 }));
+        assert_eq!(data.delete_bill(id, Some(1)), Ok(id));
+        assert_eq!(data.delete_bill(id, None), Err(DataError::BillNotFound));
     }
 
     #[test]
     fn test_get_bill() {
         let data = Memory::new();
-        let bill = Bill::new("test".to_string());
-        let id = data.add_bill(&bill);
+        let bill = Bill::new("test".to_string(), Currency::USD);
+        let id = data.add_bill(&bill).unwrap();
         assert_eq!(data.get_bill(id).unwrap(), bill);
     }
 
     #[test]
     fn test_get_bills() {
         let data = Memory::new();
-        let bill = Bill::new("test".to_string());
-        let id = data.add_bill(&bill);
-        let bills = data.get_bills();
+        let bill = Bill::new("test".to_string(), Currency::USD);
+        let before = now();
+        let id = data.add_bill(&bill).unwrap();
+        let bills = data.get_bills().unwrap();
+        assert!(bills[0].created_at >= before && bills[0].created_at <= now());
         let bill_with_id = BillWithId {
             id,
+            version: INITIAL_VERSION,
+            created_at: bills[0].created_at,
+            updated_at: bills[0].created_at,
+            owner: None,
+            members: vec![],
             bill
         };
-        assert_eq!(bills.contains(&bill_with_id), true);
+        assert!(bills.contains(&bill_with_id));
     }
 
     #[test]
     fn test_update_bill() {
         let data = Memory::new();
-        let bill = Bill::new("test".to_string());
-        let id = data.add_bill(&bill);
-        let bill = Bill::new("test2".to_string());
-        assert_eq!(data.update_bill(id, &bill), Ok(id));
+        let bill = Bill::new("test".to_string(), Currency::USD);
+        let id = data.add_bill(&bill).unwrap();
+        let bill = Bill::new("test2".to_string(), Currency::USD);
+        assert_eq!(data.update_bill(id, &bill, Some(1)), Ok(2));
         assert_eq!(data.get_bill(id).unwrap(), bill);
+        assert_eq!(data.update_bill(id, &bill, Some(1)), Err(DataError::VersionMismatch { expected: 1, actual: 2 }));
+        assert_eq!(data.update_bill(id, &bill, None), Ok(3));
+    }
+
+    #[test]
+    fn test_patch_bill() {
+        let data = Memory::new();
+        let id = data.add_bill(&Bill::new("test".to_string(), Currency::USD)).unwrap();
+        let patch = BillPatch::Merge(serde_json::json!({"name": "test2"}));
+        let patched = data.patch_bill(id, &patch, Some(1)).unwrap();
+        assert_eq!((patched.version, patched.bill.name.as_str()), (2, "test2"));
+        assert_eq!(data.get_versioned_bill(id).unwrap(), patched);
+        assert_eq!(
+            data.patch_bill(id, &patch, Some(1)),
+            Err(DataError::VersionMismatch { expected: 1, actual: 2 })
+        );
+
+        let invalid = BillPatch::Merge(serde_json::json!({"name": "test3", "counter": -1}));
+        assert!(matches!(data.patch_bill(id, &invalid, None), Err(DataError::Invalid(_))));
+        assert_eq!(data.get_versioned_bill(id).unwrap(), patched);
+    }
+
+    #[test]
+    fn test_item_changes_bump_version() {
+        let data = Memory::new();
+        let id = data.add_bill(&Bill::new("test".to_string(), Currency::USD)).unwrap();
+        let created = data.get_versioned_bill(id).unwrap();
+        let (item_id, _) = data.add_item(id, &LineItem::from("test".to_string(), Money::new(100, Currency::USD), None), None).unwrap();
+        let changed = data.get_versioned_bill(id).unwrap();
+        assert_eq!(changed.version, 2);
+        assert_eq!(changed.created_at, created.created_at);
+        assert!(changed.updated_at >= created.updated_at);
+        data.delete_item(id, item_id, None).unwrap();
+        assert_eq!(data.get_versioned_bill(id).unwrap().version, 3);
+        // a failed change leaves the version alone
+        let stored = data.get_versioned_bill(id).unwrap();
+        assert!(data.delete_item(id, item_id, None).is_err());
+        assert_eq!(data.get_versioned_bill(id).unwrap(), stored);
+    }
+
+    #[test]
+    fn test_validation() {
+        let data = Memory::new();
+        assert!(matches!(data.add_bill(&Bill::new("".to_string(), Currency::USD)), Err(DataError::Validation(_))));
+        let id = data.add_bill(&Bill::new("test".to_string(), Currency::USD)).unwrap();
+        let stored = data.get_versioned_bill(id).unwrap();
+        let err = data.add_item(id, &LineItem::from(" ".to_string(), Money::new(100, Currency::USD), None), None).unwrap_err();
+        match err {
+            DataError::Validation(err) => assert_eq!(err.errors[0].path, "items.0.name"),
+            err => panic!("expected a validation error, got {:?}", err),
+        }
+        assert_eq!(data.get_versioned_bill(id).unwrap(), stored);
+        assert_eq!(data.get_history(id).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_groups() {
+        let data = Memory::new();
+        let bill = data.add_bill(&Bill::new("dinner".to_string(), Currency::USD)).unwrap();
+        let mut group = Group::new("team".to_string());
+        group.members = vec!["alice".to_string(), "bob".to_string()];
+        group.bills = vec![Uuid::new_v4()];
+        match data.add_group(&group) {
+            Err(DataError::Validation(err)) => assert_eq!(err.errors[0].path, "bills.0"),
+            res => panic!("expected a validation error, got {:?}", res),
+        }
+
+        group.bills = vec![bill];
+        let id = data.add_group(&group).unwrap();
+        assert_eq!(data.get_group(id).unwrap(), group);
+        group.name = "the team".to_string();
+        data.update_group(id, &group).unwrap();
+        assert_eq!(data.get_groups().unwrap(), vec![GroupWithId { id, group: group.clone() }]);
+        assert_eq!(data.update_group(Uuid::new_v4(), &group), Err(DataError::GroupNotFound));
+
+        // deleting a bill takes it out of the group
+        data.delete_bill(bill, None).unwrap();
+        assert!(data.get_group(id).unwrap().bills.is_empty());
+        assert_eq!(data.delete_group(id), Ok(id));
+        assert_eq!(data.get_group(id), Err(DataError::GroupNotFound));
+    }
+
+    #[test]
+    fn test_payments() {
+        let data = Memory::new();
+        let bill = data.add_bill(&Bill::new("dinner".to_string(), Currency::USD)).unwrap();
+        let date = NaiveDate::from_ymd_opt(2024, 5, 2).unwrap();
+        let mut payment = Payment::new("alice".to_string(), "bob".to_string(), Money::new(2340, Currency::EUR), date);
+        payment.reference = Some(Reference::Bill(bill));
+        assert!(matches!(data.add_payment(&payment), Err(DataError::Money(_))));
+        payment.reference = Some(Reference::Group(Uuid::new_v4()));
+        match data.add_payment(&payment) {
+            Err(DataError::Validation(err)) => assert_eq!(err.errors[0].path, "reference"),
+            res => panic!("expected a validation error, got {:?}", res),
+        }
+
+        payment.amount = Money::new(2340, Currency::USD);
+        payment.reference = Some(Reference::Bill(bill));
+        let id = data.add_payment(&payment).unwrap();
+        let earlier = Payment::new("bob".to_string(), "alice".to_string(), Money::new(100, Currency::USD), date.pred_opt().unwrap());
+        let earlier_id = data.add_payment(&earlier).unwrap();
+        assert_eq!(data.get_payment(id).unwrap(), payment);
+        let ids = data.get_payments().unwrap().into_iter().map(|stored| stored.id).collect::<Vec<Uuid>>();
+        assert_eq!(ids, vec![earlier_id, id]);
+
+        // deleting the bill keeps the payment
+        data.delete_bill(bill, None).unwrap();
+        assert_eq!(data.get_payment(id).unwrap().reference, None);
+        assert_eq!(data.delete_payment(id), Ok(id));
+        assert_eq!(data.get_payment(id), Err(DataError::PaymentNotFound));
+    }
+
+    #[test]
+    fn test_access() {
+        let data = Memory::new();
+        for username in ["alice", "bob", "carol"] {
+            data.add_user(&User { username: username.to_string(), password_hash: "hash".to_string() }).unwrap();
+        }
+        assert!(matches!(data.add_user(&data.get_user("alice").unwrap()), Err(DataError::Conflict(_))));
+        let alice = data.with_user("alice".to_string());
+        let bob = data.with_user("bob".to_string());
+        let id = alice.add_bill(&Bill::new("dinner".to_string(), Currency::USD)).unwrap();
+        assert_eq!(alice.get_versioned_bill(id).unwrap().owner, Some("alice".to_string()));
+        assert_eq!(bob.get_bill(id), Err(DataError::BillNotFound));
+        assert!(bob.get_bills().unwrap().is_empty());
+        assert_eq!(data.get_bills().unwrap().len(), 1);
+
+        // a bill bob can't see can't go in bob's groups or payments either
+        let mut group = Group::new("trip".to_string());
+        group.bills = vec![id];
+        assert!(matches!(bob.add_group(&group), Err(DataError::Validation(_))));
+        let date = NaiveDate::from_ymd_opt(2024, 5, 2).unwrap();
+        let mut payment = Payment::new("alice".to_string(), "bob".to_string(), Money::new(500, Currency::USD), date);
+        payment.reference = Some(Reference::Bill(id));
+        assert!(matches!(bob.add_payment(&payment), Err(DataError::Validation(_))));
+        let payment = alice.add_payment(&payment).unwrap();
+        assert_eq!(bob.get_payment(payment), Err(DataError::PaymentNotFound));
+
+        assert_eq!(bob.invite_member(id, "carol", None), Err(DataError::BillNotFound));
+        assert_eq!(alice.invite_member(id, "dave", None), Err(DataError::UserNotFound));
+        let stored = alice.invite_member(id, "bob", None).unwrap();
+        assert_eq!(stored.members, vec!["bob".to_string()]);
+        assert_eq!(bob.get_bills().unwrap().len(), 1);
+        assert!(bob.get_payment(payment).is_ok());
+        assert_eq!(bob.delete_payment(payment), Err(DataError::Forbidden));
+        assert_eq!(bob.delete_bill(id, None), Err(DataError::Forbidden));
+        bob.add_item(id, &LineItem::from("wine".to_string(), Money::new(800, Currency::USD), None), None).unwrap();
+        let group = bob.add_group(&group).unwrap();
+        assert_eq!(alice.get_group(group), Err(DataError::GroupNotFound));
+
+        assert_eq!(bob.remove_member(id, "bob", None).unwrap().members, Vec::<String>::new());
+        assert_eq!(bob.get_history(id), Err(DataError::BillNotFound));
+        assert_eq!(alice.delete_bill(id, None), Ok(id));
+    }
+
+    #[test]
+    fn test_history() {
+        let data = Memory::new().with_actor(Some("alice".to_string()));
+        let id = data.add_bill(&Bill::new("test".to_string(), Currency::USD)).unwrap();
+        let (item_id, _) = data.add_item(id, &LineItem::from("test".to_string(), Money::new(100, Currency::USD), None), None).unwrap();
+        data.with_actor(Some("bob".to_string())).delete_item(id, item_id, None).unwrap();
+
+        let history = data.get_history(id).unwrap();
+        let changes = history.iter().map(|revision| revision.change.clone()).collect::<Vec<Change>>();
+        assert_eq!(changes, vec![Change::Created, Change::ItemAdded { item_id }, Change::ItemDeleted { item_id }]);
+        assert_eq!(history[2].actor.as_deref(), Some("bob"));
+        assert_eq!(history[2].before.as_ref().unwrap().items().len(), 1);
+
+        let reverted = data.revert_bill(id, 2, Some(3)).unwrap();
+        assert_eq!(reverted.version, 4);
+        assert_eq!(reverted.bill.items().len(), 1);
+        assert_eq!(data.get_history(id).unwrap()[3].change, Change::Reverted { to: 2 });
+        assert_eq!(data.revert_bill(id, 9, None), Err(DataError::RevisionNotFound));
+        assert_eq!(data.revert_bill(id, 1, Some(3)), Err(DataError::VersionMismatch { expected: 3, actual: 4 }));
+
+        data.delete_bill(id, None).unwrap();
+        assert_eq!(data.get_history(id), Err(DataError::BillNotFound));
+    }
+
+    #[test]
+    fn test_instances_are_isolated() {
+        let data = Memory::new();
+        let id = data.add_bill(&Bill::new("test".to_string(), Currency::USD)).unwrap();
+        assert_eq!(Memory::new().get_bill(id), Err(DataError::BillNotFound));
+        assert!(data.clone().get_bill(id).is_ok());
+    }
+
+    #[test]
+    fn test_add_item() {
+        let data = Memory::new();
+        let id = data.add_bill(&Bill::new("test".to_string(), Currency::USD)).unwrap();
+        let item = LineItem::from("test".to_string(), Money::new(100, Currency::USD), None);
+        let (item_id, _) = data.add_item(id, &item, None).unwrap();
+        assert_eq!(data.get_items(id).unwrap().get(&item_id), Some(&item));
+        assert_eq!(data.add_item(Uuid::new_v4(), &item, None), Err(DataError::BillNotFound));
+    }
+
+    #[test]
+    fn test_update_item() {
+        let data = Memory::new();
+        let id = data.add_bill(&Bill::new("test".to_string(), Currency::USD)).unwrap();
+        let (item_id, _) = data.add_item(id, &LineItem::from("test".to_string(), Money::new(100, Currency::USD), None), None).unwrap();
+        let item = LineItem::from("test2".to_string(), Money::new(200, Currency::USD), None);
+        assert_eq!(data.update_item(id, item_id, &item, Some(2)), Ok((item_id, 3)));
+        assert_eq!(data.get_items(id).unwrap().get(&item_id), Some(&item));
+        assert_eq!(data.update_item(id, item_id, &item, Some(2)), Err(DataError::VersionMismatch { expected: 2, actual: 3 }));
+        assert_eq!(data.update_item(id, item_id + 1, &item, None), Err(DataError::ItemNotFound));
+        let item = LineItem::from("test3".to_string(), Money::new(200, Currency::EUR), None);
+        assert!(matches!(data.update_item(id, item_id, &item, None), Err(DataError::Money(_))));
+    }
+
+    #[test]
+    fn test_delete_item() {
+        let data = Memory::new();
+        let id = data.add_bill(&Bill::new("test".to_string(), Currency::USD)).unwrap();
+        let (item_id, _) = data.add_item(id, &LineItem::from("test".to_string(), Money::new(100, Currency::USD), None), None).unwrap();
+        assert_eq!(data.delete_item(id, item_id, None), Ok((item_id, 3)));
+        assert_eq!(data.delete_item(id, item_id, None), Err(DataError::ItemNotFound));
+        assert_eq!(data.get_items(id).unwrap().len(), 0);
     }
 }
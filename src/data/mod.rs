@@ -1,49 +1,569 @@
+mod access;
+pub mod events;
+pub mod history;
 pub mod memory;
+pub mod query;
+pub mod sqlite;
 
-use crate::models::bill::{Bill, BillWithId};
+use std::collections::HashMap;
+use std::fmt;
+use chrono::{DateTime, SubsecRound, Utc};
+use serde::Serialize;
+use crate::models::bill::{Bill, BillError, BillWithId};
+use crate::models::currency::{Currency, MoneyError};
+use crate::models::group::{Group, GroupWithId};
+use crate::models::item::LineItem;
+use crate::models::participant::{ParticipantChange, ParticipantError};
+use crate::models::patch::{BillPatch, PatchError};
+use crate::models::payment::{Payment, PaymentWithId, Reference};
+use crate::models::user::User;
+use crate::models::validate::{finish, FieldError, ValidationError};
+use history::Revision;
+use query::{BillPage, BillQuery};
 use uuid::Uuid;
 
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum DataError {
+    BillNotFound,
+    ItemNotFound,
+    AdjustmentNotFound,
+    /// The bill has no record of the version asked for.
+    RevisionNotFound,
+    GroupNotFound,
+    PaymentNotFound,
+    ParticipantNotFound,
+    UserNotFound,
+    /// The user is not one the bill is shared with.
+    MemberNotFound,
+    /// The user may see what they asked to change, but not change it that
+    /// way, e.g. a member deleting a bill they do not own.
+    Forbidden,
+    /// The bill has changed since the version the caller expected.
+    VersionMismatch { expected: u64, actual: u64 },
+    /// An amount in the change was rejected, e.g. for being in the wrong
+    /// currency.
+    Money(MoneyError),
+    /// The change clashes with what is already stored.
+    Conflict(String),
+    /// The bill the change would leave breaks the limits in
+    /// `crate::models::validate`.
+    Validation(ValidationError),
+    /// The change was rejected for any other reason.
+    Invalid(String),
+    /// The backend itself failed, e.g. the database could not be read.
+    Storage(String),
+}
+
+impl fmt::Display for DataError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DataError::BillNotFound => write!(f, "Bill not found"),
+            DataError::ItemNotFound => write!(f, "Item not found"),
+            DataError::AdjustmentNotFound => write!(f, "Adjustment not found"),
+            DataError::RevisionNotFound => write!(f, "Revision not found"),
+            DataError::GroupNotFound => write!(f, "Group not found"),
+            DataError::PaymentNotFound => write!(f, "Payment not found"),
+            DataError::ParticipantNotFound => write!(f, "Participant not found"),
+            DataError::UserNotFound => write!(f, "User not found"),
+            DataError::MemberNotFound => write!(f, "The bill is not shared with that user"),
+            DataError::Forbidden => write!(f, "Only the owner can do that"),
+            DataError::VersionMismatch { expected, actual } => {
+                write!(f, "Bill is at version {}, not {}", actual, expected)
+            },
+            DataError::Money(err) => write!(f, "{}", err),
+            DataError::Conflict(message) => write!(f, "Conflict: {}", message),
+            DataError::Validation(err) => write!(f, "{}", err),
+            DataError::Invalid(message) => write!(f, "{}", message),
+            DataError::Storage(message) => write!(f, "Storage error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for DataError {}
+
+impl From<BillError> for DataError {
+    fn from(err: BillError) -> Self {
+        match err {
+            BillError::ItemNotFound => DataError::ItemNotFound,
+            BillError::AdjustmentNotFound => DataError::AdjustmentNotFound,
+            BillError::Money(err) => DataError::Money(err),
+            BillError::Participant(err) => err.into(),
+        }
+    }
+}
+
+/// Merging someone into themselves makes no sense whatever is stored; a
+/// merge that leaves an item's portions clashing is a conflict with it.
+impl From<ParticipantError> for DataError {
+    fn from(err: ParticipantError) -> Self {
+        match err {
+            ParticipantError::NotFound => DataError::ParticipantNotFound,
+            ParticipantError::SelfMerge => DataError::Invalid(err.to_string()),
+            ParticipantError::Clash { .. } => DataError::Conflict(err.to_string()),
+        }
+    }
+}
+
+impl From<ValidationError> for DataError {
+    fn from(err: ValidationError) -> Self {
+        DataError::Validation(err)
+    }
+}
+
+/// A patch that cannot be applied clashes with the bill as it is now; a
+/// patch that applies but leaves an invalid bill is rejected.
+impl From<PatchError> for DataError {
+    fn from(err: PatchError) -> Self {
+        match err {
+            PatchError::Failed(_) => DataError::Conflict(err.to_string()),
+            PatchError::Invalid(_) => DataError::Invalid(err.to_string()),
+            PatchError::Money(err) => DataError::Money(err),
+        }
+    }
+}
+
+/// Resolves what a bill refers to against its participants, then fails
+/// unless the bill is valid. Every provider does this before it stores a
+/// bill.
+pub(crate) fn prepare_bill(bill: &mut Bill) -> Result<(), DataError> {
+    bill.resolve_participants()?;
+    Ok(bill.validate()?)
+}
+
+/// `group` as it should be stored, with its members resolved against its
+/// participants. Fails unless that is valid and `exists` finds every bill
+/// in it.
+pub(crate) fn prepare_group(
+    group: &Group,
+    exists: impl Fn(Uuid) -> Result<bool, DataError>,
+) -> Result<Group, DataError> {
+    let mut group = group.clone();
+    group.resolve_participants();
+    group.validate()?;
+    let mut errors = vec![];
+    for (index, id) in group.bills.iter().enumerate() {
+        if !exists(*id)? {
+            errors.push(FieldError { path: format!("bills.{}", index), message: "no such bill".to_string() });
+        }
+    }
+    finish(errors)?;
+    Ok(group)
+}
+
+/// Fails unless `payment` is valid and what it refers to exists: a bill
+/// `bill_currency` finds, in the payment's currency, or a group `group_exists`
+/// finds.
+pub(crate) fn check_payment(
+    payment: &Payment,
+    bill_currency: impl Fn(Uuid) -> Result<Option<Currency>, DataError>,
+    group_exists: impl Fn(Uuid) -> Result<bool, DataError>,
+) -> Result<(), DataError> {
+    payment.validate()?;
+    let missing = |message: &str| vec![FieldError { path: "reference".to_string(), message: message.to_string() }];
+    match payment.reference {
+        Some(Reference::Bill(id)) => match bill_currency(id)? {
+            Some(currency) => payment.amount.expect_currency(currency).map_err(DataError::Money),
+            None => Ok(finish(missing("no such bill"))?),
+        },
+        Some(Reference::Group(id)) if !group_exists(id)? => Ok(finish(missing("no such group"))?),
+        _ => Ok(()),
+    }
+}
+
+/// Payments oldest first by date, and in the order they were recorded on
+/// the same date.
+pub(crate) fn sort_payments(payments: &mut [PaymentWithId]) {
+    payments.sort_by_key(|stored| stored.payment.date);
+}
+
+/// The first version of every bill.
+pub const INITIAL_VERSION: u64 = 1;
+
+/// The time to record for a change, to the microsecond every backend can
+/// store.
+pub(crate) fn now() -> DateTime<Utc> {
+    Utc::now().trunc_subsecs(6)
+}
+
+/// A bill as first stored under `id`.
+pub(crate) fn new_stored(id: Uuid, bill: Bill) -> BillWithId {
+    let now = now();
+    BillWithId {
+        id,
+        version: INITIAL_VERSION,
+        created_at: now,
+        updated_at: now,
+        owner: None,
+        members: vec![],
+        bill,
+    }
+}
+
+/// Records that `stored` has just been changed.
+pub(crate) fn touch(stored: &mut BillWithId) {
+    stored.version += 1;
+    stored.updated_at = now().max(stored.updated_at);
+}
+
+/// Fails unless `actual` is the `expected` version. No expected version
+/// means the caller does not mind which version it changes.
+pub(crate) fn check_version(expected: Option<u64>, actual: u64) -> Result<(), DataError> {
+    match expected {
+        Some(expected) if expected != actual => Err(DataError::VersionMismatch { expected, actual }),
+        _ => Ok(()),
+    }
+}
+
+/// How many bills, groups and payments `Data::assign_owner` gave away.
+#[derive(Debug, Serialize, Clone, Copy, Default, Eq, PartialEq)]
+pub struct Assigned {
+    pub bills: usize,
+    pub groups: usize,
+    pub payments: usize,
+}
+
+/// Storage for bills. Every change to a bill, including to its items, bumps
+/// its version and `updated_at` and is recorded in its history;
+/// `update_bill` and `delete_bill` only go ahead if the bill is still at the
+/// version the caller last saw. Deleting a bill deletes its history too,
+/// takes it out of every group, and leaves the payments towards it with no
+/// reference. A change that would leave the bill invalid (see
+/// `Bill::validate`) is rejected whole.
+///
+/// A store acting for a user only reaches what that user may see; see
+/// `access` for the rules.
 pub trait Data {
-    fn add_bill(&self, bill: &Bill) -> Uuid;
-    fn delete_bill(&self, id: Uuid) -> Result<Uuid, String>;
-    fn get_bill(&self, id: Uuid) -> Option<Bill>;
-    fn get_bills(&self) -> Vec<BillWithId>;
-    fn update_bill(&self, id: Uuid, bill: &Bill) -> Result<Uuid, String>;
+    /// This store, recording `actor` as the one making the changes made
+    /// through it.
+    fn with_actor(&self, actor: Option<String>) -> Self where Self: Sized;
+    /// This store acting for `user`: the changes made through it are
+    /// recorded as theirs, the bills, groups and payments added through it
+    /// are theirs, and it only reaches what they may see.
+    fn with_user(&self, user: String) -> Self where Self: Sized;
+    fn add_bill(&self, bill: &Bill) -> Result<Uuid, DataError>;
+    fn delete_bill(&self, id: Uuid, expected_version: Option<u64>) -> Result<Uuid, DataError>;
+    fn get_versioned_bill(&self, id: Uuid) -> Result<BillWithId, DataError>;
+    fn get_bills(&self) -> Result<Vec<BillWithId>, DataError>;
+    /// The page of bills `query` asks for. By default every bill is loaded
+    /// and filtered in memory.
+    fn query_bills(&self, query: &BillQuery) -> Result<BillPage, DataError> {
+        let mut bills = self.get_bills()?;
+        if query.settled.is_some() {
+            query.retain_settled(&mut bills, &self.get_payments()?)?;
+        }
+        query.page(bills)
+    }
+    /// Replaces the bill and returns its new version.
+    fn update_bill(&self, id: Uuid, bill: &Bill, expected_version: Option<u64>) -> Result<u64, DataError>;
+    /// Applies `patch` to the stored bill as one change, or not at all, and
+    /// returns the patched bill.
+    fn patch_bill(&self, id: Uuid, patch: &BillPatch, expected_version: Option<u64>) -> Result<BillWithId, DataError>;
+    /// Adds `item` to the bill and returns its id and the bill's new
+    /// version. `update_item` and `delete_item` return the same pair.
+    fn add_item(&self, bill_id: Uuid, item: &LineItem, expected_version: Option<u64>) -> Result<(u16, u64), DataError>;
+    fn get_items(&self, bill_id: Uuid) -> Result<HashMap<u16, LineItem>, DataError>;
+    fn update_item(&self, bill_id: Uuid, item_id: u16, item: &LineItem, expected_version: Option<u64>) -> Result<(u16, u64), DataError>;
+    fn delete_item(&self, bill_id: Uuid, item_id: u16, expected_version: Option<u64>) -> Result<(u16, u64), DataError>;
+    /// Every recorded change to the bill, oldest first.
+    fn get_history(&self, id: Uuid) -> Result<Vec<Revision>, DataError>;
+    /// Puts the bill back as it was at `version`, as a new change, and
+    /// returns the result.
+    fn revert_bill(&self, id: Uuid, version: u64, expected_version: Option<u64>) -> Result<BillWithId, DataError>;
+    /// Applies `change` to the bill's participants, and to whatever on the
+    /// bill refers to them, as one change, and returns the result.
+    fn change_participants(&self, id: Uuid, change: &ParticipantChange, expected_version: Option<u64>) -> Result<BillWithId, DataError>;
+    /// Shares the bill with the user called `username`, as a change to it.
+    /// Only the bill's owner may.
+    fn invite_member(&self, id: Uuid, username: &str, expected_version: Option<u64>) -> Result<BillWithId, DataError>;
+    /// Stops sharing the bill with `username`, as a change to it. The owner
+    /// may remove anyone; members may remove themselves.
+    fn remove_member(&self, id: Uuid, username: &str, expected_version: Option<u64>) -> Result<BillWithId, DataError>;
+    /// Stores a new group. The group must be valid and every bill in it must
+    /// exist, as for `update_group`.
+    fn add_group(&self, group: &Group) -> Result<Uuid, DataError>;
+    fn get_group(&self, id: Uuid) -> Result<Group, DataError>;
+    /// Every group, sorted by name.
+    fn get_groups(&self) -> Result<Vec<GroupWithId>, DataError>;
+    fn update_group(&self, id: Uuid, group: &Group) -> Result<Uuid, DataError>;
+    /// Deleting a group keeps the payments towards it, as for bills.
+    fn delete_group(&self, id: Uuid) -> Result<Uuid, DataError>;
+    /// Applies `change` to the group's participants and returns the group.
+    /// Groups are not versioned, so as with `update_group` the last change
+    /// wins.
+    fn change_group_participants(&self, id: Uuid, change: &ParticipantChange) -> Result<Group, DataError> {
+        let mut group = self.get_group(id)?;
+        group.change_participants(change.clone())?;
+        self.update_group(id, &group)?;
+        self.get_group(id)
+    }
+    /// Records a payment. The bill or group it refers to must exist, and a
+    /// payment towards a bill must be in the bill's currency.
+    fn add_payment(&self, payment: &Payment) -> Result<Uuid, DataError>;
+    fn get_payment(&self, id: Uuid) -> Result<Payment, DataError>;
+    /// Every payment, oldest first by date.
+    fn get_payments(&self) -> Result<Vec<PaymentWithId>, DataError>;
+    /// Only whoever recorded a payment may delete it.
+    fn delete_payment(&self, id: Uuid) -> Result<Uuid, DataError>;
+    /// Stores a new account. Fails with a conflict if the username is
+    /// taken.
+    fn add_user(&self, user: &User) -> Result<(), DataError>;
+    fn get_user(&self, username: &str) -> Result<User, DataError>;
+    /// Gives every bill, group and payment that has no owner to the user
+    /// called `username`, e.g. those stored before there were accounts.
+    /// Each bill gets a new version, as when it is shared. Only a store
+    /// acting for nobody may.
+    fn assign_owner(&self, username: &str) -> Result<Assigned, DataError>;
+
+    fn get_bill(&self, id: Uuid) -> Result<Bill, DataError> {
+        self.get_versioned_bill(id).map(|stored| stored.bill)
+    }
 }
 
 #[derive(Clone)]
 pub enum DataProvider {
-    Memory(memory::Memory)
+    Memory(memory::Memory),
+    Sqlite(sqlite::Sqlite),
+    Events(events::EventStore),
 }
 
 impl Data for DataProvider {
-    fn add_bill(&self, bill: &Bill) -> Uuid {
+    fn with_actor(&self, actor: Option<String>) -> Self {
+        match self {
+            DataProvider::Memory(memory) => DataProvider::Memory(memory.with_actor(actor)),
+            DataProvider::Sqlite(sqlite) => DataProvider::Sqlite(sqlite.with_actor(actor)),
+            DataProvider::Events(events) => DataProvider::Events(events.with_actor(actor)),
+        }
+    }
+
+    fn with_user(&self, user: String) -> Self {
+        match self {
+            DataProvider::Memory(memory) => DataProvider::Memory(memory.with_user(user)),
+            DataProvider::Sqlite(sqlite) => DataProvider::Sqlite(sqlite.with_user(user)),
+            DataProvider::Events(events) => DataProvider::Events(events.with_user(user)),
+        }
+    }
+
+    fn add_bill(&self, bill: &Bill) -> Result<Uuid, DataError> {
+        match self {
+            DataProvider::Memory(memory) => memory.add_bill(bill),
+            DataProvider::Sqlite(sqlite) => sqlite.add_bill(bill),
+            DataProvider::Events(events) => events.add_bill(bill),
+        }
+    }
+
+    fn delete_bill(&self, id: Uuid, expected_version: Option<u64>) -> Result<Uuid, DataError> {
+        match self {
+            DataProvider::Memory(memory) => memory.delete_bill(id, expected_version),
+            DataProvider::Sqlite(sqlite) => sqlite.delete_bill(id, expected_version),
+            DataProvider::Events(events) => events.delete_bill(id, expected_version),
+        }
+    }
+
+    fn get_versioned_bill(&self, id: Uuid) -> Result<BillWithId, DataError> {
+        match self {
+            DataProvider::Memory(memory) => memory.get_versioned_bill(id),
+            DataProvider::Sqlite(sqlite) => sqlite.get_versioned_bill(id),
+            DataProvider::Events(events) => events.get_versioned_bill(id),
+        }
+    }
+
+    fn get_bills(&self) -> Result<Vec<BillWithId>, DataError> {
+        match self {
+            DataProvider::Memory(memory) => memory.get_bills(),
+            DataProvider::Sqlite(sqlite) => sqlite.get_bills(),
+            DataProvider::Events(events) => events.get_bills(),
+        }
+    }
+
+    fn query_bills(&self, query: &BillQuery) -> Result<BillPage, DataError> {
+        match self {
+            DataProvider::Memory(memory) => memory.query_bills(query),
+            DataProvider::Sqlite(sqlite) => sqlite.query_bills(query),
+            DataProvider::Events(events) => events.query_bills(query),
+        }
+    }
+
+    fn update_bill(&self, id: Uuid, bill: &Bill, expected_version: Option<u64>) -> Result<u64, DataError> {
+        match self {
+            DataProvider::Memory(memory) => memory.update_bill(id, bill, expected_version),
+            DataProvider::Sqlite(sqlite) => sqlite.update_bill(id, bill, expected_version),
+            DataProvider::Events(events) => events.update_bill(id, bill, expected_version),
+        }
+    }
+
+    fn patch_bill(&self, id: Uuid, patch: &BillPatch, expected_version: Option<u64>) -> Result<BillWithId, DataError> {
+        match self {
+            DataProvider::Memory(memory) => memory.patch_bill(id, patch, expected_version),
+            DataProvider::Sqlite(sqlite) => sqlite.patch_bill(id, patch, expected_version),
+            DataProvider::Events(events) => events.patch_bill(id, patch, expected_version),
+        }
+    }
+
+    fn add_item(&self, bill_id: Uuid, item: &LineItem, expected_version: Option<u64>) -> Result<(u16, u64), DataError> {
+        match self {
+            DataProvider::Memory(memory) => memory.add_item(bill_id, item, expected_version),
+            DataProvider::Sqlite(sqlite) => sqlite.add_item(bill_id, item, expected_version),
+            DataProvider::Events(events) => events.add_item(bill_id, item, expected_version),
+        }
+    }
+
+    fn get_items(&self, bill_id: Uuid) -> Result<HashMap<u16, LineItem>, DataError> {
+        match self {
+            DataProvider::Memory(memory) => memory.get_items(bill_id),
+            DataProvider::Sqlite(sqlite) => sqlite.get_items(bill_id),
+            DataProvider::Events(events) => events.get_items(bill_id),
+        }
+    }
+
+    fn update_item(&self, bill_id: Uuid, item_id: u16, item: &LineItem, expected_version: Option<u64>) -> Result<(u16, u64), DataError> {
+        match self {
+            DataProvider::Memory(memory) => memory.update_item(bill_id, item_id, item, expected_version),
+            DataProvider::Sqlite(sqlite) => sqlite.update_item(bill_id, item_id, item, expected_version),
+            DataProvider::Events(events) => events.update_item(bill_id, item_id, item, expected_version),
+        }
+    }
+
+    fn delete_item(&self, bill_id: Uuid, item_id: u16, expected_version: Option<u64>) -> Result<(u16, u64), DataError> {
+        match self {
+            DataProvider::Memory(memory) => memory.delete_item(bill_id, item_id, expected_version),
+            DataProvider::Sqlite(sqlite) => sqlite.delete_item(bill_id, item_id, expected_version),
+            DataProvider::Events(events) => events.delete_item(bill_id, item_id, expected_version),
+        }
+    }
+
+    fn get_history(&self, id: Uuid) -> Result<Vec<Revision>, DataError> {
+        match self {
+            DataProvider::Memory(memory) => memory.get_history(id),
+            DataProvider::Sqlite(sqlite) => sqlite.get_history(id),
+            DataProvider::Events(events) => events.get_history(id),
+        }
+    }
+
+    fn revert_bill(&self, id: Uuid, version: u64, expected_version: Option<u64>) -> Result<BillWithId, DataError> {
+        match self {
+            DataProvider::Memory(memory) => memory.revert_bill(id, version, expected_version),
+            DataProvider::Sqlite(sqlite) => sqlite.revert_bill(id, version, expected_version),
+            DataProvider::Events(events) => events.revert_bill(id, version, expected_version),
+        }
+    }
+
+    fn change_participants(&self, id: Uuid, change: &ParticipantChange, expected_version: Option<u64>) -> Result<BillWithId, DataError> {
+        match self {
+            DataProvider::Memory(memory) => memory.change_participants(id, change, expected_version),
+            DataProvider::Sqlite(sqlite) => sqlite.change_participants(id, change, expected_version),
+            DataProvider::Events(events) => events.change_participants(id, change, expected_version),
+        }
+    }
+
+    fn invite_member(&self, id: Uuid, username: &str, expected_version: Option<u64>) -> Result<BillWithId, DataError> {
+        match self {
+            DataProvider::Memory(memory) => memory.invite_member(id, username, expected_version),
+            DataProvider::Sqlite(sqlite) => sqlite.invite_member(id, username, expected_version),
+            DataProvider::Events(events) => events.invite_member(id, username, expected_version),
+        }
+    }
+
+    fn remove_member(&self, id: Uuid, username: &str, expected_version: Option<u64>) -> Result<BillWithId, DataError> {
+        match self {
+            DataProvider::Memory(memory) => memory.remove_member(id, username, expected_version),
+            DataProvider::Sqlite(sqlite) => sqlite.remove_member(id, username, expected_version),
+            DataProvider::Events(events) => events.remove_member(id, username, expected_version),
+        }
+    }
+
+    fn add_group(&self, group: &Group) -> Result<Uuid, DataError> {
+        match self {
+            DataProvider::Memory(memory) => memory.add_group(group),
+            DataProvider::Sqlite(sqlite) => sqlite.add_group(group),
+            DataProvider::Events(events) => events.add_group(group),
+        }
+    }
+
+    fn get_group(&self, id: Uuid) -> Result<Group, DataError> {
+        match self {
+            DataProvider::Memory(memory) => memory.get_group(id),
+            DataProvider::Sqlite(sqlite) => sqlite.get_group(id),
+            DataProvider::Events(events) => events.get_group(id),
+        }
+    }
+
+    fn get_groups(&self) -> Result<Vec<GroupWithId>, DataError> {
+        match self {
+            DataProvider::Memory(memory) => memory.get_groups(),
+            DataProvider::Sqlite(sqlite) => sqlite.get_groups(),
+            DataProvider::Events(events) => events.get_groups(),
+        }
+    }
+
+    fn update_group(&self, id: Uuid, group: &Group) -> Result<Uuid, DataError> {
+        match self {
+            DataProvider::Memory(memory) => memory.update_group(id, group),
+            DataProvider::Sqlite(sqlite) => sqlite.update_group(id, group),
+            DataProvider::Events(events) => events.update_group(id, group),
+        }
+    }
+
+    fn delete_group(&self, id: Uuid) -> Result<Uuid, DataError> {
+        match self {
+            DataProvider::Memory(memory) => memory.delete_group(id),
+            DataProvider::Sqlite(sqlite) => sqlite.delete_group(id),
+            DataProvider::Events(events) => events.delete_group(id),
+        }
+    }
+
+    fn add_payment(&self, payment: &Payment) -> Result<Uuid, DataError> {
+        match self {
+            DataProvider::Memory(memory) => memory.add_payment(payment),
+            DataProvider::Sqlite(sqlite) => sqlite.add_payment(payment),
+            DataProvider::Events(events) => events.add_payment(payment),
+        }
+    }
+
+    fn get_payment(&self, id: Uuid) -> Result<Payment, DataError> {
+        match self {
+            DataProvider::Memory(memory) => memory.get_payment(id),
+            DataProvider::Sqlite(sqlite) => sqlite.get_payment(id),
+            DataProvider::Events(events) => events.get_payment(id),
+        }
+    }
+
+    fn get_payments(&self) -> Result<Vec<PaymentWithId>, DataError> {
         match self {
-            DataProvider::Memory(memory) => memory.add_bill(bill)
+            DataProvider::Memory(memory) => memory.get_payments(),
+            DataProvider::Sqlite(sqlite) => sqlite.get_payments(),
+            DataProvider::Events(events) => events.get_payments(),
         }
     }
 
-    fn delete_bill(&self, id: Uuid) -> Result<Uuid, String> {
+    fn delete_payment(&self, id: Uuid) -> Result<Uuid, DataError> {
         match self {
-            DataProvider::Memory(memory) => memory.delete_bill(id)
+            DataProvider::Memory(memory) => memory.delete_payment(id),
+            DataProvider::Sqlite(sqlite) => sqlite.delete_payment(id),
+            DataProvider::Events(events) => events.delete_payment(id),
         }
     }
 
-    fn get_bill(&self, id: Uuid) -> Option<Bill> {
+    fn add_user(&self, user: &User) -> Result<(), DataError> {
         match self {
-            DataProvider::Memory(memory) => memory.get_bill(id)
+            DataProvider::Memory(memory) => memory.add_user(user),
+            DataProvider::Sqlite(sqlite) => sqlite.add_user(user),
+            DataProvider::Events(events) => events.add_user(user),
         }
     }
 
-    fn get_bills(&self) -> Vec<BillWithId> {
+    fn get_user(&self, username: &str) -> Result<User, DataError> {
         match self {
-            DataProvider::Memory(memory) => memory.get_bills()
+            DataProvider::Memory(memory) => memory.get_user(username),
+            DataProvider::Sqlite(sqlite) => sqlite.get_user(username),
+            DataProvider::Events(events) => events.get_user(username),
         }
     }
 
-    fn update_bill(&self, id: Uuid, bill: &Bill) -> Result<Uuid, String> {
+    fn assign_owner(&self, username: &str) -> Result<Assigned, DataError> {
         match self {
-            DataProvider::Memory(memory) => memory.update_bill(id, bill)
+            DataProvider::Memory(memory) => memory.assign_owner(username),
+            DataProvider::Sqlite(sqlite) => sqlite.assign_owner(username),
+            DataProvider::Events(events) => events.assign_owner(username),
         }
     }
 }
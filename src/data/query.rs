@@ -0,0 +1,345 @@
+//! Filtering, sorting and pagination for listing bills.
+use std::cmp::Ordering;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::data::DataError;
+use crate::models::bill::BillWithId;
+use crate::models::currency::Currency;
+use crate::models::payment::{for_bill, PaymentWithId};
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    #[default]
+    Created,
+    Updated,
+    Name,
+    /// By currency, then by total within each currency.
+    Total,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// A bill's position in a sort order. Bills with the same value are ordered
+/// by id, so every position is unique.
+#[derive(Debug, Deserialize, Serialize, Clone, Eq, Ord, PartialEq, PartialOrd)]
+#[serde(rename_all = "snake_case")]
+pub enum SortValue {
+    /// Microseconds since the Unix epoch.
+    Created(i64),
+    Updated(i64),
+    Name(String),
+    /// The total in minor units.
+    Total(Currency, i64),
+}
+
+impl SortValue {
+    pub fn of(key: SortKey, stored: &BillWithId) -> Result<Self, DataError> {
+        Ok(match key {
+            SortKey::Created => SortValue::Created(stored.created_at.timestamp_micros()),
+            SortKey::Updated => SortValue::Updated(stored.updated_at.timestamp_micros()),
+            SortKey::Name => SortValue::Name(stored.bill.name.clone()),
+            SortKey::Total => {
+                let total = stored.bill.calculate_total().map_err(DataError::Money)?;
+                SortValue::Total(total.currency(), total.amount())
+            },
+        })
+    }
+
+    fn key(&self) -> SortKey {
+        match self {
+            SortValue::Created(_) => SortKey::Created,
+            SortValue::Updated(_) => SortKey::Updated,
+            SortValue::Name(_) => SortKey::Name,
+            SortValue::Total(..) => SortKey::Total,
+        }
+    }
+}
+
+/// The last bill on a page; the next page starts after it. Clients should
+/// treat it as opaque.
+#[derive(Debug, Deserialize, Serialize, Clone, Eq, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub struct Cursor {
+    pub value: SortValue,
+    pub id: Uuid,
+}
+
+impl TryFrom<String> for Cursor {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let invalid = || format!("invalid cursor {:?}", value);
+        if !value.len().is_multiple_of(2) || !value.is_ascii() {
+            return Err(invalid());
+        }
+        let bytes = (0..value.len()).step_by(2)
+            .map(|index| u8::from_str_radix(&value[index..index + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| invalid())?;
+        let (value, id) = serde_json::from_slice(&bytes).map_err(|_| invalid())?;
+        Ok(Cursor { value, id })
+    }
+}
+
+impl From<Cursor> for String {
+    fn from(cursor: Cursor) -> Self {
+        serde_json::to_vec(&(cursor.value, cursor.id)).unwrap().iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+/// Which bills to list and in what order. Every filter is optional.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, Eq, PartialEq)]
+#[serde(default)]
+pub struct BillQuery {
+    /// Only bills whose name contains this, ignoring ASCII case.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Only bills with an item or adjustment for this orderer.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub participant: Option<String>,
+    /// Only bills created at or after this time.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_since: Option<DateTime<Utc>>,
+    /// Only bills created before this time.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_before: Option<DateTime<Utc>>,
+    /// Only bills that are settled, or only those that are not, counting
+    /// the payments made towards them. See `SettlementPlan::settled`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub settled: Option<bool>,
+    pub sort: SortKey,
+    pub order: SortOrder,
+    /// At most this many bills; all of them if not set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<Cursor>,
+}
+
+/// One page of bills, and where the next one starts if there is one.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BillPage {
+    pub bills: Vec<BillWithId>,
+    pub next: Option<Cursor>,
+}
+
+impl BillQuery {
+    pub fn matches(&self, stored: &BillWithId) -> bool {
+        let name = self.name.as_ref().is_none_or(|name| {
+            stored.bill.name.to_ascii_lowercase().contains(&name.to_ascii_lowercase())
+        });
+        let participant = self.participant.as_ref().is_none_or(|participant| {
+            stored.bill.get_orderers().contains(&stored.bill.participants().reference(participant))
+        });
+        let since = self.created_since.is_none_or(|since| stored.created_at >= since);
+        let before = self.created_before.is_none_or(|before| stored.created_at < before);
+        name && participant && since && before
+    }
+
+    /// Leaves out the bills that are not settled as `settled` asks, counting
+    /// `payments` towards them. Keeps every bill if `settled` is not set.
+    pub fn retain_settled(&self, bills: &mut Vec<BillWithId>, payments: &[PaymentWithId]) -> Result<(), DataError> {
+        if let Some(settled) = self.settled {
+            let mut kept = Vec::with_capacity(bills.len());
+            for stored in bills.drain(..) {
+                let plan = stored.bill.get_settlement_plan_with(for_bill(payments, stored.id)).map_err(DataError::Money)?;
+                if plan.settled == settled {
+                    kept.push(stored);
+                }
+            }
+            *bills = kept;
+        }
+        Ok(())
+    }
+
+    /// The cursor to continue from, if it belongs to this query's sort key.
+    pub fn after(&self) -> Result<Option<&Cursor>, DataError> {
+        match &self.cursor {
+            Some(cursor) if cursor.value.key() != self.sort => {
+                Err(DataError::Invalid("The cursor belongs to a different sort key".to_string()))
+            },
+            cursor => Ok(cursor.as_ref()),
+        }
+    }
+
+    /// How two positions compare in this query's order.
+    pub fn compare(&self, a: (&SortValue, Uuid), b: (&SortValue, Uuid)) -> Ordering {
+        match self.order {
+            SortOrder::Asc => a.cmp(&b),
+            SortOrder::Desc => b.cmp(&a),
+        }
+    }
+
+    /// The page of `bills` this query asks for, filtering and sorting them
+    /// all in memory. Backends that can do better need only hand
+    /// `paginate` the bills past the cursor in order.
+    pub fn page(&self, bills: Vec<BillWithId>) -> Result<BillPage, DataError> {
+        let after = self.after()?;
+        let mut bills = bills.into_iter()
+            .filter(|stored| self.matches(stored))
+            .map(|stored| Ok((SortValue::of(self.sort, &stored)?, stored)))
+            .collect::<Result<Vec<(SortValue, BillWithId)>, DataError>>()?;
+        bills.retain(|(value, stored)| after.is_none_or(|after| {
+            self.compare((value, stored.id), (&after.value, after.id)) == Ordering::Greater
+        }));
+        bills.sort_by(|(a, a_bill), (b, b_bill)| self.compare((a, a_bill.id), (b, b_bill.id)));
+        self.paginate(bills.into_iter().map(|(_, stored)| stored).collect())
+    }
+
+    /// Cuts matching bills, already in order and past the cursor, down to
+    /// `limit`.
+    pub fn paginate(&self, mut bills: Vec<BillWithId>) -> Result<BillPage, DataError> {
+        let next = match self.limit {
+            Some(limit) if bills.len() > limit => {
+                bills.truncate(limit);
+                match bills.last() {
+                    Some(last) => Some(Cursor { value: SortValue::of(self.sort, last)?, id: last.id }),
+                    None => None,
+                }
+            },
+            _ => None,
+        };
+        Ok(BillPage { bills, next })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, TimeZone};
+    use crate::models::bill::Bill;
+    use crate::models::currency::Money;
+    use crate::models::item::LineItem;
+    use crate::models::participant::{Participant, ParticipantChange};
+    use crate::models::payer::Payer;
+    use crate::models::payment::{Payment, Reference};
+
+    fn stored(name: &str, total: i64, day: u32, orderer: &str) -> BillWithId {
+        let mut bill = Bill::new(name.to_string(), Currency::USD);
+        bill.add_item(LineItem::from("item".to_string(), Money::new(total, Currency::USD), Some(orderer.to_string()))).unwrap();
+        BillWithId {
+            id: Uuid::new_v4(),
+            version: 1,
+            created_at: Utc.with_ymd_and_hms(2024, 1, day, 12, 0, 0).unwrap(),
+            updated_at: Utc.with_ymd_and_hms(2024, 2, 6 - day, 12, 0, 0).unwrap(),
+            owner: None,
+            members: vec![],
+            bill,
+        }
+    }
+
+    fn names(page: &BillPage) -> Vec<&str> {
+        page.bills.iter().map(|stored| stored.bill.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_filter_and_sort() {
+        let bills = vec![
+            stored("Dinner", 3000, 3, "alice"),
+            stored("lunch", 1000, 1, "bob"),
+            stored("late dinner", 2000, 2, "bob"),
+        ];
+        let page = BillQuery::default().page(bills.clone()).unwrap();
+        assert_eq!(names(&page), vec!["lunch", "late dinner", "Dinner"]);
+        assert_eq!(page.next, None);
+
+        let query = BillQuery { sort: SortKey::Updated, ..BillQuery::default() };
+        assert_eq!(names(&query.page(bills.clone()).unwrap()), vec!["Dinner", "late dinner", "lunch"]);
+
+        let query = BillQuery { name: Some("DINNER".to_string()), sort: SortKey::Total, ..BillQuery::default() };
+        assert_eq!(names(&query.page(bills.clone()).unwrap()), vec!["late dinner", "Dinner"]);
+
+        let query = BillQuery {
+            participant: Some("bob".to_string()),
+            sort: SortKey::Name,
+            order: SortOrder::Desc,
+            ..BillQuery::default()
+        };
+        assert_eq!(names(&query.page(bills.clone()).unwrap()), vec!["lunch", "late dinner"]);
+
+        let query = BillQuery {
+            created_since: Some(Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap()),
+            created_before: Some(Utc.with_ymd_and_hms(2024, 1, 3, 12, 0, 0).unwrap()),
+            ..BillQuery::default()
+        };
+        assert_eq!(names(&query.page(bills).unwrap()), vec!["late dinner"]);
+    }
+
+    #[test]
+    fn test_filter_by_registered_participant() {
+        let mut bills = vec![stored("dinner", 3000, 1, "Bob"), stored("lunch", 1000, 2, "bob")];
+        let bob = Uuid::new_v4();
+        let participant = Participant { name: "Bob".to_string(), aliases: vec!["Robert".to_string()] };
+        bills[0].bill.change_participants(ParticipantChange::Added { id: bob, participant }).unwrap();
+        assert_eq!(bills[0].bill.get_orderers(), vec![bob.to_string()]);
+
+        for participant in ["Bob", " robert ", &bob.to_string()] {
+            let query = BillQuery { participant: Some(participant.to_string()), ..BillQuery::default() };
+            assert_eq!(names(&query.page(bills.clone()).unwrap()), vec!["dinner"], "{}", participant);
+        }
+        // only bills that registered Bob know "bob" is him
+        let query = BillQuery { participant: Some("bob".to_string()), ..BillQuery::default() };
+        assert_eq!(names(&query.page(bills).unwrap()), vec!["dinner", "lunch"]);
+    }
+
+    #[test]
+    fn test_pages() {
+        let bills = (1..=5).map(|day| stored(&format!("bill {}", day), 100, day, "alice")).collect::<Vec<BillWithId>>();
+        let mut query = BillQuery { order: SortOrder::Desc, limit: Some(2), ..BillQuery::default() };
+        let mut seen = vec![];
+        loop {
+            let page = query.page(bills.clone()).unwrap();
+            seen.extend(names(&page).into_iter().map(String::from));
+            match page.next {
+                Some(next) => query.cursor = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(seen, vec!["bill 5", "bill 4", "bill 3", "bill 2", "bill 1"]);
+
+        query.sort = SortKey::Name;
+        assert!(matches!(query.page(bills), Err(DataError::Invalid(_))));
+    }
+
+    #[test]
+    fn test_settled() {
+        let mut bills = vec![stored("lunch", 1000, 1, "alice"), stored("dinner", 3000, 2, "bob")];
+        for stored in &mut bills {
+            let total = stored.bill.calculate_total().unwrap();
+            stored.bill.set_payers(vec![Payer::new("carol".to_string(), total)]).unwrap();
+        }
+        let date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        let mut payment = Payment::new("alice".to_string(), "carol".to_string(), Money::new(1000, Currency::USD), date);
+        payment.reference = Some(Reference::Bill(bills[0].id));
+        let payments = vec![PaymentWithId { id: Uuid::new_v4(), payment }];
+
+        let query = BillQuery { settled: Some(true), ..BillQuery::default() };
+        let mut settled = bills.clone();
+        query.retain_settled(&mut settled, &payments).unwrap();
+        assert_eq!(names(&query.page(settled).unwrap()), vec!["lunch"]);
+
+        let query = BillQuery { settled: Some(false), ..BillQuery::default() };
+        let mut unsettled = bills;
+        query.retain_settled(&mut unsettled, &payments).unwrap();
+        assert_eq!(names(&query.page(unsettled).unwrap()), vec!["dinner"]);
+    }
+
+    #[test]
+    fn test_cursor() {
+        let cursor = Cursor { value: SortValue::Name("dinner".to_string()), id: Uuid::new_v4() };
+        let encoded = String::from(cursor.clone());
+        assert_eq!(Cursor::try_from(encoded), Ok(cursor));
+        assert!(Cursor::try_from("zz".to_string()).is_err());
+        assert!(Cursor::try_from("7b7d".to_string()).is_err());
+    }
+}
@@ -0,0 +1,1596 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use chrono::{DateTime, NaiveDate};
+use rusqlite::{params, params_from_iter, Connection, ErrorCode, OptionalExtension};
+use rusqlite::functions::FunctionFlags;
+use rusqlite::types::{Type, Value};
+use serde::{de::DeserializeOwned, Serialize};
+use crate::models::bill::{Bill, BillError, BillWithId};
+use crate::models::item::{LineItem, Portion};
+use crate::models::currency::{Currency, Money};
+use crate::models::adjustment::{Adjustment, AdjustmentKind, AllocationRule, TipBase};
+use crate::models::participant::{normalize, Participant, ParticipantChange, Participants};
+use crate::models::patch::{BillPatch, PatchError};
+use crate::models::group::{Group, GroupWithId};
+use crate::models::payer::Payer;
+use crate::models::payment::{Payment, PaymentWithId, Reference};
+use crate::models::user::User;
+use uuid::Uuid;
+use crate::data::{check_payment, check_version, new_stored, prepare_bill, prepare_group, touch, Assigned, Data, DataError};
+use crate::data::access::{self, can_see, check_owner};
+use crate::data::history::{self, Change, Revision};
+use crate::data::query::{BillPage, BillQuery, SortKey, SortOrder, SortValue};
+
+/// Schema changes, applied in order. The database's `user_version` records
+/// how many have been applied, so new migrations must only be appended.
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE bills (
+        id TEXT PRIMARY KEY NOT NULL,
+        name TEXT NOT NULL,
+        currency TEXT NOT NULL,
+        total INTEGER,
+        counter INTEGER NOT NULL,
+        rounding TEXT NOT NULL
+    );
+    CREATE TABLE items (
+        bill_id TEXT NOT NULL REFERENCES bills(id) ON DELETE CASCADE,
+        item_id INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        price INTEGER NOT NULL,
+        PRIMARY KEY (bill_id, item_id)
+    );
+    CREATE TABLE item_orderers (
+        bill_id TEXT NOT NULL,
+        item_id INTEGER NOT NULL,
+        orderer TEXT NOT NULL,
+        portion TEXT NOT NULL,
+        value INTEGER,
+        PRIMARY KEY (bill_id, item_id, orderer),
+        FOREIGN KEY (bill_id, item_id) REFERENCES items(bill_id, item_id) ON DELETE CASCADE
+    );
+    CREATE TABLE adjustments (
+        bill_id TEXT NOT NULL REFERENCES bills(id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        rate INTEGER,
+        amount INTEGER,
+        tip_base TEXT,
+        allocation TEXT NOT NULL,
+        assigned_to TEXT,
+        PRIMARY KEY (bill_id, position)
+    );",
+    "ALTER TABLE bills ADD COLUMN version INTEGER NOT NULL DEFAULT 1;",
+    "CREATE INDEX bills_name ON bills (name, id);
+    CREATE INDEX item_orderers_orderer ON item_orderers (orderer);",
+    // Bills stored before this get the current time, keeping their order.
+    "ALTER TABLE bills ADD COLUMN created_at INTEGER NOT NULL DEFAULT 0;
+    UPDATE bills SET created_at = CAST((julianday('now') - 2440587.5) * 86400000000 AS INTEGER) + rowid;
+    ALTER TABLE bills ADD COLUMN updated_at INTEGER NOT NULL DEFAULT 0;
+    UPDATE bills SET updated_at = created_at;
+    CREATE INDEX bills_created_at ON bills (created_at, id);
+    CREATE INDEX bills_updated_at ON bills (updated_at, id);",
+    "CREATE TABLE revisions (
+        bill_id TEXT NOT NULL REFERENCES bills(id) ON DELETE CASCADE,
+        version INTEGER NOT NULL,
+        at INTEGER NOT NULL,
+        actor TEXT,
+        change TEXT NOT NULL,
+        bill TEXT NOT NULL,
+        PRIMARY KEY (bill_id, version)
+    );",
+    "CREATE TABLE payers (
+        bill_id TEXT NOT NULL REFERENCES bills(id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        amount INTEGER NOT NULL,
+        PRIMARY KEY (bill_id, position)
+    );",
+    "CREATE TABLE groups (
+        id TEXT PRIMARY KEY NOT NULL,
+        name TEXT NOT NULL
+    );
+    CREATE TABLE group_members (
+        group_id TEXT NOT NULL REFERENCES groups(id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        PRIMARY KEY (group_id, position)
+    );
+    CREATE TABLE group_bills (
+        group_id TEXT NOT NULL REFERENCES groups(id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        bill_id TEXT NOT NULL REFERENCES bills(id) ON DELETE CASCADE,
+        PRIMARY KEY (group_id, position)
+    );
+    CREATE INDEX group_bills_bill_id ON group_bills (bill_id);",
+    "CREATE TABLE payments (
+        id TEXT PRIMARY KEY NOT NULL,
+        from_person TEXT NOT NULL,
+        to_person TEXT NOT NULL,
+        amount INTEGER NOT NULL,
+        currency TEXT NOT NULL,
+        date TEXT NOT NULL,
+        note TEXT,
+        bill_id TEXT REFERENCES bills(id) ON DELETE SET NULL,
+        group_id TEXT REFERENCES groups(id) ON DELETE SET NULL
+    );
+    CREATE INDEX payments_date ON payments (date);
+    CREATE INDEX payments_bill_id ON payments (bill_id);
+    CREATE INDEX payments_group_id ON payments (group_id);",
+    // Aliases are a JSON array; nothing looks inside them but the bill.
+    "CREATE TABLE participants (
+        bill_id TEXT NOT NULL REFERENCES bills(id) ON DELETE CASCADE,
+        id TEXT NOT NULL,
+        name TEXT NOT NULL,
+        aliases TEXT NOT NULL,
+        PRIMARY KEY (bill_id, id)
+    );
+    CREATE TABLE group_participants (
+        group_id TEXT NOT NULL REFERENCES groups(id) ON DELETE CASCADE,
+        id TEXT NOT NULL,
+        name TEXT NOT NULL,
+        aliases TEXT NOT NULL,
+        PRIMARY KEY (group_id, id)
+    );",
+    // Bills, groups and payments stored before this have no owner, so only
+    // a store acting for nobody reaches them until `assign_owner` gives them
+    // to a user.
+    "ALTER TABLE bills ADD COLUMN owner TEXT;
+    CREATE INDEX bills_owner ON bills (owner);
+    CREATE TABLE bill_members (
+        bill_id TEXT NOT NULL REFERENCES bills(id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        username TEXT NOT NULL,
+        PRIMARY KEY (bill_id, position)
+    );
+    CREATE INDEX bill_members_username ON bill_members (username, bill_id);
+    ALTER TABLE groups ADD COLUMN owner TEXT;
+    ALTER TABLE payments ADD COLUMN owner TEXT;
+    CREATE TABLE users (
+        username TEXT PRIMARY KEY NOT NULL,
+        password_hash TEXT NOT NULL
+    );",
+];
+
+/// Bills stored in a SQLite database. Amounts are stored in minor units of
+/// the bill's currency, so a bill must pass `Bill::check_currency` before it
+/// is stored.
+#[derive(Clone)]
+pub struct Sqlite {
+    connection: Arc<Mutex<Connection>>,
+    actor: Option<String>,
+    user: Option<String>,
+}
+
+impl Sqlite {
+    /// Opens or creates the database at `path` and brings its schema up to
+    /// date.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, DataError> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    /// A database that only lives as long as this provider.
+    pub fn in_memory() -> Result<Self, DataError> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(mut connection: Connection) -> Result<Self, DataError> {
+        connection.pragma_update(None, "foreign_keys", true)?;
+        // so that queries match participants' names as `Participants` does
+        connection.create_scalar_function(
+            "normalize_name",
+            1,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            |context| Ok(normalize(&context.get::<String>(0)?)),
+        )?;
+        migrate(&mut connection, MIGRATIONS)?;
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+            actor: None,
+            user: None,
+        })
+    }
+
+    /// Locks the connection. Every change runs in a transaction that rolls
+    /// back if it is dropped part way, so a poisoned lock is still safe to use.
+    fn connection(&self) -> MutexGuard<'_, Connection> {
+        self.connection.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Loads a bill, changes it and writes it back with the next version in
+    /// one transaction, recording the change `change` describes. Returns the
+    /// change's result and the changed bill.
+    fn modify<T, E>(
+        &self,
+        id: Uuid,
+        expected_version: Option<u64>,
+        change: impl FnOnce(&mut Bill) -> Result<(T, Change), E>,
+    ) -> Result<(T, BillWithId), DataError>
+    where
+        DataError: From<E>,
+    {
+        self.modify_stored(id, expected_version, |_, stored| change(&mut stored.bill))
+    }
+
+    /// `modify`, for changes to who the bill is shared with as well as to
+    /// the bill itself. `change` may read other tables through the
+    /// connection it is given.
+    fn modify_stored<T, E>(
+        &self,
+        id: Uuid,
+        expected_version: Option<u64>,
+        change: impl FnOnce(&Connection, &mut BillWithId) -> Result<(T, Change), E>,
+    ) -> Result<(T, BillWithId), DataError>
+    where
+        DataError: From<E>,
+    {
+        let mut connection = self.connection();
+        let transaction = connection.transaction()?;
+        let mut stored = load_visible_bill(&transaction, id, &self.user)?;
+        check_version(expected_version, stored.version)?;
+        let (res, change) = change(&transaction, &mut stored)?;
+        prepare_bill(&mut stored.bill)?;
+        touch(&mut stored);
+        save_bill(&transaction, &stored)?;
+        save_revision(&transaction, id, &Revision::record(&stored, &self.actor, change))?;
+        transaction.commit()?;
+        Ok((res, stored))
+    }
+}
+
+/// Constraint violations mean the change clashed with stored data; anything
+/// else is a failure of the database itself.
+impl From<rusqlite::Error> for DataError {
+    fn from(err: rusqlite::Error) -> Self {
+        match err.sqlite_error_code() {
+            Some(ErrorCode::ConstraintViolation) => DataError::Conflict(err.to_string()),
+            _ => DataError::Storage(err.to_string()),
+        }
+    }
+}
+
+/// Applies whichever of `migrations` the database has not had yet.
+pub(crate) fn migrate(connection: &mut Connection, migrations: &[&str]) -> rusqlite::Result<()> {
+    let version: usize = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (index, migration) in migrations.iter().enumerate().skip(version) {
+        let transaction = connection.transaction()?;
+        transaction.execute_batch(migration)?;
+        transaction.pragma_update(None, "user_version", index + 1)?;
+        transaction.commit()?;
+    }
+    Ok(())
+}
+
+/// The snake_case name serde gives a unit enum variant.
+fn to_text<T: Serialize>(value: &T) -> String {
+    serde_json::to_value(value).unwrap().as_str().unwrap().to_string()
+}
+
+fn from_text<T: DeserializeOwned>(column: usize, text: String) -> rusqlite::Result<T> {
+    serde_json::from_value(serde_json::Value::String(text))
+        .map_err(|err| rusqlite::Error::FromSqlConversionFailure(column, Type::Text, Box::new(err)))
+}
+
+/// A value stored whole as JSON, for records that are only ever read back
+/// whole, such as revisions.
+pub(crate) fn to_json<T: Serialize>(value: &T) -> String {
+    serde_json::to_string(value).unwrap()
+}
+
+pub(crate) fn from_json<T: DeserializeOwned>(column: usize, json: String) -> rusqlite::Result<T> {
+    serde_json::from_str(&json)
+        .map_err(|err| rusqlite::Error::FromSqlConversionFailure(column, Type::Text, Box::new(err)))
+}
+
+pub(crate) fn invalid_column(column: usize, value: &str) -> rusqlite::Error {
+    rusqlite::Error::FromSqlConversionFailure(column, Type::Text, format!("Unexpected value {}", value).into())
+}
+
+fn portion_columns(portion: &Portion) -> (&'static str, Option<i64>) {
+    match portion {
+        Portion::Equal => ("equal", None),
+        Portion::Weight(weight) => ("weight", Some(*weight as i64)),
+        Portion::Fixed(amount) => ("fixed", Some(amount.amount())),
+        Portion::Percent(basis_points) => ("percent", Some(*basis_points as i64)),
+    }
+}
+
+fn portion_from_columns(portion: &str, value: Option<i64>, currency: Currency) -> rusqlite::Result<Portion> {
+    let value = || value.ok_or(rusqlite::Error::InvalidColumnType(4, "value".to_string(), Type::Null));
+    Ok(match portion {
+        "equal" => Portion::Equal,
+        "weight" => Portion::Weight(value()? as u32),
+        "fixed" => Portion::Fixed(Money::new(value()?, currency)),
+        "percent" => Portion::Percent(value()? as u32),
+        other => return Err(invalid_column(3, other)),
+    })
+}
+
+/// The kind, rate, amount and tip base columns of an adjustment.
+fn kind_columns(kind: &AdjustmentKind) -> (&'static str, Option<u32>, Option<i64>, Option<String>) {
+    match kind {
+        AdjustmentKind::TaxRate(rate) => ("tax_rate", Some(*rate), None, None),
+        AdjustmentKind::TaxAmount(amount) => ("tax_amount", None, Some(amount.amount()), None),
+        AdjustmentKind::Tip { rate, base } => ("tip", Some(*rate), None, Some(to_text(base))),
+        AdjustmentKind::TipAmount(amount) => ("tip_amount", None, Some(amount.amount()), None),
+        AdjustmentKind::FeeRate(rate) => ("fee_rate", Some(*rate), None, None),
+        AdjustmentKind::Fee(amount) => ("fee", None, Some(amount.amount()), None),
+        AdjustmentKind::DiscountRate(rate) => ("discount_rate", Some(*rate), None, None),
+        AdjustmentKind::Discount(amount) => ("discount", None, Some(amount.amount()), None),
+    }
+}
+
+fn kind_from_columns(
+    kind: &str,
+    rate: Option<u32>,
+    amount: Option<i64>,
+    tip_base: Option<String>,
+    currency: Currency,
+) -> rusqlite::Result<AdjustmentKind> {
+    let rate = || rate.ok_or(rusqlite::Error::InvalidColumnType(2, "rate".to_string(), Type::Null));
+    let amount = || amount
+        .map(|amount| Money::new(amount, currency))
+        .ok_or(rusqlite::Error::InvalidColumnType(3, "amount".to_string(), Type::Null));
+    Ok(match kind {
+        "tax_rate" => AdjustmentKind::TaxRate(rate()?),
+        "tax_amount" => AdjustmentKind::TaxAmount(amount()?),
+        "tip" => AdjustmentKind::Tip {
+            rate: rate()?,
+            base: match tip_base {
+                Some(base) => from_text(4, base)?,
+                None => TipBase::default(),
+            },
+        },
+        "tip_amount" => AdjustmentKind::TipAmount(amount()?),
+        "fee_rate" => AdjustmentKind::FeeRate(rate()?),
+        "fee" => AdjustmentKind::Fee(amount()?),
+        "discount_rate" => AdjustmentKind::DiscountRate(rate()?),
+        "discount" => AdjustmentKind::Discount(amount()?),
+        other => return Err(invalid_column(1, other)),
+    })
+}
+
+fn load_bill(connection: &Connection, id: Uuid) -> rusqlite::Result<Option<BillWithId>> {
+    let row = connection.query_row(
+        "SELECT name, currency, total, counter, rounding, version, created_at, updated_at, owner FROM bills WHERE id = ?1",
+        params![id.to_string()],
+        |row| Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, Option<i64>>(2)?,
+            row.get::<_, u16>(3)?,
+            row.get::<_, String>(4)?,
+            row.get::<_, u64>(5)?,
+            row.get::<_, i64>(6)?,
+            row.get::<_, i64>(7)?,
+            row.get::<_, Option<String>>(8)?,
+        )),
+    ).optional()?;
+    let Some((name, currency, total, counter, rounding, version, created_at, updated_at, owner)) = row else {
+        return Ok(None);
+    };
+    let timestamp = |column: usize, micros: i64| {
+        DateTime::from_timestamp_micros(micros).ok_or_else(|| invalid_column(column, &micros.to_string()))
+    };
+    let currency = currency.parse::<Currency>()
+        .map_err(|err| rusqlite::Error::FromSqlConversionFailure(1, Type::Text, Box::new(err)))?;
+
+    let mut items = HashMap::new();
+    let mut statement = connection.prepare(
+        "SELECT item_id, name, price FROM items WHERE bill_id = ?1"
+    )?;
+    let rows = statement.query_map(params![id.to_string()], |row| {
+        Ok((row.get::<_, u16>(0)?, row.get::<_, String>(1)?, row.get::<_, i64>(2)?))
+    })?;
+    for row in rows {
+        let (item_id, name, price) = row?;
+        items.insert(item_id, LineItem::shared(name, Money::new(price, currency), BTreeMap::new()));
+    }
+
+    let mut statement = connection.prepare(
+        "SELECT item_id, orderer, portion, value FROM item_orderers WHERE bill_id = ?1"
+    )?;
+    let rows = statement.query_map(params![id.to_string()], |row| {
+        Ok((row.get::<_, u16>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, Option<i64>>(3)?))
+    })?;
+    for row in rows {
+        let (item_id, orderer, portion, value) = row?;
+        let portion = portion_from_columns(&portion, value, currency)?;
+        if let Some(item) = items.get_mut(&item_id) {
+            item.orderers.insert(orderer, portion);
+        }
+    }
+
+    let mut adjustments = Vec::new();
+    let mut statement = connection.prepare(
+        "SELECT name, kind, rate, amount, tip_base, allocation, assigned_to
+        FROM adjustments WHERE bill_id = ?1 ORDER BY position"
+    )?;
+    let rows = statement.query_map(params![id.to_string()], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, Option<u32>>(2)?,
+            row.get::<_, Option<i64>>(3)?,
+            row.get::<_, Option<String>>(4)?,
+            row.get::<_, String>(5)?,
+            row.get::<_, Option<String>>(6)?,
+        ))
+    })?;
+    for row in rows {
+        let (name, kind, rate, amount, tip_base, allocation, assigned_to) = row?;
+        let mut adjustment = Adjustment::new(name, kind_from_columns(&kind, rate, amount, tip_base, currency)?);
+        adjustment.allocation = match (allocation.as_str(), assigned_to) {
+            ("proportional", _) => AllocationRule::Proportional,
+            ("equal", _) => AllocationRule::Equal,
+            ("assigned", Some(orderer)) => AllocationRule::Assigned(orderer),
+            (other, _) => return Err(invalid_column(5, other)),
+        };
+        adjustments.push(adjustment);
+    }
+
+    let mut statement = connection.prepare(
+        "SELECT name, amount FROM payers WHERE bill_id = ?1 ORDER BY position"
+    )?;
+    let payers = statement.query_map(params![id.to_string()], |row| {
+        Ok(Payer::new(row.get(0)?, Money::new(row.get(1)?, currency)))
+    })?.collect::<rusqlite::Result<Vec<Payer>>>()?;
+
+    let participants = load_participants(connection, "SELECT id, name, aliases FROM participants WHERE bill_id = ?1", &id.to_string())?;
+
+    let mut bill = Bill::from_parts(
+        name,
+        currency,
+        total.map(|total| Money::new(total, currency)),
+        items,
+        counter,
+        from_text(4, rounding)?,
+        adjustments,
+    );
+    bill.set_payers(payers)
+        .map_err(|err| rusqlite::Error::FromSqlConversionFailure(1, Type::Integer, Box::new(err)))?;
+    bill.set_participants(participants);
+    let members = connection.prepare("SELECT username FROM bill_members WHERE bill_id = ?1 ORDER BY position")?
+        .query_map(params![id.to_string()], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+
+    Ok(Some(BillWithId {
+        id,
+        version,
+        created_at: timestamp(6, created_at)?,
+        updated_at: timestamp(7, updated_at)?,
+        owner,
+        members,
+        bill,
+    }))
+}
+
+/// Writes a bill, replacing whatever was stored under its id before except
+/// for when it was created and who owns it.
+fn save_bill(connection: &Connection, stored: &BillWithId) -> rusqlite::Result<()> {
+    let id = stored.id.to_string();
+    let bill = &stored.bill;
+    connection.execute(
+        "INSERT INTO bills (id, name, currency, total, counter, rounding, version, created_at, updated_at, owner)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+        ON CONFLICT (id) DO UPDATE SET
+            name = excluded.name,
+            currency = excluded.currency,
+            total = excluded.total,
+            counter = excluded.counter,
+            rounding = excluded.rounding,
+            version = excluded.version,
+            updated_at = excluded.updated_at,
+            owner = excluded.owner",
+        params![
+            id,
+            bill.name,
+            bill.currency().code(),
+            bill.total().map(|total| total.amount()),
+            bill.counter(),
+            to_text(&bill.rounding),
+            stored.version,
+            stored.created_at.timestamp_micros(),
+            stored.updated_at.timestamp_micros(),
+            stored.owner,
+        ],
+    )?;
+    connection.execute("DELETE FROM items WHERE bill_id = ?1", params![id])?;
+    connection.execute("DELETE FROM adjustments WHERE bill_id = ?1", params![id])?;
+    connection.execute("DELETE FROM payers WHERE bill_id = ?1", params![id])?;
+    connection.execute("DELETE FROM participants WHERE bill_id = ?1", params![id])?;
+    connection.execute("DELETE FROM bill_members WHERE bill_id = ?1", params![id])?;
+
+    for (item_id, item) in bill.items() {
+        connection.execute(
+            "INSERT INTO items (bill_id, item_id, name, price) VALUES (?1, ?2, ?3, ?4)",
+            params![id, item_id, item.name, item.price.amount()],
+        )?;
+        for (orderer, portion) in &item.orderers {
+            let (portion, value) = portion_columns(portion);
+            connection.execute(
+                "INSERT INTO item_orderers (bill_id, item_id, orderer, portion, value) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![id, item_id, orderer, portion, value],
+            )?;
+        }
+    }
+
+    for (position, adjustment) in bill.adjustments().iter().enumerate() {
+        let (kind, rate, amount, tip_base) = kind_columns(&adjustment.kind);
+        let (allocation, assigned_to) = match &adjustment.allocation {
+            AllocationRule::Proportional => ("proportional", None),
+            AllocationRule::Equal => ("equal", None),
+            AllocationRule::Assigned(orderer) => ("assigned", Some(orderer)),
+        };
+        connection.execute(
+            "INSERT INTO adjustments (bill_id, position, name, kind, rate, amount, tip_base, allocation, assigned_to)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![id, position, adjustment.name, kind, rate, amount, tip_base, allocation, assigned_to],
+        )?;
+    }
+
+    for (position, payer) in bill.payers().iter().enumerate() {
+        connection.execute(
+            "INSERT INTO payers (bill_id, position, name, amount) VALUES (?1, ?2, ?3, ?4)",
+            params![id, position, payer.name, payer.amount.amount()],
+        )?;
+    }
+
+    for (position, username) in stored.members.iter().enumerate() {
+        connection.execute(
+            "INSERT INTO bill_members (bill_id, position, username) VALUES (?1, ?2, ?3)",
+            params![id, position, username],
+        )?;
+    }
+    save_participants(connection, "INSERT INTO participants (bill_id, id, name, aliases) VALUES (?1, ?2, ?3, ?4)", &id, bill.participants())
+}
+
+/// Writes a bill's or group's participants with `sql`, which takes the
+/// owner's id, then each participant's id, name and aliases.
+fn save_participants(connection: &Connection, sql: &str, owner: &str, participants: &Participants) -> rusqlite::Result<()> {
+    for (id, participant) in participants.iter() {
+        connection.execute(sql, params![owner, id.to_string(), participant.name, to_json(&participant.aliases)])?;
+    }
+    Ok(())
+}
+
+/// Reads participants with `sql`, which selects their id, name and aliases
+/// by their owner's id.
+fn load_participants(connection: &Connection, sql: &str, owner: &str) -> rusqlite::Result<Participants> {
+    connection.prepare(sql)?
+        .query_map(params![owner], |row| {
+            let id = row.get::<_, String>(0)?;
+            let mut participant = Participant::new(row.get(1)?);
+            participant.aliases = from_json(2, row.get(2)?)?;
+            Ok((Uuid::parse_str(&id).map_err(|_| invalid_column(0, &id))?, participant))
+        })?
+        .collect()
+}
+
+fn save_revision(connection: &Connection, id: Uuid, revision: &Revision) -> rusqlite::Result<()> {
+    connection.execute(
+        "INSERT INTO revisions (bill_id, version, at, actor, change, bill) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            id.to_string(),
+            revision.version,
+            revision.at.timestamp_micros(),
+            revision.actor,
+            to_json(&revision.change),
+            to_json(&revision.after),
+        ],
+    )?;
+    Ok(())
+}
+
+fn load_revisions(connection: &Connection, id: Uuid) -> rusqlite::Result<Vec<Revision>> {
+    let mut statement = connection.prepare(
+        "SELECT version, at, actor, change, bill FROM revisions WHERE bill_id = ?1 ORDER BY version"
+    )?;
+    let rows = statement.query_map(params![id.to_string()], |row| {
+        let at = row.get::<_, i64>(1)?;
+        Ok(Revision {
+            version: row.get(0)?,
+            at: DateTime::from_timestamp_micros(at).ok_or_else(|| invalid_column(1, &at.to_string()))?,
+            actor: row.get(2)?,
+            change: from_json(3, row.get(3)?)?,
+            before: None,
+            after: from_json(4, row.get(4)?)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// A condition on `bills` that holds for the bills the user in parameter
+/// `param` may see, or for every bill if that parameter is null.
+fn bill_access(param: usize) -> String {
+    format!(
+        "(?{0} IS NULL OR bills.owner = ?{0} OR EXISTS (SELECT 1 FROM bill_members WHERE bill_id = bills.id AND username = ?{0}))",
+        param,
+    )
+}
+
+fn user_value(user: &Option<String>) -> Value {
+    user.clone().map_or(Value::Null, Value::Text)
+}
+
+fn where_clause(conditions: &[String]) -> String {
+    match conditions.is_empty() {
+        true => String::new(),
+        false => format!(" WHERE {}", conditions.join(" AND ")),
+    }
+}
+
+/// Loads the bills whose ids `sql` selects, in the order it selects them.
+fn load_bills(connection: &Connection, sql: &str, values: Vec<Value>) -> Result<Vec<BillWithId>, DataError> {
+    let mut statement = connection.prepare(sql)?;
+    let ids = statement.query_map(params_from_iter(values), |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    let mut bills = Vec::with_capacity(ids.len());
+    for id in ids {
+        let id = Uuid::parse_str(&id).map_err(|err| DataError::Storage(err.to_string()))?;
+        if let Some(bill) = load_bill(connection, id)? {
+            bills.push(bill);
+        }
+    }
+    Ok(bills)
+}
+
+/// Writes a group, replacing whatever was stored under its id before except
+/// for who owns it. The event store keeps groups in the same tables.
+pub(crate) fn save_group(connection: &Connection, id: Uuid, owner: &Option<String>, group: &Group) -> rusqlite::Result<()> {
+    let id = id.to_string();
+    connection.execute(
+        "INSERT INTO groups (id, name, owner) VALUES (?1, ?2, ?3) ON CONFLICT (id) DO UPDATE SET name = excluded.name",
+        params![id, group.name, owner],
+    )?;
+    connection.execute("DELETE FROM group_members WHERE group_id = ?1", params![id])?;
+    connection.execute("DELETE FROM group_bills WHERE group_id = ?1", params![id])?;
+    connection.execute("DELETE FROM group_participants WHERE group_id = ?1", params![id])?;
+    for (position, member) in group.members.iter().enumerate() {
+        connection.execute(
+            "INSERT INTO group_members (group_id, position, name) VALUES (?1, ?2, ?3)",
+            params![id, position, member],
+        )?;
+    }
+    for (position, bill) in group.bills.iter().enumerate() {
+        connection.execute(
+            "INSERT INTO group_bills (group_id, position, bill_id) VALUES (?1, ?2, ?3)",
+            params![id, position, bill.to_string()],
+        )?;
+    }
+    save_participants(
+        connection,
+        "INSERT INTO group_participants (group_id, id, name, aliases) VALUES (?1, ?2, ?3, ?4)",
+        &id,
+        &group.participants,
+    )
+}
+
+/// Loads a group, or nothing if there is none that `user` may see.
+pub(crate) fn load_group(connection: &Connection, id: Uuid, user: &Option<String>) -> rusqlite::Result<Option<Group>> {
+    let id = id.to_string();
+    let name = connection.query_row(
+        "SELECT name FROM groups WHERE id = ?1 AND (?2 IS NULL OR owner = ?2)",
+        params![id, user],
+        |row| row.get::<_, String>(0),
+    ).optional()?;
+    let Some(name) = name else {
+        return Ok(None);
+    };
+    let mut group = Group::new(name);
+    group.members = connection.prepare("SELECT name FROM group_members WHERE group_id = ?1 ORDER BY position")?
+        .query_map(params![id], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    group.bills = connection.prepare("SELECT bill_id FROM group_bills WHERE group_id = ?1 ORDER BY position")?
+        .query_map(params![id], |row| {
+            let bill = row.get::<_, String>(0)?;
+            Uuid::parse_str(&bill).map_err(|_| invalid_column(0, &bill))
+        })?
+        .collect::<rusqlite::Result<Vec<Uuid>>>()?;
+    group.participants = load_participants(connection, "SELECT id, name, aliases FROM group_participants WHERE group_id = ?1", &id)?;
+    Ok(Some(group))
+}
+
+pub(crate) fn load_groups(connection: &Connection, user: &Option<String>) -> rusqlite::Result<Vec<GroupWithId>> {
+    let ids = connection.prepare("SELECT id FROM groups WHERE ?1 IS NULL OR owner = ?1 ORDER BY name, id")?
+        .query_map(params![user], |row| {
+            let id = row.get::<_, String>(0)?;
+            Uuid::parse_str(&id).map_err(|_| invalid_column(0, &id))
+        })?
+        .collect::<rusqlite::Result<Vec<Uuid>>>()?;
+    let mut groups = Vec::with_capacity(ids.len());
+    for id in ids {
+        if let Some(group) = load_group(connection, id, user)? {
+            groups.push(GroupWithId { id, group });
+        }
+    }
+    Ok(groups)
+}
+
+/// Deletes a group along with its members and bills, or returns false if
+/// there was none that `user` may see.
+pub(crate) fn delete_group(connection: &Connection, id: Uuid, user: &Option<String>) -> rusqlite::Result<bool> {
+    Ok(connection.execute(
+        "DELETE FROM groups WHERE id = ?1 AND (?2 IS NULL OR owner = ?2)",
+        params![id.to_string(), user],
+    )? > 0)
+}
+
+fn save_payment(connection: &Connection, id: Uuid, owner: &Option<String>, payment: &Payment) -> rusqlite::Result<()> {
+    let (bill, group) = match payment.reference {
+        Some(Reference::Bill(bill)) => (Some(bill.to_string()), None),
+        Some(Reference::Group(group)) => (None, Some(group.to_string())),
+        None => (None, None),
+    };
+    connection.execute(
+        "INSERT INTO payments (id, from_person, to_person, amount, currency, date, note, bill_id, group_id, owner)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            id.to_string(),
+            payment.from,
+            payment.to,
+            payment.amount.amount(),
+            payment.amount.currency().code(),
+            payment.date.to_string(),
+            payment.note,
+            bill,
+            group,
+            owner,
+        ],
+    )?;
+    Ok(())
+}
+
+fn payment_from_row(row: &rusqlite::Row) -> rusqlite::Result<PaymentWithId> {
+    let id = row.get::<_, String>(0)?;
+    let id = Uuid::parse_str(&id).map_err(|_| invalid_column(0, &id))?;
+    let reference = |index: usize| -> rusqlite::Result<Option<Uuid>> {
+        row.get::<_, Option<String>>(index)?
+            .map(|id| Uuid::parse_str(&id).map_err(|_| invalid_column(index, &id)))
+            .transpose()
+    };
+    let currency = row.get::<_, String>(4)?;
+    let currency = currency.parse::<Currency>().map_err(|_| invalid_column(4, &currency))?;
+    let date = row.get::<_, String>(5)?;
+    let date = date.parse::<NaiveDate>().map_err(|_| invalid_column(5, &date))?;
+    let reference = match (reference(7)?, reference(8)?) {
+        (Some(bill), _) => Some(Reference::Bill(bill)),
+        (None, Some(group)) => Some(Reference::Group(group)),
+        (None, None) => None,
+    };
+    let mut payment = Payment::new(row.get(1)?, row.get(2)?, Money::new(row.get(3)?, currency), date);
+    payment.note = row.get(6)?;
+    payment.reference = reference;
+    Ok(PaymentWithId { id, payment })
+}
+
+const PAYMENT_COLUMNS: &str = "id, from_person, to_person, amount, currency, date, note, bill_id, group_id";
+
+/// A condition on `payments` that holds for the payments the user in
+/// parameter `param` may see: those they recorded, and those towards a bill
+/// or group they may see.
+fn payment_access(param: usize) -> String {
+    format!(
+        "(?{0} IS NULL OR owner = ?{0}
+        OR bill_id IN (SELECT id FROM bills WHERE {1})
+        OR group_id IN (SELECT id FROM groups WHERE owner = ?{0}))",
+        param,
+        bill_access(param),
+    )
+}
+
+fn load_payment(connection: &Connection, id: Uuid, user: &Option<String>) -> rusqlite::Result<Option<Payment>> {
+    connection.query_row(
+        &format!("SELECT {} FROM payments WHERE id = ?1 AND {}", PAYMENT_COLUMNS, payment_access(2)),
+        params![id.to_string(), user],
+        payment_from_row,
+    ).optional().map(|stored| stored.map(|stored| stored.payment))
+}
+
+/// Every payment `user` may see, oldest first by date, then in the order
+/// they were recorded.
+fn load_payments(connection: &Connection, user: &Option<String>) -> rusqlite::Result<Vec<PaymentWithId>> {
+    connection.prepare(&format!("SELECT {} FROM payments WHERE {} ORDER BY date, rowid", PAYMENT_COLUMNS, payment_access(1)))?
+        .query_map(params![user], payment_from_row)?
+        .collect()
+}
+
+/// Whether there is a bill `id` that `user` may see.
+fn bill_visible(connection: &Connection, id: Uuid, user: &Option<String>) -> Result<bool, DataError> {
+    Ok(connection.query_row(
+        &format!("SELECT EXISTS (SELECT 1 FROM bills WHERE id = ?1 AND {})", bill_access(2)),
+        params![id.to_string(), user],
+        |row| row.get(0),
+    )?)
+}
+
+fn load_visible_bill(connection: &Connection, id: Uuid, user: &Option<String>) -> Result<BillWithId, DataError> {
+    load_bill(connection, id)?
+        .filter(|stored| can_see(stored, user))
+        .ok_or(DataError::BillNotFound)
+}
+
+/// Writes an account. The event store keeps accounts in the same table.
+pub(crate) fn save_user(connection: &Connection, user: &User) -> Result<(), DataError> {
+    match connection.execute(
+        "INSERT INTO users (username, password_hash) VALUES (?1, ?2)",
+        params![user.username, user.password_hash],
+    ) {
+        Ok(_) => Ok(()),
+        Err(rusqlite::Error::SqliteFailure(err, _)) if err.code == ErrorCode::ConstraintViolation => {
+            Err(DataError::Conflict(format!("the username {} is taken", user.username)))
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+pub(crate) fn load_user(connection: &Connection, username: &str) -> Result<User, DataError> {
+    connection.query_row(
+        "SELECT username, password_hash FROM users WHERE username = ?1",
+        params![username],
+        |row| Ok(User { username: row.get(0)?, password_hash: row.get(1)? }),
+    ).optional()?.ok_or(DataError::UserNotFound)
+}
+
+impl Data for Sqlite {
+    fn with_actor(&self, actor: Option<String>) -> Self {
+        Self {
+            actor,
+            ..self.clone()
+        }
+    }
+
+    fn with_user(&self, user: String) -> Self {
+        Self {
+            actor: Some(user.clone()),
+            user: Some(user),
+            ..self.clone()
+        }
+    }
+
+    fn add_bill(&self, bill: &Bill) -> Result<Uuid, DataError> {
+        let mut bill = bill.clone();
+        prepare_bill(&mut bill)?;
+        let mut connection = self.connection();
+        let id = Uuid::new_v4();
+        let transaction = connection.transaction()?;
+        let mut stored = new_stored(id, bill);
+        stored.owner = self.user.clone();
+        save_bill(&transaction, &stored)?;
+        save_revision(&transaction, id, &Revision::record(&stored, &self.actor, Change::Created))?;
+        transaction.commit()?;
+        Ok(id)
+    }
+
+    fn delete_bill(&self, id: Uuid, expected_version: Option<u64>) -> Result<Uuid, DataError> {
+        let mut connection = self.connection();
+        let transaction = connection.transaction()?;
+        let stored = load_visible_bill(&transaction, id, &self.user)?;
+        check_version(expected_version, stored.version)?;
+        check_owner(&stored, &self.user)?;
+        transaction.execute("DELETE FROM bills WHERE id = ?1", params![id.to_string()])?;
+        transaction.commit()?;
+        Ok(id)
+    }
+
+    fn get_versioned_bill(&self, id: Uuid) -> Result<BillWithId, DataError> {
+        let connection = self.connection();
+        load_visible_bill(&connection, id, &self.user)
+    }
+
+    fn get_bills(&self) -> Result<Vec<BillWithId>, DataError> {
+        let connection = self.connection();
+        let sql = format!("SELECT id FROM bills WHERE {} ORDER BY rowid", bill_access(1));
+        load_bills(&connection, &sql, vec![user_value(&self.user)])
+    }
+
+    /// Filters, and sorts by time or name, in SQL. Totals are worked out
+    /// from the items, so sorting by total happens in memory after the
+    /// filters.
+    fn query_bills(&self, query: &BillQuery) -> Result<BillPage, DataError> {
+        let after = query.after()?;
+        let mut values = vec![user_value(&self.user)];
+        let mut conditions = vec![bill_access(1)];
+        if let Some(name) = &query.name {
+            values.push(Value::Text(name.to_ascii_lowercase()));
+            conditions.push(format!("instr(lower(name), ?{}) > 0", values.len()));
+        }
+        if let Some(participant) = &query.participant {
+            // the participant may be registered on the bill, and then it
+            // refers to them by id
+            values.push(Value::Text(participant.clone()));
+            values.push(Uuid::parse_str(participant.trim()).map_or(Value::Null, |id| Value::Text(id.to_string())));
+            values.push(Value::Text(normalize(participant)));
+            let references = format!(
+                "?{0} UNION SELECT id FROM participants WHERE bill_id = bills.id AND (id = ?{1}
+                    OR normalize_name(name) = ?{2}
+                    OR EXISTS (SELECT 1 FROM json_each(aliases) WHERE normalize_name(value) = ?{2}))",
+                values.len() - 2,
+                values.len() - 1,
+                values.len(),
+            );
+            conditions.push(format!(
+                "(EXISTS (SELECT 1 FROM item_orderers WHERE bill_id = bills.id AND orderer IN (SELECT {0}))
+                OR EXISTS (SELECT 1 FROM adjustments WHERE bill_id = bills.id AND assigned_to IN (SELECT {0})))",
+                references,
+            ));
+        }
+        if let Some(since) = query.created_since {
+            values.push(Value::Integer(since.timestamp_micros()));
+            conditions.push(format!("created_at >= ?{}", values.len()));
+        }
+        if let Some(before) = query.created_before {
+            values.push(Value::Integer(before.timestamp_micros()));
+            conditions.push(format!("created_at < ?{}", values.len()));
+        }
+
+        let connection = self.connection();
+        // Totals and whether bills are settled are only known from whole
+        // bills, so those queries are finished in memory.
+        if query.sort == SortKey::Total || query.settled.is_some() {
+            let sql = format!("SELECT id FROM bills{}", where_clause(&conditions));
+            let mut bills = load_bills(&connection, &sql, values)?;
+            if query.settled.is_some() {
+                query.retain_settled(&mut bills, &load_payments(&connection, &self.user)?)?;
+            }
+            return query.page(bills);
+        }
+        let column = match query.sort {
+            SortKey::Created => "created_at",
+            SortKey::Updated => "updated_at",
+            SortKey::Name => "name",
+            SortKey::Total => unreachable!("finished in memory above"),
+        };
+        let (comparison, direction) = match query.order {
+            SortOrder::Asc => (">", "ASC"),
+            SortOrder::Desc => ("<", "DESC"),
+        };
+        if let Some(after) = after {
+            values.push(match &after.value {
+                SortValue::Created(micros) | SortValue::Updated(micros) => Value::Integer(*micros),
+                SortValue::Name(name) => Value::Text(name.clone()),
+                SortValue::Total(..) => unreachable!("checked by BillQuery::after"),
+            });
+            values.push(Value::Text(after.id.to_string()));
+            conditions.push(format!("({}, id) {} (?{}, ?{})", column, comparison, values.len() - 1, values.len()));
+        }
+        let mut sql = format!(
+            "SELECT id FROM bills{} ORDER BY {1} {2}, id {2}",
+            where_clause(&conditions),
+            column,
+            direction,
+        );
+        if let Some(limit) = query.limit {
+            // One more than the page holds, to tell whether there is another.
+            values.push(Value::Integer(i64::try_from(limit).unwrap_or(i64::MAX).saturating_add(1)));
+            sql.push_str(&format!(" LIMIT ?{}", values.len()));
+        }
+        query.paginate(load_bills(&connection, &sql, values)?)
+    }
+
+    fn update_bill(&self, id: Uuid, bill: &Bill, expected_version: Option<u64>) -> Result<u64, DataError> {
+        let ((), stored) = self.modify(id, expected_version, |existing_bill| {
+            *existing_bill = bill.clone();
+            Ok::<_, DataError>(((), Change::Replaced))
+        })?;
+        Ok(stored.version)
+    }
+
+    fn patch_bill(&self, id: Uuid, patch: &BillPatch, expected_version: Option<u64>) -> Result<BillWithId, DataError> {
+        let ((), stored) = self.modify(id, expected_version, |bill| {
+            *bill = patch.apply(bill)?;
+            Ok::<_, PatchError>(((), Change::Patched))
+        })?;
+        Ok(stored)
+    }
+
+    fn add_item(&self, bill_id: Uuid, item: &LineItem, expected_version: Option<u64>) -> Result<(u16, u64), DataError> {
+        self.modify(bill_id, expected_version, |bill| {
+            let item_id = bill.add_item(item.clone())?;
+            Ok::<_, BillError>((item_id, Change::ItemAdded { item_id }))
+        }).map(|(item_id, stored)| (item_id, stored.version))
+    }
+
+    fn get_items(&self, bill_id: Uuid) -> Result<HashMap<u16, LineItem>, DataError> {
+        self.get_bill(bill_id).map(|bill| bill.items().clone())
+    }
+
+    fn update_item(&self, bill_id: Uuid, item_id: u16, item: &LineItem, expected_version: Option<u64>) -> Result<(u16, u64), DataError> {
+        self.modify(bill_id, expected_version, |bill| {
+            let item_id = bill.update_item(item_id, item.clone())?;
+            Ok::<_, BillError>((item_id, Change::ItemUpdated { item_id }))
+        }).map(|(item_id, stored)| (item_id, stored.version))
+    }
+
+    fn delete_item(&self, bill_id: Uuid, item_id: u16, expected_version: Option<u64>) -> Result<(u16, u64), DataError> {
+        self.modify(bill_id, expected_version, |bill| {
+            let item_id = bill.delete_item(item_id)?;
+            Ok::<_, BillError>((item_id, Change::ItemDeleted { item_id }))
+        }).map(|(item_id, stored)| (item_id, stored.version))
+    }
+
+    fn get_history(&self, id: Uuid) -> Result<Vec<Revision>, DataError> {
+        let connection = self.connection();
+        if !bill_visible(&connection, id, &self.user)? {
+            return Err(DataError::BillNotFound);
+        }
+        Ok(history::link(load_revisions(&connection, id)?))
+    }
+
+    fn revert_bill(&self, id: Uuid, version: u64, expected_version: Option<u64>) -> Result<BillWithId, DataError> {
+        let revision = self.get_history(id)?.into_iter()
+            .find(|revision| revision.version == version)
+            .ok_or(DataError::RevisionNotFound)?;
+        let ((), stored) = self.modify(id, expected_version, |bill| {
+            *bill = history::restore(bill, &revision);
+            Ok::<_, DataError>(((), Change::Reverted { to: version }))
+        })?;
+        Ok(stored)
+    }
+
+    fn change_participants(&self, id: Uuid, change: &ParticipantChange, expected_version: Option<u64>) -> Result<BillWithId, DataError> {
+        let ((), stored) = self.modify(id, expected_version, |bill| {
+            bill.change_participants(change.clone())?;
+            Ok::<_, BillError>(((), Change::from(change)))
+        })?;
+        Ok(stored)
+    }
+
+    fn invite_member(&self, id: Uuid, username: &str, expected_version: Option<u64>) -> Result<BillWithId, DataError> {
+        let ((), stored) = self.modify_stored(id, expected_version, |connection, stored| {
+            access::invite(stored, username, &self.user, |username| match load_user(connection, username) {
+                Ok(_) => Ok(true),
+                Err(DataError::UserNotFound) => Ok(false),
+                Err(err) => Err(err),
+            })?;
+            Ok::<_, DataError>(((), Change::MemberInvited { username: username.to_string() }))
+        })?;
+        Ok(stored)
+    }
+
+    fn remove_member(&self, id: Uuid, username: &str, expected_version: Option<u64>) -> Result<BillWithId, DataError> {
+        let ((), stored) = self.modify_stored(id, expected_version, |_, stored| {
+            access::uninvite(stored, username, &self.user)?;
+            Ok::<_, DataError>(((), Change::MemberRemoved { username: username.to_string() }))
+        })?;
+        Ok(stored)
+    }
+
+    fn add_group(&self, group: &Group) -> Result<Uuid, DataError> {
+        let mut connection = self.connection();
+        let transaction = connection.transaction()?;
+        let group = prepare_group(group, |bill| bill_visible(&transaction, bill, &self.user))?;
+        let id = Uuid::new_v4();
+        save_group(&transaction, id, &self.user, &group)?;
+        transaction.commit()?;
+        Ok(id)
+    }
+
+    fn get_group(&self, id: Uuid) -> Result<Group, DataError> {
+        let connection = self.connection();
+        load_group(&connection, id, &self.user)?.ok_or(DataError::GroupNotFound)
+    }
+
+    fn get_groups(&self) -> Result<Vec<GroupWithId>, DataError> {
+        let connection = self.connection();
+        Ok(load_groups(&connection, &self.user)?)
+    }
+
+    fn update_group(&self, id: Uuid, group: &Group) -> Result<Uuid, DataError> {
+        let mut connection = self.connection();
+        let transaction = connection.transaction()?;
+        load_group(&transaction, id, &self.user)?.ok_or(DataError::GroupNotFound)?;
+        let group = prepare_group(group, |bill| bill_visible(&transaction, bill, &self.user))?;
+        save_group(&transaction, id, &self.user, &group)?;
+        transaction.commit()?;
+        Ok(id)
+    }
+
+    fn delete_group(&self, id: Uuid) -> Result<Uuid, DataError> {
+        let connection = self.connection();
+        match delete_group(&connection, id, &self.user)? {
+            true => Ok(id),
+            false => Err(DataError::GroupNotFound),
+        }
+    }
+
+    fn add_payment(&self, payment: &Payment) -> Result<Uuid, DataError> {
+        let mut connection = self.connection();
+        let transaction = connection.transaction()?;
+        check_payment(
+            payment,
+            |bill| Ok(load_bill(&transaction, bill)?
+                .filter(|stored| can_see(stored, &self.user))
+                .map(|stored| stored.bill.currency())),
+            |group| Ok(load_group(&transaction, group, &self.user)?.is_some()),
+        )?;
+        let id = Uuid::new_v4();
+        save_payment(&transaction, id, &self.user, payment)?;
+        transaction.commit()?;
+        Ok(id)
+    }
+
+    fn get_payment(&self, id: Uuid) -> Result<Payment, DataError> {
+        let connection = self.connection();
+        load_payment(&connection, id, &self.user)?.ok_or(DataError::PaymentNotFound)
+    }
+
+    fn get_payments(&self) -> Result<Vec<PaymentWithId>, DataError> {
+        let connection = self.connection();
+        Ok(load_payments(&connection, &self.user)?)
+    }
+
+    fn delete_payment(&self, id: Uuid) -> Result<Uuid, DataError> {
+        let mut connection = self.connection();
+        let transaction = connection.transaction()?;
+        load_payment(&transaction, id, &self.user)?.ok_or(DataError::PaymentNotFound)?;
+        let deleted = transaction.execute(
+            "DELETE FROM payments WHERE id = ?1 AND (?2 IS NULL OR owner = ?2)",
+            params![id.to_string(), self.user],
+        )?;
+        if deleted == 0 {
+            return Err(DataError::Forbidden);
+        }
+        transaction.commit()?;
+        Ok(id)
+    }
+
+    fn add_user(&self, user: &User) -> Result<(), DataError> {
+        save_user(&self.connection(), user)
+    }
+
+    fn get_user(&self, username: &str) -> Result<User, DataError> {
+        load_user(&self.connection(), username)
+    }
+
+    fn assign_owner(&self, username: &str) -> Result<Assigned, DataError> {
+        access::check_nobody(&self.user)?;
+        let mut connection = self.connection();
+        let transaction = connection.transaction()?;
+        load_user(&transaction, username)?;
+        let bills = load_bills(&transaction, "SELECT id FROM bills WHERE owner IS NULL ORDER BY rowid", vec![])?;
+        for mut stored in bills.iter().cloned() {
+            access::assign(&mut stored, username);
+            touch(&mut stored);
+            save_bill(&transaction, &stored)?;
+            let change = Change::OwnerAssigned { username: username.to_string() };
+            save_revision(&transaction, stored.id, &Revision::record(&stored, &self.actor, change))?;
+        }
+        let groups = transaction.execute("UPDATE groups SET owner = ?1 WHERE owner IS NULL", params![username])?;
+        let payments = transaction.execute("UPDATE payments SET owner = ?1 WHERE owner IS NULL", params![username])?;
+        transaction.commit()?;
+        Ok(Assigned { bills: bills.len(), groups, payments })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::INITIAL_VERSION;
+    use crate::models::split::RoundingPolicy;
+
+    fn usd(amount: i64) -> Money {
+        Money::new(amount, Currency::USD)
+    }
+
+    #[test]
+    fn test_add_bill() {
+        let data = Sqlite::in_memory().unwrap();
+        let bill = Bill::new("test".to_string(), Currency::USD);
+        let id = data.add_bill(&bill).unwrap();
+        assert_eq!(data.get_bill(id).unwrap(), bill);
+        assert_eq!(data.get_bill(Uuid::new_v4()), Err(DataError::BillNotFound));
+    }
+
+    #[test]
+    fn test_delete_bill() {
+        let data = Sqlite::in_memory().unwrap();
+        let id = data.add_bill(&Bill::new("test".to_string(), Currency::USD)).unwrap();
+        data.add_item(id, &LineItem::from("test".to_string(), usd(100), Some("a".to_string())), None).unwrap();
+        assert_eq!(data.delete_bill(id, Some(1)), Err(DataError::VersionMismatch { expected: 1, actual: 2 }));
+        assert_eq!(data.delete_bill(id, Some(2)), Ok(id));
+        assert_eq!(data.delete_bill(id, None), Err(DataError::BillNotFound));
+        assert_eq!(data.get_items(id), Err(DataError::BillNotFound));
+    }
+
+    #[test]
+    fn test_get_bills() {
+        let data = Sqlite::in_memory().unwrap();
+        let bill = Bill::new("test".to_string(), Currency::USD);
+        let id = data.add_bill(&bill).unwrap();
+        let id2 = data.add_bill(&Bill::new("test2".to_string(), Currency::EUR)).unwrap();
+        let bills = data.get_bills().unwrap();
+        assert_eq!(bills.iter().map(|bill| bill.id).collect::<Vec<Uuid>>(), vec![id, id2]);
+        let created_at = bills[0].created_at;
+        assert!(bills.contains(&BillWithId { id, version: INITIAL_VERSION, created_at, updated_at: created_at, owner: None, members: vec![], bill }));
+        assert!(bills[0].created_at <= bills[1].created_at);
+    }
+
+    #[test]
+    fn test_update_bill() {
+        let data = Sqlite::in_memory().unwrap();
+        let id = data.add_bill(&Bill::new("test".to_string(), Currency::USD)).unwrap();
+        let bill = Bill::from("test2".to_string(), Money::new(500, Currency::JPY));
+        assert_eq!(data.update_bill(id, &bill, Some(1)), Ok(2));
+        assert_eq!(data.get_bill(id).unwrap(), bill);
+        assert_eq!(data.update_bill(id, &bill, Some(1)), Err(DataError::VersionMismatch { expected: 1, actual: 2 }));
+        assert_eq!(data.get_versioned_bill(id).unwrap().version, 2);
+        assert_eq!(data.update_bill(Uuid::new_v4(), &bill, None), Err(DataError::BillNotFound));
+    }
+
+    #[test]
+    fn test_patch_bill() {
+        let data = Sqlite::in_memory().unwrap();
+        let mut bill = Bill::new("test".to_string(), Currency::USD);
+        bill.add_item(LineItem::from("pasta".to_string(), usd(1200), Some("alice".to_string()))).unwrap();
+        let id = data.add_bill(&bill).unwrap();
+
+        let patch = BillPatch::Json(serde_json::from_value(serde_json::json!([
+            {"op": "add", "path": "/items/0/orderers/bob", "value": {"weight": 2}},
+            {"op": "replace", "path": "/name", "value": "dinner"},
+        ])).unwrap());
+        let patched = data.patch_bill(id, &patch, Some(1)).unwrap();
+        assert_eq!(patched.version, 2);
+        assert_eq!(patched.bill.items()[&0].orderers["bob"], Portion::Weight(2));
+        assert_eq!(data.get_versioned_bill(id).unwrap(), patched);
+
+        let failed = BillPatch::Json(serde_json::from_value(serde_json::json!([
+            {"op": "replace", "path": "/name", "value": "lunch"},
+            {"op": "remove", "path": "/items/5"},
+        ])).unwrap());
+        assert!(matches!(data.patch_bill(id, &failed, None), Err(DataError::Conflict(_))));
+        assert_eq!(data.get_versioned_bill(id).unwrap(), patched);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let data = Sqlite::in_memory().unwrap();
+        let mut bill = Bill::from("dinner".to_string(), usd(5000));
+        bill.rounding = RoundingPolicy::InOrder;
+        bill.add_item(LineItem::from("pasta".to_string(), usd(1500), None)).unwrap();
+        bill.add_item(LineItem::shared(
+            "wine".to_string(),
+            usd(3000),
+            [
+                ("a".to_string(), Portion::Equal),
+                ("b".to_string(), Portion::Weight(2)),
+                ("c".to_string(), Portion::Fixed(usd(500))),
+                ("d".to_string(), Portion::Percent(1000)),
+            ].into_iter().collect(),
+        )).unwrap();
+        bill.delete_item(0).unwrap();
+        bill.add_adjustment(Adjustment::new("tax".to_string(), AdjustmentKind::TaxRate(825))).unwrap();
+        bill.add_adjustment(Adjustment::new(
+            "tip".to_string(),
+            AdjustmentKind::Tip { rate: 2000, base: TipBase::PostTax },
+        )).unwrap();
+        let mut delivery = Adjustment::new("delivery".to_string(), AdjustmentKind::Fee(usd(299)));
+        delivery.allocation = AllocationRule::Assigned("a".to_string());
+        bill.add_adjustment(delivery).unwrap();
+        let mut coupon = Adjustment::new("coupon".to_string(), AdjustmentKind::Discount(usd(100)));
+        coupon.allocation = AllocationRule::Equal;
+        bill.add_adjustment(coupon).unwrap();
+        bill.set_payers(vec![Payer::new("b".to_string(), usd(4000)), Payer::new("a".to_string(), usd(1000))]).unwrap();
+
+        let id = data.add_bill(&bill).unwrap();
+        assert_eq!(data.get_bill(id).unwrap(), bill);
+        // the next item keeps counting from the stored counter
+        assert_eq!(data.add_item(id, &LineItem::from("bread".to_string(), usd(300), None), None), Ok((2, 2)));
+    }
+
+    #[test]
+    fn test_items() {
+        let data = Sqlite::in_memory().unwrap();
+        let id = data.add_bill(&Bill::new("test".to_string(), Currency::USD)).unwrap();
+        let item = LineItem::from("test".to_string(), usd(100), None);
+        let (item_id, _) = data.add_item(id, &item, None).unwrap();
+        assert_eq!(data.get_items(id).unwrap().get(&item_id), Some(&item));
+        assert_eq!(data.add_item(Uuid::new_v4(), &item, None), Err(DataError::BillNotFound));
+
+        let item = LineItem::from("test2".to_string(), usd(200), Some("a".to_string()));
+        assert_eq!(data.update_item(id, item_id, &item, Some(2)), Ok((item_id, 3)));
+        assert_eq!(data.get_items(id).unwrap().get(&item_id), Some(&item));
+        assert_eq!(data.update_item(id, item_id, &item, Some(2)), Err(DataError::VersionMismatch { expected: 2, actual: 3 }));
+        assert_eq!(data.update_item(id, item_id + 1, &item, None), Err(DataError::ItemNotFound));
+        let item = LineItem::from("test3".to_string(), Money::new(200, Currency::EUR), None);
+        assert!(matches!(data.update_item(id, item_id, &item, None), Err(DataError::Money(_))));
+
+        assert_eq!(data.delete_item(id, item_id, None), Ok((item_id, 4)));
+        assert_eq!(data.delete_item(id, item_id, None), Err(DataError::ItemNotFound));
+        assert_eq!(data.get_items(id).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_query_bills() {
+        let data = Sqlite::in_memory().unwrap();
+        for (name, price, orderer) in [("Dinner", 3000, "alice"), ("lunch", 1000, "bob"), ("late dinner", 2000, "bob")] {
+            let mut bill = Bill::new(name.to_string(), Currency::USD);
+            bill.add_item(LineItem::from("item".to_string(), usd(price), Some(orderer.to_string()))).unwrap();
+            data.add_bill(&bill).unwrap();
+        }
+        let mut adjusted = Bill::new("brunch".to_string(), Currency::USD);
+        adjusted.add_adjustment(Adjustment {
+            allocation: AllocationRule::Assigned("bob".to_string()),
+            ..Adjustment::new("tip".to_string(), AdjustmentKind::Fee(usd(500)))
+        }).unwrap();
+        data.add_bill(&adjusted).unwrap();
+        let all = data.get_bills().unwrap();
+
+        // the queries pushed down to SQL agree with filtering everything in memory
+        let queries = [
+            BillQuery::default(),
+            BillQuery { name: Some("DINNER".to_string()), ..BillQuery::default() },
+            BillQuery { participant: Some("bob".to_string()), sort: SortKey::Name, ..BillQuery::default() },
+            BillQuery { sort: SortKey::Total, order: SortOrder::Desc, ..BillQuery::default() },
+            BillQuery { created_since: Some(all[1].created_at), created_before: Some(all[3].created_at), ..BillQuery::default() },
+        ];
+        for query in queries {
+            assert_eq!(data.query_bills(&query).unwrap(), query.page(all.clone()).unwrap());
+        }
+
+        data.update_bill(all[0].id, &all[0].bill, None).unwrap();
+        let all = data.get_bills().unwrap();
+        let query = BillQuery { sort: SortKey::Updated, ..BillQuery::default() };
+        assert_eq!(data.query_bills(&query).unwrap().bills.last(), Some(&all[0]));
+
+        for sort in [SortKey::Created, SortKey::Updated, SortKey::Name, SortKey::Total] {
+            let mut query = BillQuery { sort, order: SortOrder::Desc, limit: Some(3), ..BillQuery::default() };
+            let first = data.query_bills(&query).unwrap();
+            assert_eq!(first.bills.len(), 3);
+            query.cursor = first.next;
+            let second = data.query_bills(&query).unwrap();
+            assert_eq!(second.next, None);
+            let mut pages = [first.bills, second.bills].concat();
+            assert_eq!(pages.len(), 4);
+            pages.sort_by_key(|stored| stored.id);
+            let mut expected = all.clone();
+            expected.sort_by_key(|stored| stored.id);
+            assert_eq!(pages, expected);
+        }
+    }
+
+    #[test]
+    fn test_query_bills_by_participant() {
+        let data = Sqlite::in_memory().unwrap();
+        let bob = Uuid::new_v4();
+        for (name, orderer, aliases) in [("dinner", "Bob", vec!["Robert", "Bøb"]), ("lunch", "bob", vec![]), ("brunch", "Robert", vec![])] {
+            let mut bill = Bill::new(name.to_string(), Currency::USD);
+            bill.add_item(LineItem::from("item".to_string(), usd(1000), Some(orderer.to_string()))).unwrap();
+            let id = data.add_bill(&bill).unwrap();
+            if !aliases.is_empty() {
+                let participant = Participant {
+                    name: "Bob".to_string(),
+                    aliases: aliases.into_iter().map(String::from).collect(),
+                };
+                data.change_participants(id, &ParticipantChange::Added { id: bob, participant }, None).unwrap();
+            }
+        }
+        let all = data.get_bills().unwrap();
+
+        // names and aliases are resolved through each bill's own participants
+        for participant in ["Bob", "bob", " ROBERT ", "BØB", &bob.to_string(), &bob.simple().to_string(), "carol"] {
+            let query = BillQuery { participant: Some(participant.to_string()), ..BillQuery::default() };
+            assert_eq!(data.query_bills(&query).unwrap(), query.page(all.clone()).unwrap(), "{}", participant);
+        }
+        let query = BillQuery { participant: Some("robert".to_string()), sort: SortKey::Name, ..BillQuery::default() };
+        let names = data.query_bills(&query).unwrap().bills.into_iter().map(|stored| stored.bill.name).collect::<Vec<String>>();
+        assert_eq!(names, vec!["dinner"]);
+    }
+
+    #[test]
+    fn test_history() {
+        let data = Sqlite::in_memory().unwrap().with_actor(Some("alice".to_string()));
+        let id = data.add_bill(&Bill::new("test".to_string(), Currency::USD)).unwrap();
+        let (item_id, _) = data.add_item(id, &LineItem::from("pasta".to_string(), usd(1200), None), None).unwrap();
+        let patch = BillPatch::Merge(serde_json::json!({"name": "dinner"}));
+        data.with_actor(None).patch_bill(id, &patch, None).unwrap();
+
+        let history = data.get_history(id).unwrap();
+        let changes = history.iter().map(|revision| revision.change.clone()).collect::<Vec<Change>>();
+        assert_eq!(changes, vec![Change::Created, Change::ItemAdded { item_id }, Change::Patched]);
+        assert_eq!(history[1].actor.as_deref(), Some("alice"));
+        assert_eq!(history[2].actor, None);
+        assert_eq!(history[2].before.as_ref().unwrap().name, "test");
+        assert_eq!(history[2].at, data.get_versioned_bill(id).unwrap().updated_at);
+
+        let reverted = data.revert_bill(id, 1, Some(3)).unwrap();
+        assert_eq!((reverted.version, reverted.bill.name.as_str()), (4, "test"));
+        assert!(reverted.bill.items().is_empty());
+        assert_eq!(data.get_versioned_bill(id).unwrap(), reverted);
+        assert_eq!(data.revert_bill(id, 9, None), Err(DataError::RevisionNotFound));
+
+        data.delete_bill(id, None).unwrap();
+        assert_eq!(data.get_history(id), Err(DataError::BillNotFound));
+    }
+
+    #[test]
+    fn test_groups() {
+        let data = Sqlite::in_memory().unwrap();
+        let bill = data.add_bill(&Bill::new("dinner".to_string(), Currency::USD)).unwrap();
+        let mut group = Group::new("team".to_string());
+        group.members = vec!["alice".to_string(), "bob".to_string()];
+        group.bills = vec![Uuid::new_v4()];
+        match data.add_group(&group) {
+            Err(DataError::Validation(err)) => assert_eq!(err.errors[0].path, "bills.0"),
+            res => panic!("expected a validation error, got {:?}", res),
+        }
+
+        group.bills = vec![bill];
+        let id = data.add_group(&group).unwrap();
+        assert_eq!(data.get_group(id).unwrap(), group);
+        group.name = "the team".to_string();
+        data.update_group(id, &group).unwrap();
+        assert_eq!(data.get_groups().unwrap(), vec![GroupWithId { id, group: group.clone() }]);
+        assert_eq!(data.update_group(Uuid::new_v4(), &group), Err(DataError::GroupNotFound));
+
+        // deleting a bill takes it out of the group
+        data.delete_bill(bill, None).unwrap();
+        assert!(data.get_group(id).unwrap().bills.is_empty());
+        assert_eq!(data.delete_group(id), Ok(id));
+        assert_eq!(data.get_group(id), Err(DataError::GroupNotFound));
+    }
+
+    #[test]
+    fn test_participants() {
+        let data = Sqlite::in_memory().unwrap();
+        let mut bill = Bill::new("dinner".to_string(), Currency::USD);
+        bill.add_item(LineItem::from("pasta".to_string(), usd(1200), Some("Bob ".to_string()))).unwrap();
+        let id = data.add_bill(&bill).unwrap();
+        let bob = Uuid::new_v4();
+        let mut participant = Participant::new("Bob".to_string());
+        participant.aliases = vec!["Robert".to_string()];
+        let stored = data.change_participants(id, &ParticipantChange::Added { id: bob, participant }, None).unwrap();
+        assert_eq!(stored.version, INITIAL_VERSION + 1);
+        assert_eq!(data.get_bill(id).unwrap(), stored.bill);
+        assert_eq!(stored.bill.get_orderers(), vec![bob.to_string()]);
+
+        // later writes resolve against the stored participants
+        data.add_item(id, &LineItem::from("wine".to_string(), usd(800), Some("robert".to_string())), None).unwrap();
+        assert_eq!(data.get_bill(id).unwrap().get_orderers(), vec![bob.to_string()]);
+        let history = data.get_history(id).unwrap();
+        assert_eq!(history[1].change, Change::ParticipantAdded { participant_id: bob });
+        assert_eq!(
+            data.change_participants(id, &ParticipantChange::Removed { id: Uuid::new_v4() }, None),
+            Err(DataError::ParticipantNotFound)
+        );
+
+        let mut group = Group::new("team".to_string());
+        group.members = vec!["alice".to_string(), "ALICE".to_string()];
+        let group_id = data.add_group(&group).unwrap();
+        let alice = Uuid::new_v4();
+        let change = ParticipantChange::Added { id: alice, participant: Participant::new("Alice".to_string()) };
+        let group = data.change_group_participants(group_id, &change).unwrap();
+        assert_eq!(group.members, vec![alice.to_string()]);
+        assert_eq!(data.get_group(group_id).unwrap(), group);
+    }
+
+    #[test]
+    fn test_payments() {
+        let data = Sqlite::in_memory().unwrap();
+        let bill = data.add_bill(&Bill::new("dinner".to_string(), Currency::USD)).unwrap();
+        let group = data.add_group(&Group::new("team".to_string())).unwrap();
+        let date = NaiveDate::from_ymd_opt(2024, 5, 2).unwrap();
+        let mut payment = Payment::new("alice".to_string(), "bob".to_string(), Money::new(2340, Currency::EUR), date);
+        payment.reference = Some(Reference::Bill(bill));
+        assert!(matches!(data.add_payment(&payment), Err(DataError::Money(_))));
+
+        payment.amount = usd(2340);
+        payment.note = Some("dinner".to_string());
+        let id = data.add_payment(&payment).unwrap();
+        let mut earlier = Payment::new("bob".to_string(), "alice".to_string(), usd(100), date.pred_opt().unwrap());
+        earlier.reference = Some(Reference::Group(group));
+        let earlier_id = data.add_payment(&earlier).unwrap();
+        assert_eq!(data.get_payment(id).unwrap(), payment);
+        assert_eq!(data.get_payments().unwrap(), vec![
+            PaymentWithId { id: earlier_id, payment: earlier },
+            PaymentWithId { id, payment: payment.clone() },
+        ]);
+
+        // deleting what they refer to keeps the payments
+        data.delete_bill(bill, None).unwrap();
+        data.delete_group(group).unwrap();
+        let references = data.get_payments().unwrap().into_iter()
+            .map(|stored| stored.payment.reference)
+            .collect::<Vec<Option<Reference>>>();
+        assert_eq!(references, vec![None, None]);
+        assert_eq!(data.delete_payment(id), Ok(id));
+        assert_eq!(data.get_payment(id), Err(DataError::PaymentNotFound));
+    }
+
+    #[test]
+    fn test_access() {
+        let data = Sqlite::in_memory().unwrap();
+        for username in ["alice", "bob", "carol"] {
+            data.add_user(&User { username: username.to_string(), password_hash: "hash".to_string() }).unwrap();
+        }
+        assert!(matches!(data.add_user(&data.get_user("alice").unwrap()), Err(DataError::Conflict(_))));
+        let alice = data.with_user("alice".to_string());
+        let bob = data.with_user("bob".to_string());
+        let id = alice.add_bill(&Bill::new("dinner".to_string(), Currency::USD)).unwrap();
+        assert_eq!(alice.get_versioned_bill(id).unwrap().owner, Some("alice".to_string()));
+        assert_eq!(bob.get_bill(id), Err(DataError::BillNotFound));
+        assert!(bob.get_bills().unwrap().is_empty());
+        assert_eq!(data.get_bills().unwrap().len(), 1);
+
+        // a bill bob can't see can't go in bob's groups or payments either
+        let mut group = Group::new("trip".to_string());
+        group.bills = vec![id];
+        assert!(matches!(bob.add_group(&group), Err(DataError::Validation(_))));
+        let date = NaiveDate::from_ymd_opt(2024, 5, 2).unwrap();
+        let mut payment = Payment::new("alice".to_string(), "bob".to_string(), Money::new(500, Currency::USD), date);
+        payment.reference = Some(Reference::Bill(id));
+        assert!(matches!(bob.add_payment(&payment), Err(DataError::Validation(_))));
+        let payment = alice.add_payment(&payment).unwrap();
+        assert_eq!(bob.get_payment(payment), Err(DataError::PaymentNotFound));
+
+        assert_eq!(bob.invite_member(id, "carol", None), Err(DataError::BillNotFound));
+        assert_eq!(alice.invite_member(id, "dave", None), Err(DataError::UserNotFound));
+        let stored = alice.invite_member(id, "bob", None).unwrap();
+        assert_eq!(stored.members, vec!["bob".to_string()]);
+        assert_eq!(bob.get_bills().unwrap().len(), 1);
+        assert!(bob.get_payment(payment).is_ok());
+        assert_eq!(bob.delete_payment(payment), Err(DataError::Forbidden));
+        assert_eq!(bob.delete_bill(id, None), Err(DataError::Forbidden));
+        bob.add_item(id, &LineItem::from("wine".to_string(), Money::new(800, Currency::USD), None), None).unwrap();
+        let group = bob.add_group(&group).unwrap();
+        assert_eq!(alice.get_group(group), Err(DataError::GroupNotFound));
+
+        assert_eq!(bob.remove_member(id, "bob", None).unwrap().members, Vec::<String>::new());
+        assert_eq!(bob.get_history(id), Err(DataError::BillNotFound));
+        assert_eq!(alice.delete_bill(id, None), Ok(id));
+    }
+
+    #[test]
+    fn test_reopen() {
+        let path = std::env::temp_dir().join(format!("billsplit-{}.db", Uuid::new_v4()));
+        let bill = Bill::from("test".to_string(), usd(100));
+        let id = Sqlite::open(&path).unwrap().add_bill(&bill).unwrap();
+
+        // opening again finds the bill and does not rerun the migrations
+        let data = Sqlite::open(&path).unwrap();
+        assert_eq!(data.get_bill(id).unwrap(), bill);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_assign_owner() {
+        // a database from before there were accounts
+        let mut connection = Connection::open_in_memory().unwrap();
+        migrate(&mut connection, &MIGRATIONS[..MIGRATIONS.len() - 1]).unwrap();
+        let (bill, group, payment) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        connection.execute(
+            "INSERT INTO bills (id, name, currency, counter, rounding)
+            VALUES (?1, 'dinner', 'USD', 0, 'largest_remainder')",
+            params![bill.to_string()],
+        ).unwrap();
+        connection.execute("INSERT INTO groups (id, name) VALUES (?1, 'trip')", params![group.to_string()]).unwrap();
+        connection.execute(
+            "INSERT INTO payments (id, from_person, to_person, amount, currency, date)
+            VALUES (?1, 'bob', 'alice', 500, 'USD', '2024-05-01')",
+            params![payment.to_string()],
+        ).unwrap();
+
+        let data = Sqlite::from_connection(connection).unwrap();
+        for username in ["alice", "bob"] {
+            data.add_user(&User { username: username.to_string(), password_hash: "hash".to_string() }).unwrap();
+        }
+        let alice = data.with_user("alice".to_string());
+        assert_eq!(alice.get_bill(bill), Err(DataError::BillNotFound));
+        assert_eq!(alice.assign_owner("alice"), Err(DataError::Forbidden));
+        assert_eq!(data.assign_owner("dave"), Err(DataError::UserNotFound));
+
+        assert_eq!(data.assign_owner("alice"), Ok(Assigned { bills: 1, groups: 1, payments: 1 }));
+        let stored = alice.get_versioned_bill(bill).unwrap();
+        assert_eq!((stored.owner, stored.version), (Some("alice".to_string()), 2));
+        let change = alice.get_history(bill).unwrap().pop().unwrap().change;
+        assert_eq!(change, Change::OwnerAssigned { username: "alice".to_string() });
+        assert_eq!(alice.get_group(group).unwrap().name, "trip");
+        assert!(alice.get_payment(payment).is_ok());
+        assert_eq!(alice.delete_payment(payment), Ok(payment));
+
+        // what has an owner keeps them
+        assert_eq!(data.assign_owner("bob"), Ok(Assigned::default()));
+        assert_eq!(data.with_user("bob".to_string()).get_bill(bill), Err(DataError::BillNotFound));
+    }
+}
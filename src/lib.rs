@@ -1,7 +1,5 @@
-#[macro_use]
-extern crate lazy_static;
-
 pub mod api;
+pub mod cli;
 pub mod config;
 pub mod data;
 pub mod models;
@@ -11,9 +9,11 @@ use crate::config::Config;
 use crate::api::routes;
 
 pub async fn start_server(config: Config) {
+    let address = (config.app.host.clone(), config.app.port);
     let app = routes::routes(config);
 
-    // Run our application as a hyper server on http://localhost:3000.
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
+    // Run our application as a hyper server on the configured address.
+    let listener = tokio::net::TcpListener::bind(&address).await
+        .unwrap_or_else(|err| panic!("Could not listen on {}:{}: {}", address.0, address.1, err));
     axum::serve(listener, app).await.unwrap()
 }
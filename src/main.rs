@@ -1,10 +1,23 @@
-use billsplit;
+use clap::Parser;
+use billsplit::config::Config;
+use billsplit::config::settings::ConfigArgs;
+
+/// Serves the bill splitting API over HTTP.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    #[command(flatten)]
+    config: ConfigArgs,
+}
 
 #[tokio::main]
 pub async fn main() {
-    let config = billsplit::config::Config::new();
+    let args = Args::parse();
+    let config = Config::load(&args.config).unwrap_or_else(|err| {
+        eprintln!("billsplit: {}", err);
+        std::process::exit(1);
+    });
 
     billsplit::start_server(config).await;
 
 }
-
@@ -0,0 +1,218 @@
+use serde::{Deserialize, Serialize};
+use crate::models::currency::{Currency, Money, MoneyError};
+
+/// What a tip percentage is taken from.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, Eq, Hash, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TipBase {
+    /// The subtotal after discounts, before tax.
+    #[default]
+    PreTax,
+    /// The subtotal after discounts, plus tax.
+    PostTax,
+}
+
+/// A charge or credit on top of the items. Rates are in hundredths of a
+/// percent (825 = 8.25%) and round half away from zero to the nearest unit.
+#[derive(Debug, Deserialize, Serialize, Clone, Eq, Hash, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AdjustmentKind {
+    /// Tax on the subtotal after discounts.
+    TaxRate(u32),
+    TaxAmount(Money),
+    Tip {
+        rate: u32,
+        #[serde(default)]
+        base: TipBase,
+    },
+    TipAmount(Money),
+    /// A service fee on the subtotal.
+    FeeRate(u32),
+    Fee(Money),
+    /// A discount on the subtotal.
+    DiscountRate(u32),
+    Discount(Money),
+}
+
+impl AdjustmentKind {
+    /// Whether the adjustment takes money off the bill rather than adding it.
+    pub fn is_credit(&self) -> bool {
+        matches!(self, AdjustmentKind::DiscountRate(_) | AdjustmentKind::Discount(_))
+    }
+
+    /// The fixed-amount adjustment of the same kind, e.g. a tax rate becomes
+    /// a tax amount.
+    pub fn with_amount(&self, amount: Money) -> AdjustmentKind {
+        match self {
+            AdjustmentKind::TaxRate(_) | AdjustmentKind::TaxAmount(_) => AdjustmentKind::TaxAmount(amount),
+            AdjustmentKind::Tip { .. } | AdjustmentKind::TipAmount(_) => AdjustmentKind::TipAmount(amount),
+            AdjustmentKind::FeeRate(_) | AdjustmentKind::Fee(_) => AdjustmentKind::Fee(amount),
+            AdjustmentKind::DiscountRate(_) | AdjustmentKind::Discount(_) => AdjustmentKind::Discount(amount),
+        }
+    }
+
+    /// The fixed amount, if this is a fixed-amount adjustment.
+    pub fn amount(&self) -> Option<Money> {
+        match self {
+            AdjustmentKind::TaxAmount(amount)
+            | AdjustmentKind::TipAmount(amount)
+            | AdjustmentKind::Fee(amount)
+            | AdjustmentKind::Discount(amount) => Some(*amount),
+            _ => None,
+        }
+    }
+}
+
+/// Who pays for an adjustment.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, Eq, Hash, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AllocationRule {
+    /// In proportion to each orderer's subtotal.
+    #[default]
+    Proportional,
+    /// Evenly between everyone on the bill.
+    Equal,
+    /// Entirely by one person.
+    Assigned(String),
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Eq, Hash, PartialEq)]
+pub struct Adjustment {
+    pub name: String,
+    pub kind: AdjustmentKind,
+    #[serde(default)]
+    pub allocation: AllocationRule,
+}
+
+impl Adjustment {
+    pub fn new(name: String, kind: AdjustmentKind) -> Self {
+        Self {
+            name,
+            kind,
+            allocation: AllocationRule::default(),
+        }
+    }
+
+    /// Fails unless any fixed amount is in `currency`.
+    pub fn expect_currency(&self, currency: Currency) -> Result<(), MoneyError> {
+        match self.kind.amount() {
+            Some(amount) => amount.expect_currency(currency),
+            None => Ok(()),
+        }
+    }
+
+    pub fn assigned_to(&self) -> Option<&str> {
+        match &self.allocation {
+            AllocationRule::Assigned(orderer) => Some(orderer),
+            _ => None,
+        }
+    }
+}
+
+fn apply_rate(base: Money, rate: u32) -> Result<Money, MoneyError> {
+    let exact = base.amount() as i128 * rate as i128;
+    let rounded = (exact.abs() + 5_000) / 10_000 * exact.signum();
+    let amount = i64::try_from(rounded).map_err(|_| MoneyError::Overflow)?;
+    Ok(Money::new(amount, base.currency()))
+}
+
+/// Works out the amount of every adjustment on a bill with the given
+/// subtotal, in the same order as `adjustments`. Every amount is in the
+/// subtotal's currency.
+///
+/// Discounts are worked out first, from the subtotal. Taxes and pre-tax tips
+/// are taken from the subtotal less discounts, post-tax tips from that plus
+/// tax, and fees from the subtotal. Fails if any of these is too large to
+/// hold.
+pub fn calculate_amounts(adjustments: &[Adjustment], subtotal: Money) -> Result<Vec<Money>, MoneyError> {
+    let mut amounts = vec![Money::zero(subtotal.currency()); adjustments.len()];
+
+    for (index, adjustment) in adjustments.iter().enumerate() {
+        match &adjustment.kind {
+            AdjustmentKind::DiscountRate(rate) => amounts[index] = apply_rate(subtotal, *rate)?,
+            AdjustmentKind::Discount(amount) => amounts[index] = *amount,
+            _ => {},
+        }
+    }
+    let pre_tax = subtotal.checked_sub(Money::checked_sum(subtotal.currency(), &amounts)?)?;
+
+    for (index, adjustment) in adjustments.iter().enumerate() {
+        match &adjustment.kind {
+            AdjustmentKind::TaxRate(rate) => amounts[index] = apply_rate(pre_tax, *rate)?,
+            AdjustmentKind::TaxAmount(amount) => amounts[index] = *amount,
+            _ => {},
+        }
+    }
+    let taxes = adjustments.iter().zip(&amounts)
+        .filter(|(adjustment, _)| matches!(adjustment.kind, AdjustmentKind::TaxRate(_) | AdjustmentKind::TaxAmount(_)))
+        .map(|(_, amount)| amount);
+    let post_tax = pre_tax.checked_add(Money::checked_sum(subtotal.currency(), taxes)?)?;
+
+    for (index, adjustment) in adjustments.iter().enumerate() {
+        match &adjustment.kind {
+            AdjustmentKind::Tip { rate, base: TipBase::PreTax } => amounts[index] = apply_rate(pre_tax, *rate)?,
+            AdjustmentKind::Tip { rate, base: TipBase::PostTax } => amounts[index] = apply_rate(post_tax, *rate)?,
+            AdjustmentKind::TipAmount(amount) => amounts[index] = *amount,
+            AdjustmentKind::FeeRate(rate) => amounts[index] = apply_rate(subtotal, *rate)?,
+            AdjustmentKind::Fee(amount) => amounts[index] = *amount,
+            _ => {},
+        }
+    }
+    Ok(amounts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usd(amount: i64) -> Money {
+        Money::new(amount, Currency::USD)
+    }
+
+    fn amounts(amounts: Vec<Money>) -> Vec<i64> {
+        amounts.iter().map(|amount| amount.amount()).collect()
+    }
+
+    #[test]
+    fn test_calculate_amounts() {
+        let adjustments = vec![
+            Adjustment::new("tax".to_string(), AdjustmentKind::TaxRate(1000)),
+            Adjustment::new("tip".to_string(), AdjustmentKind::Tip { rate: 2000, base: TipBase::PreTax }),
+            Adjustment::new("tip on tax".to_string(), AdjustmentKind::Tip { rate: 2000, base: TipBase::PostTax }),
+            Adjustment::new("service".to_string(), AdjustmentKind::FeeRate(500)),
+            Adjustment::new("delivery".to_string(), AdjustmentKind::Fee(usd(299))),
+            Adjustment::new("coupon".to_string(), AdjustmentKind::Discount(usd(1000))),
+        ];
+        // the tax and tips are taken from 10000 - 1000, the service fee from 10000
+        assert_eq!(amounts(calculate_amounts(&adjustments, usd(10_000)).unwrap()), vec![900, 1800, 1980, 500, 299, 1000]);
+    }
+
+    #[test]
+    fn test_calculate_amounts_rounds_half_up() {
+        let adjustments = vec![
+            Adjustment::new("tax".to_string(), AdjustmentKind::TaxRate(825)),
+            Adjustment::new("discount".to_string(), AdjustmentKind::DiscountRate(1000)),
+        ];
+        // 1234 * 10% = 123.4, then (1234 - 123) * 8.25% = 91.66
+        assert_eq!(amounts(calculate_amounts(&adjustments, usd(1234)).unwrap()), vec![92, 123]);
+    }
+
+    #[test]
+    fn test_calculate_amounts_overflow() {
+        let discounts = vec![
+            Adjustment::new("coupon".to_string(), AdjustmentKind::Discount(usd(i64::MAX))),
+            Adjustment::new("voucher".to_string(), AdjustmentKind::Discount(usd(i64::MAX))),
+        ];
+        assert_eq!(calculate_amounts(&discounts, usd(100)), Err(MoneyError::Overflow));
+        let tip = vec![Adjustment::new("tip".to_string(), AdjustmentKind::Tip { rate: 20_000, base: TipBase::PreTax })];
+        assert_eq!(calculate_amounts(&tip, usd(i64::MAX)), Err(MoneyError::Overflow));
+    }
+
+    #[test]
+    fn test_with_amount() {
+        assert_eq!(AdjustmentKind::TaxRate(825).with_amount(usd(10)), AdjustmentKind::TaxAmount(usd(10)));
+        assert_eq!(AdjustmentKind::Tip { rate: 1, base: TipBase::PostTax }.with_amount(usd(10)), AdjustmentKind::TipAmount(usd(10)));
+        assert!(AdjustmentKind::DiscountRate(10).with_amount(usd(10)).is_credit());
+        assert!(!AdjustmentKind::Fee(usd(10)).is_credit());
+    }
+}
@@ -1,106 +1,650 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use crate::models::item::LineItem;
-use crate::models::currency::Currency;
+use crate::models::item::{LineItem, Portion};
+use crate::models::currency::{Currency, Money, MoneyError};
+use crate::models::breakdown::{Breakdown, Settlement, Share};
+use crate::models::exchange::{ExchangeError, RateTable};
+use crate::models::split::{split, Allocation, RoundingPolicy};
+use crate::models::adjustment::{calculate_amounts, Adjustment, AdjustmentKind, AllocationRule};
+use crate::models::participant::{ParticipantChange, ParticipantError, Participants};
+use crate::models::payer::Payer;
+use crate::models::payment::Payment;
+use crate::models::settlement::{Ledger, SettlementPlan};
+use crate::models::validate::{finish, Report, ValidationError, MAX_ADJUSTMENTS, MAX_ITEMS, MAX_PAYERS};
 
 
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum BillError {
+    ItemNotFound,
+    AdjustmentNotFound,
+    Money(MoneyError),
+    Participant(ParticipantError),
+}
+
+impl fmt::Display for BillError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BillError::ItemNotFound => write!(f, "Item not found"),
+            BillError::AdjustmentNotFound => write!(f, "Adjustment not found"),
+            BillError::Money(err) => write!(f, "{}", err),
+            BillError::Participant(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for BillError {}
+
+impl From<MoneyError> for BillError {
+    fn from(err: MoneyError) -> Self {
+        BillError::Money(err)
+    }
+}
+
+impl From<ParticipantError> for BillError {
+    fn from(err: ParticipantError) -> Self {
+        BillError::Participant(err)
+    }
+}
+
+/// A bill in a single currency. Every amount on it, from item prices to
+/// the total, must be in `currency`.
 #[derive(Debug, Deserialize, Serialize, Clone, Eq, PartialEq)]
 pub struct Bill {
     pub name: String,
-    total: Option<Currency>,
+    #[serde(default)]
+    currency: Currency,
+    total: Option<Money>,
     items: HashMap<u16, LineItem>,
     counter: u16,
+    #[serde(default)]
+    pub rounding: RoundingPolicy,
+    #[serde(default)]
+    adjustments: Vec<Adjustment>,
+    #[serde(default)]
+    payers: Vec<Payer>,
+    #[serde(default)]
+    participants: Participants,
 }
 
 impl Bill {
-    pub fn new(name: String) -> Self {
+    pub fn new(name: String, currency: Currency) -> Self {
         Self {
             name,
+            currency,
             total: None,
             items: HashMap::new(),
             counter: 0,
+            rounding: RoundingPolicy::default(),
+            adjustments: vec![],
+            payers: vec![],
+            participants: Participants::new(),
         }
     }
 
     pub fn from(
         name: String,
-        total: Currency,
+        total: Money,
     ) -> Self {
-        let mut bill = Self::new(name);
+        let mut bill = Self::new(name, total.currency());
         bill.total = Some(total);
         bill
     }
 
+    /// Puts a bill back together from its stored parts, keeping the item ids
+    /// and the next id to hand out.
+    pub(crate) fn from_parts(
+        name: String,
+        currency: Currency,
+        total: Option<Money>,
+        items: HashMap<u16, LineItem>,
+        counter: u16,
+        rounding: RoundingPolicy,
+        adjustments: Vec<Adjustment>,
+    ) -> Self {
+        Self {
+            name,
+            currency,
+            total,
+            items,
+            counter,
+            rounding,
+            adjustments,
+            payers: vec![],
+            participants: Participants::new(),
+        }
+    }
+
+    /// The id the next item added will get.
+    pub(crate) fn counter(&self) -> u16 {
+        self.counter
+    }
+
+    pub(crate) fn set_counter(&mut self, counter: u16) {
+        self.counter = counter;
+    }
+
     fn get_counter(&mut self) -> u16 {
         self.counter += 1;
         self.counter - 1
     }
 
-    pub fn add_item(&mut self, item: LineItem) -> u16 {
+    pub fn add_item(&mut self, item: LineItem) -> Result<u16, BillError> {
+        item.expect_currency(self.currency)?;
         let id = self.get_counter();
         self.items.insert(id, item);
-        id
+        Ok(id)
     }
 
-    pub fn delete_item(&mut self, id: u16) -> Result<u16, String> {
+    pub fn delete_item(&mut self, id: u16) -> Result<u16, BillError> {
         match self.items.remove(&id) {
             Some(_) => Ok(id),
-            None => Err("Item not found".to_string())
+            None => Err(BillError::ItemNotFound)
         }
     }
 
-    pub fn update_item(&mut self, id: u16, item: LineItem) -> Result<u16, String> {
+    pub fn update_item(&mut self, id: u16, item: LineItem) -> Result<u16, BillError> {
+        item.expect_currency(self.currency)?;
         match self.items.get_mut(&id) {
             Some(existing_item) => {
                 *existing_item = item;
                 Ok(id)
             },
-            None => Err("Item not found".to_string())
+            None => Err(BillError::ItemNotFound)
         }
     }
 
-    pub fn calculate_subtotal(&self) -> Currency {
-        let mut total = 0;
-        for (_, item) in &self.items {
-            total += item.price;
+    pub fn add_adjustment(&mut self, adjustment: Adjustment) -> Result<usize, BillError> {
+        adjustment.expect_currency(self.currency)?;
+        self.adjustments.push(adjustment);
+        Ok(self.adjustments.len() - 1)
+    }
+
+    pub fn delete_adjustment(&mut self, index: usize) -> Result<Adjustment, BillError> {
+        match index < self.adjustments.len() {
+            true => Ok(self.adjustments.remove(index)),
+            false => Err(BillError::AdjustmentNotFound)
+        }
+    }
+
+    pub fn adjustments(&self) -> &[Adjustment] {
+        &self.adjustments
+    }
+
+    /// Who paid towards the bill, replacing anyone recorded before.
+    pub fn set_payers(&mut self, payers: Vec<Payer>) -> Result<(), BillError> {
+        for payer in &payers {
+            payer.expect_currency(self.currency)?;
+        }
+        self.payers = payers;
+        Ok(())
+    }
+
+    pub fn payers(&self) -> &[Payer] {
+        &self.payers
+    }
+
+    pub fn participants(&self) -> &Participants {
+        &self.participants
+    }
+
+    /// Puts back participants as they were stored, without touching what
+    /// refers to them.
+    pub(crate) fn set_participants(&mut self, participants: Participants) {
+        self.participants = participants;
+    }
+
+    /// Registers, updates, removes or merges a participant, then resolves
+    /// every reference on the bill against the participants that are left.
+    pub fn change_participants(&mut self, change: ParticipantChange) -> Result<(), BillError> {
+        if let Some((id, name)) = self.participants.apply(change)? {
+            let id = id.to_string();
+            self.rename_references(|reference| match reference == id {
+                true => name.clone(),
+                false => reference.to_string(),
+            })?;
+        }
+        self.resolve_participants()
+    }
+
+    /// Replaces every orderer, assignee and payer that names a participant
+    /// with the participant's id. Two orderers of an item that turn out to
+    /// be the same participant become one, with their portions added up.
+    pub fn resolve_participants(&mut self) -> Result<(), BillError> {
+        if self.participants.is_empty() {
+            return Ok(());
         }
-        total
+        let participants = self.participants.clone();
+        self.rename_references(|reference| participants.reference(reference))
     }
 
-    pub fn get_bill_for(&self, orderer: &str) -> Bill {
-        let mut bill = Bill::new(self.name.clone());
-        for (_, item) in &self.items {
-            if item.orderer == Some(orderer.to_string()) {
-                bill.add_item(item.clone());
+    /// Renames everyone the bill refers to, or changes nothing if that would
+    /// leave an item with portions that do not add up.
+    fn rename_references(&mut self, rename: impl Fn(&str) -> String) -> Result<(), BillError> {
+        let mut items = HashMap::with_capacity(self.items.len());
+        for (item_id, item) in &self.items {
+            let mut orderers: BTreeMap<String, Portion> = BTreeMap::new();
+            for (orderer, portion) in &item.orderers {
+                let orderer = rename(orderer);
+                let portion = match orderers.get(&orderer) {
+                    Some(existing) => existing.combine(*portion).ok_or_else(|| {
+                        ParticipantError::Clash { item_id: *item_id, orderer: orderer.clone() }
+                    })?,
+                    None => *portion,
+                };
+                orderers.insert(orderer, portion);
             }
+            items.insert(*item_id, LineItem::shared(item.name.clone(), item.price, orderers));
         }
+        self.items = items;
+        for adjustment in &mut self.adjustments {
+            if let AllocationRule::Assigned(orderer) = &adjustment.allocation {
+                adjustment.allocation = AllocationRule::Assigned(rename(orderer));
+            }
+        }
+        for payer in &mut self.payers {
+            payer.name = rename(&payer.name);
+        }
+        Ok(())
+    }
 
-        // split the total proportionally
-        if !self.total.is_none() {
-            let my_subtotal = bill.calculate_subtotal();
-            let all_subtotal = self.calculate_subtotal();
-            let ratio = my_subtotal as f64 / all_subtotal as f64;
-            let total = self.total.unwrap() as f64;
-            bill.total = Some((total * ratio) as Currency);
+    pub fn set_total(&mut self, total: Option<Money>) -> Result<(), BillError> {
+        if let Some(total) = total {
+            total.expect_currency(self.currency)?;
         }
-        bill
+        self.total = total;
+        Ok(())
+    }
+
+    pub fn total(&self) -> Option<Money> {
+        self.total
+    }
+
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn items(&self) -> &HashMap<u16, LineItem> {
+        &self.items
+    }
+
+    /// Fails if any amount on the bill is in a different currency from the
+    /// bill itself. Bills built through `add_item` and friends always pass;
+    /// this is for bills that arrive whole, e.g. deserialized from a request.
+    pub fn check_currency(&self) -> Result<(), MoneyError> {
+        if let Some(total) = self.total {
+            total.expect_currency(self.currency)?;
+        }
+        for item in self.items.values() {
+            item.expect_currency(self.currency)?;
+        }
+        for adjustment in &self.adjustments {
+            adjustment.expect_currency(self.currency)?;
+        }
+        for payer in &self.payers {
+            payer.expect_currency(self.currency)?;
+        }
+        Ok(())
+    }
+
+    /// Fails with every field that breaks the limits in
+    /// `crate::models::validate`, or that could not have come from building
+    /// the bill up: an item id the counter would hand out again, or a total
+    /// below what the items come to after discounts.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let mut errors = vec![];
+        let mut report = Report::new(&mut errors);
+        report.check_name("name", &self.name);
+        self.participants.check(&mut report);
+
+        report.check_count("items", self.items.len(), MAX_ITEMS);
+        let mut ids = self.items.keys().copied().collect::<Vec<u16>>();
+        ids.sort_unstable();
+        for id in &ids {
+            self.items[id].check(&mut report.nested(&format!("items.{}", id)));
+        }
+        if let Some(id) = ids.last().filter(|id| **id >= self.counter) {
+            report.push("counter", format!("must be greater than every item id, but item {} exists", id));
+        }
+
+        report.check_count("adjustments", self.adjustments.len(), MAX_ADJUSTMENTS);
+        for (index, adjustment) in self.adjustments.iter().enumerate() {
+            let mut report = report.nested(&format!("adjustments.{}", index));
+            report.check_name("name", &adjustment.name);
+            if let AdjustmentKind::DiscountRate(rate) = adjustment.kind {
+                if rate > 10_000 {
+                    report.push("kind", "discount rate must be at most 10000 (100%)");
+                }
+            }
+            // whether a fixed amount adds to the bill or takes off it is up
+            // to its kind
+            if let Some(amount) = adjustment.kind.amount() {
+                report.check_amount("kind", amount);
+                if amount.is_negative() {
+                    report.push("kind", "amount must not be negative");
+                }
+            }
+            if let Some(orderer) = adjustment.assigned_to() {
+                report.check_name("allocation", orderer);
+            }
+        }
+
+        report.check_count("payers", self.payers.len(), MAX_PAYERS);
+        for (index, payer) in self.payers.iter().enumerate() {
+            let mut report = report.nested(&format!("payers.{}", index));
+            report.check_name("name", &payer.name);
+            report.check_amount("amount", payer.amount);
+            if payer.amount.is_negative() {
+                report.push("amount", "must not be negative");
+            }
+        }
+
+        if let Some(total) = self.total.filter(|total| total.currency() == self.currency) {
+            report.check_amount("total", total);
+            match self.calculate_least_total() {
+                Ok(least) if total.amount() < least.amount() => {
+                    report.push("total", format!("must be at least the subtotal less discounts ({})", least));
+                },
+                Ok(_) => {},
+                Err(err) => report.push("total", format!("cannot be checked against the items: {}", err)),
+            }
+        }
+        finish(errors)
+    }
+
+    /// The subtotal less discounts, which a set total must not go below.
+    fn calculate_least_total(&self) -> Result<Money, MoneyError> {
+        let subtotal = self.calculate_subtotal()?;
+        let amounts = calculate_amounts(&self.adjustments, subtotal)?;
+        let credits = self.adjustments.iter()
+            .zip(&amounts)
+            .filter(|(adjustment, _)| adjustment.kind.is_credit())
+            .map(|(_, amount)| amount);
+        subtotal.checked_sub(Money::checked_sum(self.currency, credits)?)
+    }
+
+    /// The sum of the item prices. Fails if it is too large to hold.
+    pub fn calculate_subtotal(&self) -> Result<Money, MoneyError> {
+        Money::checked_sum(self.currency, self.items.values().map(|item| &item.price))
+    }
+
+    /// Each orderer's subtotal after splitting shared items, and how many
+    /// rounding units they absorbed doing so, in the same order as `orderers`.
+    fn calculate_subtotals(&self, orderers: &[String]) -> Result<(Vec<Money>, Vec<u64>), MoneyError> {
+        let mut subtotals = vec![Money::zero(self.currency); orderers.len()];
+        let mut rounding = vec![0; orderers.len()];
+        for item in self.items.values() {
+            let (item_orderers, allocation) = item.allocate(orderers, self.rounding)?;
+            for (index, orderer) in item_orderers.iter().enumerate() {
+                if let Some(position) = orderers.iter().position(|other| other == orderer) {
+                    subtotals[position] = subtotals[position].checked_add(allocation.parts[index])?;
+                    rounding[position] += allocation.rounding[index];
+                }
+            }
+        }
+        Ok((subtotals, rounding))
+    }
+
+    pub fn calculate_subtotal_for(&self, orderer: &str) -> Result<Money, MoneyError> {
+        let orderer = &self.participants.reference(orderer);
+        let orderers = self.get_orderers();
+        let (subtotals, _) = self.calculate_subtotals(&orderers)?;
+        Ok(orderers.iter()
+            .position(|other| other == orderer)
+            .map_or(Money::zero(self.currency), |index| subtotals[index]))
+    }
+
+    /// The subtotal with every adjustment applied, before `total` is
+    /// taken into account.
+    fn calculate_itemized_total(&self) -> Result<Money, MoneyError> {
+        let subtotal = self.calculate_subtotal()?;
+        let amounts = calculate_amounts(&self.adjustments, subtotal)?;
+        self.adjustments.iter().zip(amounts).try_fold(
+            subtotal,
+            |total, (adjustment, amount)| match adjustment.kind.is_credit() {
+                true => total.checked_sub(amount),
+                false => total.checked_add(amount),
+            },
+        )
+    }
+
+    /// The amount due for the whole bill: `total` if it is set, otherwise
+    /// the subtotal with every adjustment applied.
+    pub fn calculate_total(&self) -> Result<Money, MoneyError> {
+        match self.total {
+            Some(total) => Ok(total),
+            None => self.calculate_itemized_total(),
+        }
+    }
+
+    /// Splits the bill between `orderers`.
+    ///
+    /// Everyone starts from their subtotal, and each adjustment is then split
+    /// by its own allocation rule. If a total is set, whatever it adds over
+    /// (or takes off) the itemized total is split in proportion to the
+    /// subtotals. Every split is exact, so the parts always add up to the
+    /// whole bill. A bill nobody has ordered anything from is left entirely
+    /// unassigned.
+    fn allocate(&self, orderers: &[String]) -> Result<Allocated, MoneyError> {
+        if orderers.is_empty() {
+            return Ok(Allocated {
+                owed: vec![],
+                rounding: vec![],
+                adjustments: vec![vec![]; self.adjustments.len()],
+                unassigned: self.calculate_total()?,
+            });
+        }
+
+        let (subtotals, mut rounding) = self.calculate_subtotals(orderers)?;
+        let weights = subtotals.iter().map(|subtotal| subtotal.amount().max(0) as u64).collect::<Vec<u64>>();
+        let mut owed = subtotals;
+        let mut add = |allocation: &Allocation, credit: bool| -> Result<(), MoneyError> {
+            for (index, part) in allocation.parts.iter().enumerate() {
+                owed[index] = match credit {
+                    true => owed[index].checked_sub(*part)?,
+                    false => owed[index].checked_add(*part)?,
+                };
+                rounding[index] += allocation.rounding[index];
+            }
+            Ok(())
+        };
+
+        let amounts = calculate_amounts(&self.adjustments, self.calculate_subtotal()?)?;
+        let mut adjustments = Vec::with_capacity(self.adjustments.len());
+        for (adjustment, amount) in self.adjustments.iter().zip(amounts) {
+            let assigned = adjustment.assigned_to()
+                .and_then(|orderer| orderers.iter().position(|other| other == orderer));
+            let allocation = match (&adjustment.allocation, assigned) {
+                (AllocationRule::Equal, _) => split(amount, &vec![1; orderers.len()], self.rounding)?,
+                (AllocationRule::Assigned(_), Some(index)) => {
+                    let mut parts = vec![Money::zero(self.currency); orderers.len()];
+                    parts[index] = amount;
+                    Allocation { parts, rounding: vec![0; orderers.len()] }
+                },
+                _ => split(amount, &weights, self.rounding)?,
+            };
+            add(&allocation, adjustment.kind.is_credit())?;
+            adjustments.push(allocation.parts);
+        }
+
+        if let Some(total) = self.total {
+            let difference = total.checked_sub(self.calculate_itemized_total()?)?;
+            let allocation = split(difference, &weights, self.rounding)?;
+            add(&allocation, false)?;
+        }
+
+        Ok(Allocated {
+            owed,
+            rounding,
+            adjustments,
+            unassigned: Money::zero(self.currency),
+        })
+    }
+
+    /// The part of the bill `orderer` is responsible for. Each shared item
+    /// appears with only their part of its price, and each adjustment as the
+    /// fixed amount they pay of it. `orderer` may be a participant's id, name
+    /// or alias.
+    pub fn get_bill_for(&self, orderer: &str) -> Result<Bill, MoneyError> {
+        let orderer = &self.participants.reference(orderer);
+        let mut bill = Bill::new(self.name.clone(), self.currency);
+        bill.rounding = self.rounding;
+        let orderers = self.get_orderers();
+        for item in self.items.values() {
+            let (item_orderers, allocation) = item.allocate(&orderers, self.rounding)?;
+            if let Some(index) = item_orderers.iter().position(|other| other == orderer) {
+                let price = allocation.parts[index];
+                let id = bill.get_counter();
+                bill.items.insert(id, LineItem::from(item.name.clone(), price, Some(orderer.to_string())));
+            }
+        }
+
+        let allocated = self.allocate(&orderers)?;
+        let index = orderers.iter().position(|other| other == orderer);
+        if let Some(index) = index {
+            for (adjustment, parts) in self.adjustments.iter().zip(&allocated.adjustments) {
+                if !parts[index].is_zero() {
+                    bill.adjustments.push(Adjustment::new(
+                        adjustment.name.clone(),
+                        adjustment.kind.with_amount(parts[index]),
+                    ));
+                }
+            }
+        }
+
+        // split the total exactly between everyone on the bill
+        if self.total.is_some() {
+            bill.total = Some(index.map_or(Money::zero(self.currency), |index| allocated.owed[index]));
+        }
+        Ok(bill)
+    }
+
+    /// Every distinct orderer on the bill, sorted by name. This includes
+    /// anyone an adjustment is assigned to.
+    pub fn get_orderers(&self) -> Vec<String> {
+        let mut orderers = self.items.values()
+            .flat_map(|item| item.orderers.keys().cloned())
+            .chain(self.adjustments.iter().filter_map(|adjustment| adjustment.assigned_to().map(String::from)))
+            .collect::<Vec<String>>();
+        orderers.sort();
+        orderers.dedup();
+        orderers
+    }
+
+    /// `orderer`'s sub-bill, with what they owe converted into `currency`
+    /// using `rates`.
+    pub fn get_settlement_for(
+        &self,
+        orderer: &str,
+        currency: Currency,
+        rates: &RateTable,
+    ) -> Result<Settlement, ExchangeError> {
+        let bill = self.get_bill_for(orderer)?;
+        let conversion = rates.convert(bill.calculate_total()?, currency)?;
+        Ok(Settlement {
+            orderer: orderer.to_string(),
+            bill,
+            conversion,
+        })
+    }
+
+    pub fn get_breakdown(&self) -> Result<Breakdown, MoneyError> {
+        let orderers = self.get_orderers();
+        let allocated = self.allocate(&orderers)?;
+        let shares = orderers.into_iter().enumerate().map(|(index, orderer)| {
+            let bill = self.get_bill_for(&orderer)?;
+            Ok(Share {
+                orderer,
+                owed: allocated.owed[index],
+                rounding: allocated.rounding[index],
+                bill,
+            })
+        }).collect::<Result<Vec<Share>, MoneyError>>()?;
+        Ok(Breakdown {
+            total: self.calculate_total()?,
+            shares,
+            unassigned: allocated.unassigned,
+        })
+    }
+
+    /// Everyone's balance from what they paid and what they owe by
+    /// `get_breakdown`, and the fewest transfers that settle them. Payments
+    /// made since are counted with `SettlementPlan::with_payments`.
+    pub fn get_settlement_plan(&self) -> Result<SettlementPlan, MoneyError> {
+        let orderers = self.get_orderers();
+        let allocated = self.allocate(&orderers)?;
+        let mut ledger = Ledger::new(self.currency);
+        for (orderer, owed) in orderers.iter().zip(&allocated.owed) {
+            ledger.add_owed(orderer, *owed);
+        }
+        for payer in &self.payers {
+            ledger.add_paid(&payer.name, payer.amount);
+        }
+        let paid = Money::checked_sum(self.currency, self.payers.iter().map(|payer| &payer.amount))?;
+        Ok(SettlementPlan::new(self.calculate_total()?, paid, allocated.unassigned, &ledger))
+    }
+
+    /// `get_settlement_plan` once `payments` towards the bill are counted,
+    /// with whoever they name resolved against the bill's participants.
+    pub fn get_settlement_plan_with<'a>(
+        &self,
+        payments: impl IntoIterator<Item = &'a Payment>,
+    ) -> Result<SettlementPlan, MoneyError> {
+        let payments = payments.into_iter().map(|payment| Payment {
+            from: self.participants.reference(&payment.from),
+            to: self.participants.reference(&payment.to),
+            ..payment.clone()
+        }).collect::<Vec<Payment>>();
+        Ok(self.get_settlement_plan()?.with_payments(&payments))
     }
 }
 
+/// Every orderer's part of a bill, in the same order as the orderers.
+struct Allocated {
+    owed: Vec<Money>,
+    rounding: Vec<u64>,
+    /// Each adjustment's parts, in the same order as the bill's adjustments.
+    adjustments: Vec<Vec<Money>>,
+    unassigned: Money,
+}
+
+/// A stored bill. `version` starts at 1 and goes up by one with every change
+/// to the bill or its items, which also moves `updated_at` on. The data
+/// layer keeps all of these; clients only ever send the `Bill`.
 #[derive(Debug, Deserialize, Serialize, Clone, Eq, PartialEq)]
 pub struct BillWithId {
     pub id: Uuid,
+    pub version: u64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// The user who created the bill, who alone can delete or share it.
+    /// Bills created by nobody in particular, e.g. from the command line,
+    /// have no owner and are only reached by stores acting for nobody,
+    /// until `billsplit assign-owner` gives them to a user.
+    #[serde(default)]
+    pub owner: Option<String>,
+    /// Everyone else the bill is shared with, who can see and change it.
+    #[serde(default)]
+    pub members: Vec<String>,
     pub bill: Bill,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::participant::Participant;
+    use crate::models::adjustment::TipBase;
+    use crate::models::exchange::ExchangeRate;
+
+    fn usd(amount: i64) -> Money {
+        Money::new(amount, Currency::USD)
+    }
 
     #[test]
     fn test_new() {
-        let bill = Bill::new("test".to_string());
+        let bill = Bill::new("test".to_string(), Currency::USD);
         assert_eq!(bill.name, "test");
         assert_eq!(bill.items.len(), 0);
         assert_eq!(bill.total, None);
@@ -110,87 +654,392 @@ mod tests {
     fn test_from() {
         let bill = Bill::from(
             "test".to_string(),
-            120,
+            usd(120),
         );
         assert_eq!(bill.name, "test");
         assert_eq!(bill.items.len(), 0);
-        assert_eq!(bill.total, Some(120));
+        assert_eq!(bill.total, Some(usd(120)));
     }
 
     #[test]
     fn test_add_item() {
-        let mut bill = Bill::new("test".to_string());
-        let item = LineItem::from("test".to_string(), 100, None);
-        let id = bill.add_item(item.clone());
+        let mut bill = Bill::new("test".to_string(), Currency::USD);
+        let item = LineItem::from("test".to_string(), usd(100), None);
+        let id = bill.add_item(item.clone()).unwrap();
         assert_eq!(bill.items.get(&id).unwrap(), &item);
     }
 
     #[test]
     fn test_delete_item() {
-        let mut bill = Bill::new("test".to_string());
-        let item = LineItem::from("test".to_string(), 100, None);
-        let id = bill.add_item(item.clone());
+        let mut bill = Bill::new("test".to_string(), Currency::USD);
+        let item = LineItem::from("test".to_string(), usd(100), None);
+        let id = bill.add_item(item.clone()).unwrap();
         assert_eq!(bill.delete_item(id), Ok(id));
-        assert_eq!(bill.delete_item(id), Err("Item not found".to_string()));
+        assert_eq!(bill.delete_item(id), Err(BillError::ItemNotFound));
     }
 
     #[test]
     fn test_update_item() {
-        let mut bill = Bill::new("test".to_string());
-        let item = LineItem::from("test".to_string(), 100, None);
-        let id = bill.add_item(item.clone());
-        let item = LineItem::from("test2".to_string(), 200, None);
+        let mut bill = Bill::new("test".to_string(), Currency::USD);
+        let item = LineItem::from("test".to_string(), usd(100), None);
+        let id = bill.add_item(item.clone()).unwrap();
+        let item = LineItem::from("test2".to_string(), usd(200), None);
         assert_eq!(bill.update_item(id, item.clone()), Ok(id));
         assert_eq!(bill.items.get(&id).unwrap(), &item);
-        assert_eq!(bill.update_item(100, item.clone()), Err("Item not found".to_string()));
+        assert_eq!(bill.update_item(100, item.clone()), Err(BillError::ItemNotFound));
     }
 
     #[test]
     fn test_calculate_subtotal() {
-        let mut bill = Bill::new("test".to_string());
-        let item = LineItem::from("test".to_string(), 100, None);
-        bill.add_item(item.clone());
-        let item = LineItem::from("test2".to_string(), 200, None);
-        bill.add_item(item.clone());
-        assert_eq!(bill.calculate_subtotal(), 300);
+        let mut bill = Bill::new("test".to_string(), Currency::USD);
+        let item = LineItem::from("test".to_string(), usd(100), None);
+        bill.add_item(item.clone()).unwrap();
+        let item = LineItem::from("test2".to_string(), usd(200), None);
+        bill.add_item(item.clone()).unwrap();
+        assert_eq!(bill.calculate_subtotal().unwrap(), usd(300));
     }
 
     #[test]
     fn test_get_bill_for() {
-        let mut bill = Bill::new("test".to_string());
-        let item = LineItem::from("test".to_string(), 100, Some("test".to_string()));
-        bill.add_item(item.clone());
-        let item = LineItem::from("test2".to_string(), 200, Some("test2".to_string()));
-        bill.add_item(item.clone());
+        let mut bill = Bill::new("test".to_string(), Currency::USD);
+        let item = LineItem::from("test".to_string(), usd(100), Some("test".to_string()));
+        bill.add_item(item.clone()).unwrap();
+        let item = LineItem::from("test2".to_string(), usd(200), Some("test2".to_string()));
+        bill.add_item(item.clone()).unwrap();
 
-        let bill1 = bill.get_bill_for("test");
+        let bill1 = bill.get_bill_for("test").unwrap();
         assert_eq!(bill1.total, None);
         assert_eq!(bill1.items.len(), 1);
-        assert_eq!(bill1.calculate_subtotal(), 100);
+        assert_eq!(bill1.calculate_subtotal().unwrap(), usd(100));
 
-        let bill2 = bill.get_bill_for("test2");
+        let bill2 = bill.get_bill_for("test2").unwrap();
         assert_eq!(bill2.total, None);
         assert_eq!(bill2.items.len(), 1);
-        assert_eq!(bill2.calculate_subtotal(), 200);
+        assert_eq!(bill2.calculate_subtotal().unwrap(), usd(200));
     }
 
     #[test]
     fn test_get_bill_for_with_total() {
-        let mut bill = Bill::new("test".to_string());
-        bill.total = Some(330);
-        let item = LineItem::from("test".to_string(), 100, Some("test".to_string()));
-        bill.add_item(item.clone());
-        let item = LineItem::from("test2".to_string(), 200, Some("test2".to_string()));
-        bill.add_item(item.clone());
-
-        let bill1 = bill.get_bill_for("test");
-        assert_eq!(bill1.total, Some(110));
+        let mut bill = Bill::new("test".to_string(), Currency::USD);
+        bill.total = Some(usd(330));
+        let item = LineItem::from("test".to_string(), usd(100), Some("test".to_string()));
+        bill.add_item(item.clone()).unwrap();
+        let item = LineItem::from("test2".to_string(), usd(200), Some("test2".to_string()));
+        bill.add_item(item.clone()).unwrap();
+
+        let bill1 = bill.get_bill_for("test").unwrap();
+        assert_eq!(bill1.total, Some(usd(110)));
         assert_eq!(bill1.items.len(), 1);
-        assert_eq!(bill1.calculate_subtotal(), 100);
+        assert_eq!(bill1.calculate_subtotal().unwrap(), usd(100));
 
-        let bill2 = bill.get_bill_for("test2");
-        assert_eq!(bill2.total, Some(220));
+        let bill2 = bill.get_bill_for("test2").unwrap();
+        assert_eq!(bill2.total, Some(usd(220)));
         assert_eq!(bill2.items.len(), 1);
-        assert_eq!(bill2.calculate_subtotal(), 200);
+        assert_eq!(bill2.calculate_subtotal().unwrap(), usd(200));
+    }
+
+    #[test]
+    fn test_get_orderers() {
+        let mut bill = Bill::new("test".to_string(), Currency::USD);
+        bill.add_item(LineItem::from("a".to_string(), usd(100), Some("bob".to_string()))).unwrap();
+        bill.add_item(LineItem::from("b".to_string(), usd(100), Some("alice".to_string()))).unwrap();
+        bill.add_item(LineItem::from("c".to_string(), usd(100), Some("bob".to_string()))).unwrap();
+        bill.add_item(LineItem::from("d".to_string(), usd(100), None)).unwrap();
+        assert_eq!(bill.get_orderers(), vec!["alice".to_string(), "bob".to_string()]);
+    }
+
+    #[test]
+    fn test_get_breakdown() {
+        let mut bill = Bill::new("test".to_string(), Currency::USD);
+        bill.total = Some(usd(330));
+        bill.add_item(LineItem::from("test".to_string(), usd(100), Some("test".to_string()))).unwrap();
+        bill.add_item(LineItem::from("test2".to_string(), usd(200), Some("test2".to_string()))).unwrap();
+
+        let breakdown = bill.get_breakdown().unwrap();
+        assert_eq!(breakdown.shares.len(), 2);
+        assert_eq!(breakdown.shares[0].orderer, "test");
+        assert_eq!(breakdown.shares[0].owed, usd(110));
+        assert_eq!(breakdown.shares[0].bill, bill.get_bill_for("test").unwrap());
+        assert_eq!(breakdown.shares[1].orderer, "test2");
+        assert_eq!(breakdown.shares[1].owed, usd(220));
+        assert_eq!(breakdown.unassigned, usd(0));
+    }
+
+    #[test]
+    fn test_get_breakdown_without_total() {
+        let mut bill = Bill::new("test".to_string(), Currency::USD);
+        bill.add_item(LineItem::from("test".to_string(), usd(100), Some("test".to_string()))).unwrap();
+        bill.add_item(LineItem::from("shared".to_string(), usd(50), None)).unwrap();
+
+        // items nobody in particular ordered are shared by everyone
+        let breakdown = bill.get_breakdown().unwrap();
+        assert_eq!(breakdown.shares.len(), 1);
+        assert_eq!(breakdown.shares[0].owed, usd(150));
+        assert_eq!(breakdown.unassigned, usd(0));
+
+        let mut bill = Bill::from("test".to_string(), usd(80));
+        bill.add_item(LineItem::from("shared".to_string(), usd(50), None)).unwrap();
+        let breakdown = bill.get_breakdown().unwrap();
+        assert_eq!(breakdown.shares.len(), 0);
+        assert_eq!(breakdown.unassigned, usd(80));
+    }
+
+    #[test]
+    fn test_get_settlement_plan() {
+        let mut bill = Bill::from("dinner".to_string(), usd(3300));
+        bill.add_item(LineItem::from("pasta".to_string(), usd(1000), Some("alice".to_string()))).unwrap();
+        bill.add_item(LineItem::from("steak".to_string(), usd(2000), Some("bob".to_string()))).unwrap();
+        bill.set_payers(vec![
+            Payer::new("carol".to_string(), usd(3000)),
+            Payer::new("alice".to_string(), usd(300)),
+        ]).unwrap();
+
+        let plan = bill.get_settlement_plan().unwrap();
+        assert_eq!((plan.total, plan.paid), (usd(3300), usd(3300)));
+        let nets = plan.balances.iter()
+            .map(|balance| (balance.person.as_str(), balance.net.amount()))
+            .collect::<Vec<(&str, i64)>>();
+        assert_eq!(nets, vec![("alice", -800), ("bob", -2200), ("carol", 3000)]);
+        let transfers = plan.transfers.iter()
+            .map(|transfer| (transfer.from.as_str(), transfer.to.as_str(), transfer.amount.amount()))
+            .collect::<Vec<(&str, &str, i64)>>();
+        assert_eq!(transfers, vec![("bob", "carol", 2200), ("alice", "carol", 800)]);
+
+        assert_eq!(
+            bill.set_payers(vec![Payer::new("carol".to_string(), Money::new(100, Currency::EUR))]),
+            Err(BillError::Money(MoneyError::CurrencyMismatch { expected: Currency::USD, found: Currency::EUR }))
+        );
+    }
+
+    #[test]
+    fn test_get_breakdown_sums_to_total() {
+        let mut bill = Bill::from("test".to_string(), usd(1000));
+        bill.add_item(LineItem::from("a".to_string(), usd(100), Some("alice".to_string()))).unwrap();
+        bill.add_item(LineItem::from("b".to_string(), usd(100), Some("bob".to_string()))).unwrap();
+        bill.add_item(LineItem::from("c".to_string(), usd(100), Some("carol".to_string()))).unwrap();
+
+        let breakdown = bill.get_breakdown().unwrap();
+        let owed = breakdown.shares.iter().map(|share| share.owed.amount()).collect::<Vec<i64>>();
+        assert_eq!(owed, vec![334, 333, 333]);
+        assert_eq!(breakdown.shares[0].rounding, 1);
+        assert_eq!(breakdown.shares[1].rounding, 0);
+        assert_eq!(breakdown.total, usd(1000));
+        assert_eq!(bill.get_bill_for("alice").unwrap().total, Some(usd(334)));
+        assert_eq!(bill.get_bill_for("bob").unwrap().total, Some(usd(333)));
+        assert_eq!(bill.get_bill_for("dave").unwrap().total, Some(usd(0)));
+
+        bill.rounding = RoundingPolicy::InOrder;
+        bill.add_item(LineItem::from("d".to_string(), usd(101), None)).unwrap();
+        let breakdown = bill.get_breakdown().unwrap();
+        let owed = breakdown.shares.iter().map(|share| share.owed.amount()).sum::<i64>();
+        assert_eq!(owed, 1000);
+        assert_eq!(breakdown.unassigned, usd(0));
+    }
+
+    #[test]
+    fn test_shared_items() {
+        let mut bill = Bill::from("test".to_string(), usd(550));
+        let orderers = [("alice".to_string(), Portion::Equal), ("bob".to_string(), Portion::Equal)];
+        bill.add_item(LineItem::shared("appetizer".to_string(), usd(300), orderers.into_iter().collect())).unwrap();
+        bill.add_item(LineItem::from("main".to_string(), usd(100), Some("alice".to_string()))).unwrap();
+
+        assert_eq!(bill.calculate_subtotal_for("alice").unwrap(), usd(250));
+        assert_eq!(bill.calculate_subtotal_for("bob").unwrap(), usd(150));
+        assert_eq!(bill.calculate_subtotal_for("carol").unwrap(), usd(0));
+
+        let alice = bill.get_bill_for("alice").unwrap();
+        assert_eq!(alice.items.len(), 2);
+        assert_eq!(alice.calculate_subtotal().unwrap(), usd(250));
+        // 550 * 250 / 400 = 343.75 and 550 * 150 / 400 = 206.25
+        assert_eq!(alice.total, Some(usd(344)));
+
+        let bob = bill.get_bill_for("bob").unwrap();
+        assert_eq!(bob.items.len(), 1);
+        assert_eq!(bob.items.values().next().unwrap().price, usd(150));
+        assert_eq!(bob.total, Some(usd(206)));
+
+        let breakdown = bill.get_breakdown().unwrap();
+        assert_eq!(breakdown.shares[0].rounding, 1);
+        assert_eq!(breakdown.shares[1].rounding, 0);
+    }
+
+    #[test]
+    fn test_adjustments() {
+        let mut bill = Bill::new("test".to_string(), Currency::USD);
+        bill.add_item(LineItem::from("a".to_string(), usd(6000), Some("alice".to_string()))).unwrap();
+        bill.add_item(LineItem::from("b".to_string(), usd(4000), Some("bob".to_string()))).unwrap();
+        bill.add_adjustment(Adjustment::new("tax".to_string(), AdjustmentKind::TaxRate(1000))).unwrap();
+        bill.add_adjustment(Adjustment {
+            name: "tip".to_string(),
+            kind: AdjustmentKind::Tip { rate: 2000, base: TipBase::PreTax },
+            allocation: AllocationRule::Equal,
+        }).unwrap();
+        bill.add_adjustment(Adjustment {
+            name: "corkage".to_string(),
+            kind: AdjustmentKind::Fee(usd(300)),
+            allocation: AllocationRule::Assigned("bob".to_string()),
+        }).unwrap();
+        bill.add_adjustment(Adjustment::new("coupon".to_string(), AdjustmentKind::Discount(usd(500)))).unwrap();
+
+        // 10000 - 500 discount + 950 tax + 1900 tip + 300 fee
+        assert_eq!(bill.calculate_total().unwrap(), usd(12650));
+
+        let breakdown = bill.get_breakdown().unwrap();
+        assert_eq!(breakdown.total, usd(12650));
+        assert_eq!(breakdown.shares[0].owed, usd(6000 + 570 + 950 - 300));
+        assert_eq!(breakdown.shares[1].owed, usd(4000 + 380 + 950 + 300 - 200));
+
+        let bob = bill.get_bill_for("bob").unwrap();
+        assert_eq!(bob.total, None);
+        assert_eq!(bob.adjustments, vec![
+            Adjustment::new("tax".to_string(), AdjustmentKind::TaxAmount(usd(380))),
+            Adjustment::new("tip".to_string(), AdjustmentKind::TipAmount(usd(950))),
+            Adjustment::new("corkage".to_string(), AdjustmentKind::Fee(usd(300))),
+            Adjustment::new("coupon".to_string(), AdjustmentKind::Discount(usd(200))),
+        ]);
+        assert_eq!(bob.calculate_total().unwrap(), breakdown.shares[1].owed);
+
+        // anything the total adds on top is split by subtotal
+        bill.total = Some(usd(13000));
+        let breakdown = bill.get_breakdown().unwrap();
+        assert_eq!(breakdown.shares[0].owed, usd(7220 + 210));
+        assert_eq!(breakdown.shares[1].owed, usd(5430 + 140));
+        assert_eq!(bill.get_bill_for("alice").unwrap().total, Some(usd(7430)));
+    }
+
+    #[test]
+    fn test_adjustment_assigned_to_non_orderer() {
+        let mut bill = Bill::new("test".to_string(), Currency::USD);
+        bill.add_item(LineItem::from("a".to_string(), usd(1000), Some("alice".to_string()))).unwrap();
+        bill.add_adjustment(Adjustment {
+            name: "tip".to_string(),
+            kind: AdjustmentKind::TipAmount(usd(200)),
+            allocation: AllocationRule::Assigned("carol".to_string()),
+        }).unwrap();
+        assert_eq!(bill.get_orderers(), vec!["alice".to_string(), "carol".to_string()]);
+        let breakdown = bill.get_breakdown().unwrap();
+        assert_eq!(breakdown.shares[0].owed, usd(1000));
+        assert_eq!(breakdown.shares[1].owed, usd(200));
+        assert_eq!(bill.delete_adjustment(0).unwrap().name, "tip");
+        assert_eq!(bill.delete_adjustment(0), Err(BillError::AdjustmentNotFound));
+    }
+
+    #[test]
+    fn test_mixed_currencies() {
+        let mut bill = Bill::new("test".to_string(), Currency::EUR);
+        let mismatch = BillError::Money(MoneyError::CurrencyMismatch { expected: Currency::EUR, found: Currency::USD });
+        assert_eq!(bill.add_item(LineItem::from("a".to_string(), usd(100), None)), Err(mismatch.clone()));
+        assert_eq!(bill.set_total(Some(usd(100))), Err(mismatch.clone()));
+        assert_eq!(
+            bill.add_adjustment(Adjustment::new("tip".to_string(), AdjustmentKind::TipAmount(usd(1)))),
+            Err(mismatch.clone())
+        );
+        let id = bill.add_item(LineItem::from("a".to_string(), Money::new(100, Currency::EUR), None)).unwrap();
+        assert_eq!(bill.update_item(id, LineItem::from("a".to_string(), usd(100), None)), Err(mismatch));
+        assert!(bill.check_currency().is_ok());
+
+        bill.total = Some(usd(100));
+        assert!(bill.check_currency().is_err());
+    }
+
+    #[test]
+    fn test_negative_shares() {
+        let mut bill = Bill::new("test".to_string(), Currency::USD);
+        bill.add_item(LineItem::from("a".to_string(), usd(1000), Some("alice".to_string()))).unwrap();
+        bill.add_item(LineItem::from("b".to_string(), usd(100), Some("bob".to_string()))).unwrap();
+        bill.add_adjustment(Adjustment {
+            name: "voucher".to_string(),
+            kind: AdjustmentKind::Discount(usd(300)),
+            allocation: AllocationRule::Assigned("bob".to_string()),
+        }).unwrap();
+
+        let breakdown = bill.get_breakdown().unwrap();
+        assert_eq!(breakdown.total, usd(800));
+        assert_eq!(breakdown.shares[0].owed, usd(1000));
+        assert_eq!(breakdown.shares[1].owed, usd(-200));
+    }
+
+    #[test]
+    fn test_overflow() {
+        let mut bill = Bill::new("test".to_string(), Currency::USD);
+        bill.add_item(LineItem::from("a".to_string(), usd(i64::MAX), Some("alice".to_string()))).unwrap();
+        bill.add_item(LineItem::from("b".to_string(), usd(1), Some("alice".to_string()))).unwrap();
+        assert_eq!(bill.calculate_subtotal(), Err(MoneyError::Overflow));
+        assert_eq!(bill.calculate_subtotal_for("alice"), Err(MoneyError::Overflow));
+        assert_eq!(bill.get_breakdown(), Err(MoneyError::Overflow));
+
+        let mut bill = Bill::new("test".to_string(), Currency::USD);
+        bill.add_item(LineItem::from("a".to_string(), usd(100), Some("alice".to_string()))).unwrap();
+        for _ in 0..2 {
+            bill.add_adjustment(Adjustment::new("voucher".to_string(), AdjustmentKind::Discount(usd(i64::MAX)))).unwrap();
+        }
+        assert_eq!(bill.calculate_total(), Err(MoneyError::Overflow));
+        assert_eq!(bill.get_bill_for("alice"), Err(MoneyError::Overflow));
+        assert_eq!(bill.get_settlement_plan(), Err(MoneyError::Overflow));
+        bill.set_total(Some(usd(100))).unwrap();
+        assert!(bill.validate().unwrap_err().errors.iter().any(|error| error.path == "total"));
+    }
+
+    #[test]
+    fn test_get_settlement_for() {
+        let eur = |amount| Money::new(amount, Currency::EUR);
+        let mut bill = Bill::new("test".to_string(), Currency::EUR);
+        bill.add_item(LineItem::from("a".to_string(), eur(1000), Some("a".to_string()))).unwrap();
+        bill.add_item(LineItem::from("b".to_string(), eur(2000), Some("b".to_string()))).unwrap();
+        bill.add_adjustment(Adjustment::new("tax".to_string(), AdjustmentKind::TaxRate(1000))).unwrap();
+        let rates = RateTable::new(
+            "2024-03-01T12:00:00Z".parse().unwrap(),
+            vec![ExchangeRate { from: Currency::EUR, to: Currency::USD, rate: "1.0842".parse().unwrap() }],
+        ).unwrap();
+
+        let settlement = bill.get_settlement_for("a", Currency::USD, &rates).unwrap();
+        assert_eq!(settlement.conversion.amount, eur(1100));
+        // 11.00 * 1.0842 = 11.9262
+        assert_eq!(settlement.conversion.converted, usd(1193));
+        assert_eq!(settlement.conversion.as_of, rates.as_of());
+        assert_eq!(settlement.bill.calculate_total().unwrap(), eur(1100));
+
+        assert_eq!(
+            bill.get_settlement_for("a", Currency::JPY, &rates),
+            Err(ExchangeError::NoRate { from: Currency::EUR, to: Currency::JPY })
+        );
+    }
+
+    #[test]
+    fn test_participants() {
+        let mut bill = Bill::new("dinner".to_string(), Currency::USD);
+        bill.add_item(LineItem::from("pasta".to_string(), usd(1200), Some("Bob ".to_string()))).unwrap();
+        let shared = LineItem::shared("wine".to_string(), usd(3000), BTreeMap::from([
+            ("bob".to_string(), Portion::Equal),
+            ("Robert".to_string(), Portion::Weight(2)),
+        ]));
+        let wine = bill.add_item(shared).unwrap();
+        bill.set_payers(vec![Payer::new("BOB".to_string(), usd(4200))]).unwrap();
+
+        let bob = Uuid::new_v4();
+        bill.change_participants(ParticipantChange::Added { id: bob, participant: Participant::new("Bob".to_string()) }).unwrap();
+        assert_eq!(bill.payers()[0].name, bob.to_string());
+        assert_eq!(bill.items[&wine].orderers.len(), 2);
+        assert_eq!(bill.get_bill_for("bob").unwrap().calculate_total().unwrap(), usd(2200));
+
+        let robert = Uuid::new_v4();
+        bill.change_participants(ParticipantChange::Added { id: robert, participant: Participant::new("Robert".to_string()) }).unwrap();
+        bill.add_item(LineItem::from("dessert".to_string(), usd(800), Some("robert".to_string()))).unwrap();
+        bill.resolve_participants().unwrap();
+        bill.change_participants(ParticipantChange::Merged { from: robert, into: bob }).unwrap();
+        assert_eq!(bill.get_bill_for(&robert.to_string()).unwrap().calculate_total().unwrap(), usd(5000));
+        assert_eq!(bill.get_settlement_plan().unwrap().balances.len(), 1);
+
+        bill.change_participants(ParticipantChange::Removed { id: bob }).unwrap();
+        assert_eq!(bill.payers()[0].name, "Bob");
+        assert_eq!(bill.get_orderers(), vec!["Bob".to_string()]);
+
+        let mut clash = Bill::new("dinner".to_string(), Currency::USD);
+        clash.add_item(LineItem::shared("wine".to_string(), usd(3000), BTreeMap::from([
+            ("bob".to_string(), Portion::Fixed(usd(1000))),
+            ("Bob".to_string(), Portion::Percent(5000)),
+        ]))).unwrap();
+        let res = clash.change_participants(ParticipantChange::Added { id: bob, participant: Participant::new("Bob".to_string()) });
+        assert!(matches!(res, Err(BillError::Participant(ParticipantError::Clash { .. }))));
     }
 }
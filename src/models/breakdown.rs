@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+use crate::models::bill::Bill;
+use crate::models::currency::Money;
+use crate::models::exchange::Conversion;
+
+/// One orderer's part of a bill: their sub-bill and what they owe.
+///
+/// `rounding` is how many leftover minor units this orderer absorbed while
+/// splitting shared items, adjustments and the total; it is already included
+/// in `owed`.
+#[derive(Debug, Deserialize, Serialize, Clone, Eq, PartialEq)]
+pub struct Share {
+    pub orderer: String,
+    pub owed: Money,
+    pub rounding: u64,
+    pub bill: Bill,
+}
+
+/// Every orderer's share of a bill, plus the value of items nobody ordered.
+///
+/// The owed amounts and `unassigned` always add up to `total`. Someone given
+/// more discount than they spent owes a negative amount.
+#[derive(Debug, Deserialize, Serialize, Clone, Eq, PartialEq)]
+pub struct Breakdown {
+    pub total: Money,
+    pub shares: Vec<Share>,
+    pub unassigned: Money,
+}
+
+/// What one orderer owes, converted into the currency they are settling in.
+#[derive(Debug, Deserialize, Serialize, Clone, Eq, PartialEq)]
+pub struct Settlement {
+    pub orderer: String,
+    pub bill: Bill,
+    pub conversion: Conversion,
+}
@@ -1 +1,329 @@
-pub type Currency = u64;
+use std::fmt;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+
+/// ISO 4217 codes this service knows about, with their number of minor units.
+const CURRENCIES: &[(&str, u32)] = &[
+    ("AED", 2), ("AUD", 2), ("BHD", 3), ("BRL", 2), ("CAD", 2), ("CHF", 2),
+    ("CLP", 0), ("CNY", 2), ("CZK", 2), ("DKK", 2), ("EUR", 2), ("GBP", 2),
+    ("HKD", 2), ("HUF", 2), ("IDR", 2), ("ILS", 2), ("INR", 2), ("ISK", 0),
+    ("JOD", 3), ("JPY", 0), ("KRW", 0), ("KWD", 3), ("MXN", 2), ("MYR", 2),
+    ("NOK", 2), ("NZD", 2), ("OMR", 3), ("PHP", 2), ("PLN", 2), ("RON", 2),
+    ("SAR", 2), ("SEK", 2), ("SGD", 2), ("THB", 2), ("TND", 3), ("TRY", 2),
+    ("TWD", 2), ("UAH", 2), ("USD", 2), ("VND", 0), ("ZAR", 2),
+];
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum MoneyError {
+    UnknownCurrency(String),
+    CurrencyMismatch { expected: Currency, found: Currency },
+    InvalidAmount(String),
+    Overflow,
+}
+
+impl fmt::Display for MoneyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MoneyError::UnknownCurrency(code) => write!(f, "Unknown currency {}", code),
+            MoneyError::CurrencyMismatch { expected, found } => {
+                write!(f, "Expected an amount in {} but found {}", expected, found)
+            },
+            MoneyError::InvalidAmount(amount) => write!(f, "Invalid amount {}", amount),
+            MoneyError::Overflow => write!(f, "Amount out of range"),
+        }
+    }
+}
+
+impl std::error::Error for MoneyError {}
+
+/// An ISO 4217 currency, such as USD or JPY.
+#[derive(Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Currency([u8; 3]);
+
+impl Currency {
+    pub const USD: Currency = Currency(*b"USD");
+    pub const EUR: Currency = Currency(*b"EUR");
+    pub const GBP: Currency = Currency(*b"GBP");
+    pub const JPY: Currency = Currency(*b"JPY");
+    pub const KWD: Currency = Currency(*b"KWD");
+
+    pub fn code(&self) -> &str {
+        std::str::from_utf8(&self.0).unwrap()
+    }
+
+    /// How many digits follow the decimal point, e.g. 2 for USD, 0 for JPY.
+    pub fn minor_units(&self) -> u32 {
+        CURRENCIES.iter()
+            .find(|(code, _)| *code == self.code())
+            .map(|(_, minor_units)| *minor_units)
+            .unwrap()
+    }
+}
+
+impl Default for Currency {
+    fn default() -> Self {
+        Currency::USD
+    }
+}
+
+impl FromStr for Currency {
+    type Err = MoneyError;
+
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        let code = code.to_ascii_uppercase();
+        match CURRENCIES.iter().find(|(known, _)| *known == code) {
+            Some((known, _)) => {
+                let bytes = known.as_bytes();
+                Ok(Currency([bytes[0], bytes[1], bytes[2]]))
+            },
+            None => Err(MoneyError::UnknownCurrency(code)),
+        }
+    }
+}
+
+impl TryFrom<String> for Currency {
+    type Error = MoneyError;
+
+    fn try_from(code: String) -> Result<Self, Self::Error> {
+        code.parse()
+    }
+}
+
+impl From<Currency> for String {
+    fn from(currency: Currency) -> Self {
+        currency.code().to_string()
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+impl fmt::Debug for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+/// An amount of money in a given currency, counted in minor units
+/// (cents for USD, yen for JPY, fils for KWD). Can be negative.
+///
+/// On the wire it is an object with the amount as a decimal string, e.g.
+/// `{"amount": "12.34", "currency": "USD"}`, so it never goes through a float.
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "MoneyWire", into = "MoneyWire")]
+pub struct Money {
+    amount: i64,
+    currency: Currency,
+}
+
+#[derive(Deserialize, Serialize)]
+struct MoneyWire {
+    amount: String,
+    currency: Currency,
+}
+
+impl Money {
+    pub fn new(amount: i64, currency: Currency) -> Self {
+        Self {
+            amount,
+            currency,
+        }
+    }
+
+    pub fn zero(currency: Currency) -> Self {
+        Self::new(0, currency)
+    }
+
+    /// The amount in minor units.
+    pub fn amount(&self) -> i64 {
+        self.amount
+    }
+
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.amount < 0
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.amount == 0
+    }
+
+    /// Fails unless the amount is in `currency`.
+    pub fn expect_currency(&self, currency: Currency) -> Result<(), MoneyError> {
+        match self.currency == currency {
+            true => Ok(()),
+            false => Err(MoneyError::CurrencyMismatch { expected: currency, found: self.currency }),
+        }
+    }
+
+    pub fn checked_add(&self, other: Money) -> Result<Money, MoneyError> {
+        other.expect_currency(self.currency)?;
+        self.amount.checked_add(other.amount)
+            .map(|amount| Money::new(amount, self.currency))
+            .ok_or(MoneyError::Overflow)
+    }
+
+    pub fn checked_sub(&self, other: Money) -> Result<Money, MoneyError> {
+        other.expect_currency(self.currency)?;
+        self.amount.checked_sub(other.amount)
+            .map(|amount| Money::new(amount, self.currency))
+            .ok_or(MoneyError::Overflow)
+    }
+
+    pub fn checked_neg(&self) -> Result<Money, MoneyError> {
+        self.amount.checked_neg()
+            .map(|amount| Money::new(amount, self.currency))
+            .ok_or(MoneyError::Overflow)
+    }
+
+    /// Adds up `amounts`, all of which must be in `currency`.
+    pub fn checked_sum<'a>(
+        currency: Currency,
+        amounts: impl IntoIterator<Item = &'a Money>,
+    ) -> Result<Money, MoneyError> {
+        amounts.into_iter().try_fold(Money::zero(currency), |total, amount| total.checked_add(*amount))
+    }
+
+    /// Parses a decimal amount such as `-12.5` in `currency`. More decimal
+    /// places than the currency has are rejected rather than rounded.
+    pub fn parse(amount: &str, currency: Currency) -> Result<Money, MoneyError> {
+        let invalid = || MoneyError::InvalidAmount(amount.to_string());
+        let (negative, digits) = match amount.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, amount),
+        };
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        let minor_units = currency.minor_units() as usize;
+        let all_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if whole.is_empty() || !all_digits(whole) || !all_digits(fraction)
+            || fraction.len() > minor_units || (digits.contains('.') && fraction.is_empty()) {
+            return Err(invalid());
+        }
+
+        let whole = whole.parse::<i64>().map_err(|_| MoneyError::Overflow)?;
+        let fraction = format!("{:0<width$}", fraction, width = minor_units);
+        let fraction = match fraction.is_empty() {
+            true => 0,
+            false => fraction.parse::<i64>().map_err(|_| invalid())?,
+        };
+        let amount = whole.checked_mul(10_i64.pow(minor_units as u32))
+            .and_then(|whole| whole.checked_add(fraction))
+            .ok_or(MoneyError::Overflow)?;
+        Ok(Money::new(if negative { -amount } else { amount }, currency))
+    }
+
+    /// The amount as a decimal string with the currency's number of decimal
+    /// places, without the currency code.
+    pub fn to_decimal_string(&self) -> String {
+        let minor_units = self.currency.minor_units();
+        let sign = if self.amount < 0 { "-" } else { "" };
+        let amount = self.amount.unsigned_abs();
+        if minor_units == 0 {
+            return format!("{}{}", sign, amount);
+        }
+        let scale = 10_u64.pow(minor_units);
+        format!("{}{}.{:0width$}", sign, amount / scale, amount % scale, width = minor_units as usize)
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.to_decimal_string(), self.currency)
+    }
+}
+
+/// Parses the `Display` form, e.g. `12.34 USD`.
+impl FromStr for Money {
+    type Err = MoneyError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().rsplit_once(' ') {
+            Some((amount, currency)) => Money::parse(amount.trim(), currency.parse()?),
+            None => Err(MoneyError::InvalidAmount(value.to_string())),
+        }
+    }
+}
+
+impl TryFrom<MoneyWire> for Money {
+    type Error = MoneyError;
+
+    fn try_from(wire: MoneyWire) -> Result<Self, Self::Error> {
+        Money::parse(&wire.amount, wire.currency)
+    }
+}
+
+impl From<Money> for MoneyWire {
+    fn from(money: Money) -> Self {
+        MoneyWire {
+            amount: money.to_decimal_string(),
+            currency: money.currency,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_currency() {
+        assert_eq!("usd".parse::<Currency>(), Ok(Currency::USD));
+        assert_eq!(Currency::USD.minor_units(), 2);
+        assert_eq!(Currency::JPY.minor_units(), 0);
+        assert_eq!(Currency::KWD.minor_units(), 3);
+        assert_eq!("XYZ".parse::<Currency>(), Err(MoneyError::UnknownCurrency("XYZ".to_string())));
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Money::new(1234, Currency::USD).to_string(), "12.34 USD");
+        assert_eq!(Money::new(-5, Currency::EUR).to_string(), "-0.05 EUR");
+        assert_eq!(Money::new(1200, Currency::JPY).to_string(), "1200 JPY");
+        assert_eq!(Money::new(1500, Currency::KWD).to_string(), "1.500 KWD");
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("12.34 USD".parse(), Ok(Money::new(1234, Currency::USD)));
+        assert_eq!("12.3 USD".parse(), Ok(Money::new(1230, Currency::USD)));
+        assert_eq!("-7 EUR".parse(), Ok(Money::new(-700, Currency::EUR)));
+        assert_eq!("1200 JPY".parse(), Ok(Money::new(1200, Currency::JPY)));
+        assert_eq!("0.125 KWD".parse(), Ok(Money::new(125, Currency::KWD)));
+        assert_eq!(Money::parse("12.5", Currency::JPY), Err(MoneyError::InvalidAmount("12.5".to_string())));
+        assert_eq!(Money::parse("1.234", Currency::USD), Err(MoneyError::InvalidAmount("1.234".to_string())));
+        assert!(Money::parse("abc", Currency::USD).is_err());
+        assert!(Money::parse("1.", Currency::USD).is_err());
+        assert!(Money::parse("", Currency::USD).is_err());
+        assert_eq!(Money::parse("99999999999999999999", Currency::USD), Err(MoneyError::Overflow));
+    }
+
+    #[test]
+    fn test_checked_arithmetic() {
+        let a = Money::new(100, Currency::USD);
+        assert_eq!(a.checked_add(Money::new(50, Currency::USD)), Ok(Money::new(150, Currency::USD)));
+        assert_eq!(a.checked_sub(Money::new(150, Currency::USD)), Ok(Money::new(-50, Currency::USD)));
+        assert_eq!(
+            a.checked_add(Money::new(1, Currency::EUR)),
+            Err(MoneyError::CurrencyMismatch { expected: Currency::USD, found: Currency::EUR })
+        );
+        assert_eq!(Money::new(i64::MAX, Currency::USD).checked_add(a), Err(MoneyError::Overflow));
+        assert_eq!(Money::checked_sum(Currency::USD, &[a, a, a]), Ok(Money::new(300, Currency::USD)));
+    }
+
+    #[test]
+    fn test_serde() {
+        let money = Money::new(-1234, Currency::USD);
+        let json = serde_json::to_string(&money).unwrap();
+        assert_eq!(json, r#"{"amount":"-12.34","currency":"USD"}"#);
+        assert_eq!(serde_json::from_str::<Money>(&json).unwrap(), money);
+        assert!(serde_json::from_str::<Money>(r#"{"amount":"1.5","currency":"JPY"}"#).is_err());
+        assert!(serde_json::from_str::<Money>(r#"{"amount":"1","currency":"ABC"}"#).is_err());
+    }
+}
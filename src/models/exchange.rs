@@ -0,0 +1,352 @@
+use std::fmt;
+use std::str::FromStr;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::models::currency::{Currency, Money, MoneyError};
+
+/// The most decimal places a rate may have.
+const MAX_RATE_SCALE: u32 = 12;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ExchangeError {
+    NoRate { from: Currency, to: Currency },
+    DuplicateRate { from: Currency, to: Currency },
+    InvalidRate(String),
+    Overflow,
+    /// Working out the amount to convert failed.
+    Money(MoneyError),
+}
+
+impl fmt::Display for ExchangeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExchangeError::NoRate { from, to } => write!(f, "No exchange rate from {} to {}", from, to),
+            ExchangeError::DuplicateRate { from, to } => {
+                write!(f, "More than one exchange rate from {} to {}", from, to)
+            },
+            ExchangeError::InvalidRate(rate) => write!(f, "Invalid exchange rate {}", rate),
+            ExchangeError::Overflow => write!(f, "Amount out of range"),
+            ExchangeError::Money(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for ExchangeError {}
+
+impl From<MoneyError> for ExchangeError {
+    fn from(err: MoneyError) -> Self {
+        ExchangeError::Money(err)
+    }
+}
+
+/// A positive exchange rate, kept as an exact decimal so that converting
+/// never goes through a float. On the wire it is a decimal string such as
+/// `"1.0842"`.
+#[derive(Clone, Copy, Eq, Hash, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Rate {
+    mantissa: u64,
+    scale: u32,
+}
+
+impl Rate {
+    pub const ONE: Rate = Rate { mantissa: 1, scale: 0 };
+}
+
+impl FromStr for Rate {
+    type Err = ExchangeError;
+
+    fn from_str(rate: &str) -> Result<Self, Self::Err> {
+        let invalid = || ExchangeError::InvalidRate(rate.to_string());
+        let (whole, fraction) = rate.split_once('.').unwrap_or((rate, ""));
+        let all_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if whole.is_empty() || !all_digits(whole) || !all_digits(fraction)
+            || (rate.contains('.') && fraction.is_empty()) || fraction.len() > MAX_RATE_SCALE as usize {
+            return Err(invalid());
+        }
+        let mantissa = format!("{}{}", whole, fraction).parse::<u64>().map_err(|_| invalid())?;
+        if mantissa == 0 {
+            return Err(invalid());
+        }
+        Ok(Rate {
+            mantissa,
+            scale: fraction.len() as u32,
+        })
+    }
+}
+
+impl TryFrom<String> for Rate {
+    type Error = ExchangeError;
+
+    fn try_from(rate: String) -> Result<Self, Self::Error> {
+        rate.parse()
+    }
+}
+
+impl fmt::Display for Rate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.scale == 0 {
+            return write!(f, "{}", self.mantissa);
+        }
+        let scale = 10_u64.pow(self.scale);
+        write!(f, "{}.{:0width$}", self.mantissa / scale, self.mantissa % scale, width = self.scale as usize)
+    }
+}
+
+impl fmt::Debug for Rate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl From<Rate> for String {
+    fn from(rate: Rate) -> Self {
+        rate.to_string()
+    }
+}
+
+/// One unit of `from` is worth `rate` units of `to`.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Eq, Hash, PartialEq)]
+pub struct ExchangeRate {
+    pub from: Currency,
+    pub to: Currency,
+    pub rate: Rate,
+}
+
+/// A set of exchange rates, all quoted at `as_of`.
+///
+/// A rate from EUR to USD also converts USD to EUR, by dividing instead of
+/// multiplying, so each pair only needs to be listed once. Listing the same
+/// pair twice, in either direction, is rejected so a conversion never depends
+/// on which of two rates was picked.
+#[derive(Debug, Deserialize, Serialize, Clone, Eq, PartialEq)]
+#[serde(try_from = "RateTableWire", into = "RateTableWire")]
+pub struct RateTable {
+    as_of: DateTime<Utc>,
+    rates: Vec<ExchangeRate>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct RateTableWire {
+    as_of: DateTime<Utc>,
+    rates: Vec<ExchangeRate>,
+}
+
+/// An amount converted into another currency, with the rate that was used
+/// and when that rate was quoted.
+#[derive(Debug, Deserialize, Serialize, Clone, Eq, PartialEq)]
+pub struct Conversion {
+    pub amount: Money,
+    pub converted: Money,
+    pub rate: ExchangeRate,
+    pub as_of: DateTime<Utc>,
+}
+
+impl RateTable {
+    pub fn new(as_of: DateTime<Utc>, rates: Vec<ExchangeRate>) -> Result<Self, ExchangeError> {
+        for (index, rate) in rates.iter().enumerate() {
+            if rate.from == rate.to {
+                return Err(ExchangeError::InvalidRate(format!("{} from {} to itself", rate.rate, rate.from)));
+            }
+            let duplicate = rates[..index].iter().any(|other| {
+                (other.from == rate.from && other.to == rate.to) || (other.from == rate.to && other.to == rate.from)
+            });
+            if duplicate {
+                return Err(ExchangeError::DuplicateRate { from: rate.from, to: rate.to });
+            }
+        }
+        Ok(Self { as_of, rates })
+    }
+
+    /// A table with no rates, which can only convert a currency to itself.
+    pub fn empty() -> Self {
+        Self {
+            as_of: DateTime::UNIX_EPOCH,
+            rates: Vec::new(),
+        }
+    }
+
+    pub fn as_of(&self) -> DateTime<Utc> {
+        self.as_of
+    }
+
+    pub fn rates(&self) -> &[ExchangeRate] {
+        &self.rates
+    }
+
+    /// Converts `amount` into `to`, rounding to the nearest minor unit of
+    /// `to` with ties going to the even neighbour (banker's rounding). The
+    /// whole calculation is done in integers, so the same amount and table
+    /// always give the same result.
+    pub fn convert(&self, amount: Money, to: Currency) -> Result<Conversion, ExchangeError> {
+        let from = amount.currency();
+        let (rate, inverse) = if from == to {
+            (ExchangeRate { from, to, rate: Rate::ONE }, false)
+        } else if let Some(rate) = self.rates.iter().find(|rate| rate.from == from && rate.to == to) {
+            (*rate, false)
+        } else if let Some(rate) = self.rates.iter().find(|rate| rate.from == to && rate.to == from) {
+            (*rate, true)
+        } else {
+            return Err(ExchangeError::NoRate { from, to });
+        };
+
+        // amount / 10^from_units * rate * 10^to_units, or / rate when inverse
+        let pow = |exponent: u32| 10_i128.checked_pow(exponent).ok_or(ExchangeError::Overflow);
+        let mantissa = rate.rate.mantissa as i128;
+        let rate_scale = pow(rate.rate.scale)?;
+        let (numerator, denominator) = match inverse {
+            false => (mantissa, rate_scale),
+            true => (rate_scale, mantissa),
+        };
+        let numerator = (amount.amount() as i128)
+            .checked_mul(numerator)
+            .and_then(|value| value.checked_mul(pow(to.minor_units()).ok()?))
+            .ok_or(ExchangeError::Overflow)?;
+        let denominator = denominator
+            .checked_mul(pow(from.minor_units())?)
+            .ok_or(ExchangeError::Overflow)?;
+        let converted = i64::try_from(divide_half_even(numerator, denominator))
+            .map_err(|_| ExchangeError::Overflow)?;
+
+        Ok(Conversion {
+            amount,
+            converted: Money::new(converted, to),
+            rate,
+            as_of: self.as_of,
+        })
+    }
+}
+
+impl Default for RateTable {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl TryFrom<RateTableWire> for RateTable {
+    type Error = ExchangeError;
+
+    fn try_from(wire: RateTableWire) -> Result<Self, Self::Error> {
+        RateTable::new(wire.as_of, wire.rates)
+    }
+}
+
+impl From<RateTable> for RateTableWire {
+    fn from(table: RateTable) -> Self {
+        RateTableWire {
+            as_of: table.as_of,
+            rates: table.rates,
+        }
+    }
+}
+
+/// Divides with the quotient rounded to the nearest integer and ties to the
+/// even one. `denominator` must be positive.
+fn divide_half_even(numerator: i128, denominator: i128) -> i128 {
+    let quotient = numerator.abs() / denominator;
+    let remainder = numerator.abs() % denominator;
+    let rounded = match (remainder * 2).cmp(&denominator) {
+        std::cmp::Ordering::Greater => quotient + 1,
+        std::cmp::Ordering::Equal if quotient % 2 == 1 => quotient + 1,
+        _ => quotient,
+    };
+    rounded * numerator.signum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> RateTable {
+        RateTable::new(
+            "2024-03-01T12:00:00Z".parse().unwrap(),
+            vec![
+                ExchangeRate { from: Currency::EUR, to: Currency::USD, rate: "1.0842".parse().unwrap() },
+                ExchangeRate { from: Currency::USD, to: Currency::JPY, rate: "150.12".parse().unwrap() },
+            ],
+        ).unwrap()
+    }
+
+    #[test]
+    fn test_parse_rate() {
+        assert_eq!("1.0842".parse::<Rate>().unwrap().to_string(), "1.0842");
+        assert_eq!("150".parse::<Rate>().unwrap().to_string(), "150");
+        assert_eq!("0.000001".parse::<Rate>().unwrap().to_string(), "0.000001");
+        assert!("0".parse::<Rate>().is_err());
+        assert!("-1.2".parse::<Rate>().is_err());
+        assert!("1.".parse::<Rate>().is_err());
+        assert!("".parse::<Rate>().is_err());
+        assert!("1.0000000000001".parse::<Rate>().is_err());
+    }
+
+    #[test]
+    fn test_convert() {
+        let conversion = table().convert(Money::new(4_250, Currency::EUR), Currency::USD).unwrap();
+        // 42.50 * 1.0842 = 46.0785
+        assert_eq!(conversion.converted, Money::new(4_608, Currency::USD));
+        assert_eq!(conversion.rate.rate.to_string(), "1.0842");
+        assert_eq!(conversion.as_of, table().as_of());
+
+        let conversion = table().convert(Money::new(1_000, Currency::USD), Currency::JPY).unwrap();
+        assert_eq!(conversion.converted, Money::new(1_501, Currency::JPY));
+    }
+
+    #[test]
+    fn test_convert_inverse() {
+        // 100.00 / 1.0842 = 92.233905...
+        let conversion = table().convert(Money::new(10_000, Currency::USD), Currency::EUR).unwrap();
+        assert_eq!(conversion.converted, Money::new(9_223, Currency::EUR));
+        assert_eq!(conversion.rate.from, Currency::EUR);
+    }
+
+    #[test]
+    fn test_convert_rounds_half_even() {
+        let table = RateTable::new(
+            DateTime::UNIX_EPOCH,
+            vec![ExchangeRate { from: Currency::EUR, to: Currency::USD, rate: "0.5".parse().unwrap() }],
+        ).unwrap();
+        let convert = |amount| table.convert(Money::new(amount, Currency::EUR), Currency::USD).unwrap().converted.amount();
+        assert_eq!(convert(1), 0);
+        assert_eq!(convert(3), 2);
+        assert_eq!(convert(5), 2);
+        assert_eq!(convert(-3), -2);
+        assert_eq!(convert(-5), -2);
+    }
+
+    #[test]
+    fn test_convert_same_currency() {
+        let conversion = RateTable::empty().convert(Money::new(123, Currency::USD), Currency::USD).unwrap();
+        assert_eq!(conversion.converted, Money::new(123, Currency::USD));
+        assert_eq!(conversion.rate.rate, Rate::ONE);
+    }
+
+    #[test]
+    fn test_convert_without_rate() {
+        assert_eq!(
+            table().convert(Money::new(100, Currency::EUR), Currency::JPY),
+            Err(ExchangeError::NoRate { from: Currency::EUR, to: Currency::JPY })
+        );
+    }
+
+    #[test]
+    fn test_duplicate_rates() {
+        let rate = |from, to| ExchangeRate { from, to, rate: Rate::ONE };
+        assert_eq!(
+            RateTable::new(DateTime::UNIX_EPOCH, vec![rate(Currency::EUR, Currency::USD), rate(Currency::USD, Currency::EUR)]),
+            Err(ExchangeError::DuplicateRate { from: Currency::USD, to: Currency::EUR })
+        );
+        assert!(RateTable::new(DateTime::UNIX_EPOCH, vec![rate(Currency::USD, Currency::USD)]).is_err());
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let table = serde_json::from_str::<RateTable>(
+            r#"{"as_of": "2024-03-01T12:00:00Z", "rates": [{"from": "EUR", "to": "USD", "rate": "1.0842"}]}"#
+        ).unwrap();
+        assert_eq!(table.rates().len(), 1);
+        assert_eq!(serde_json::to_value(&table).unwrap()["rates"][0]["rate"], "1.0842");
+        assert!(serde_json::from_str::<RateTable>(
+            r#"{"as_of": "2024-03-01T12:00:00Z", "rates": [{"from": "EUR", "to": "USD", "rate": 1.08}]}"#
+        ).is_err());
+    }
+}
@@ -0,0 +1,222 @@
+//! People who split many bills together, and where they stand across all of
+//! them.
+use std::collections::{BTreeMap, HashSet};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::models::currency::Currency;
+use crate::models::participant::{ParticipantChange, ParticipantError, Participants};
+use crate::models::payment::Payment;
+use crate::models::settlement::{minimal_transfers, Balance, Ledger, SettlementPlan, Transfer};
+use crate::models::validate::{finish, Report, ValidationError, MAX_GROUP_BILLS, MAX_ORDERERS};
+
+/// A group of people and the bills they split, oldest first. A bill can be
+/// in any number of groups, but only once in each.
+#[derive(Debug, Deserialize, Serialize, Clone, Eq, PartialEq)]
+pub struct Group {
+    pub name: String,
+    #[serde(default)]
+    pub members: Vec<String>,
+    #[serde(default)]
+    pub bills: Vec<Uuid>,
+    /// Who the group's balances are kept for. People on its bills are
+    /// matched to these by name or alias.
+    #[serde(default)]
+    pub participants: Participants,
+}
+
+impl Group {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            members: vec![],
+            bills: vec![],
+            participants: Participants::new(),
+        }
+    }
+
+    /// Replaces every member that names a participant with the
+    /// participant's id, dropping anyone that leaves listed twice.
+    pub fn resolve_participants(&mut self) {
+        if self.participants.is_empty() {
+            return;
+        }
+        let mut members = Vec::with_capacity(self.members.len());
+        for member in &self.members {
+            let member = self.participants.reference(member);
+            if !members.contains(&member) {
+                members.push(member);
+            }
+        }
+        self.members = members;
+    }
+
+    /// Registers, updates, removes or merges a participant, then resolves
+    /// the members against the participants that are left.
+    pub fn change_participants(&mut self, change: ParticipantChange) -> Result<(), ParticipantError> {
+        if let Some((id, name)) = self.participants.apply(change)? {
+            let id = id.to_string();
+            for member in &mut self.members {
+                if *member == id {
+                    *member = name.clone();
+                }
+            }
+        }
+        self.resolve_participants();
+        Ok(())
+    }
+
+    /// Fails with every field that breaks the limits in
+    /// `crate::models::validate`, or names a member or bill twice.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let mut errors = vec![];
+        let mut report = Report::new(&mut errors);
+        report.check_name("name", &self.name);
+        self.participants.check(&mut report);
+
+        report.check_count("members", self.members.len(), MAX_ORDERERS);
+        let mut members = HashSet::new();
+        for (index, member) in self.members.iter().enumerate() {
+            let field = format!("members.{}", index);
+            report.check_name(&field, member);
+            if !members.insert(member) {
+                report.push(&field, "is already a member");
+            }
+        }
+
+        report.check_count("bills", self.bills.len(), MAX_GROUP_BILLS);
+        let mut bills = HashSet::new();
+        for (index, bill) in self.bills.iter().enumerate() {
+            if !bills.insert(bill) {
+                report.push(&format!("bills.{}", index), "is already in the group");
+            }
+        }
+        finish(errors)
+    }
+}
+
+/// A stored group. The data layer hands out `id`; clients only ever send
+/// the `Group`.
+#[derive(Debug, Deserialize, Serialize, Clone, Eq, PartialEq)]
+pub struct GroupWithId {
+    pub id: Uuid,
+    pub group: Group,
+}
+
+/// Where everyone stands across a group's bills in one currency, and the
+/// fewest transfers that settle it all.
+#[derive(Debug, Deserialize, Serialize, Clone, Eq, PartialEq)]
+pub struct BalanceSheet {
+    pub currency: Currency,
+    /// Every member, and anyone else on the bills, sorted by name.
+    pub balances: Vec<Balance>,
+    pub transfers: Vec<Transfer>,
+}
+
+/// Adds up the settlement plans of a group's bills and the payments made
+/// towards the group itself, one sheet per currency, sorted by currency
+/// code. Bills in different currencies are never converted into each other,
+/// so their debts are settled separately.
+pub fn balance_sheets(members: &[String], plans: &[SettlementPlan], payments: &[Payment]) -> Vec<BalanceSheet> {
+    let mut ledgers: BTreeMap<Currency, Ledger> = BTreeMap::new();
+    let currencies = plans.iter().map(|plan| plan.total.currency())
+        .chain(payments.iter().map(|payment| payment.amount.currency()));
+    for currency in currencies {
+        ledgers.entry(currency).or_insert_with(|| {
+            let mut ledger = Ledger::new(currency);
+            for member in members {
+                ledger.add_person(member);
+            }
+            ledger
+        });
+    }
+    for plan in plans {
+        if let Some(ledger) = ledgers.get_mut(&plan.total.currency()) {
+            for balance in &plan.balances {
+                ledger.add_balance(balance);
+            }
+        }
+    }
+    for payment in payments {
+        if let Some(ledger) = ledgers.get_mut(&payment.amount.currency()) {
+            ledger.add_payment(payment);
+        }
+    }
+
+    ledgers.into_values().map(|ledger| {
+        let balances = ledger.balances();
+        BalanceSheet {
+            currency: ledger.currency(),
+            transfers: minimal_transfers(&balances),
+            balances,
+        }
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use crate::models::bill::Bill;
+    use crate::models::currency::{Money, MoneyError};
+    use crate::models::item::LineItem;
+    use crate::models::payer::Payer;
+
+    fn bill(currency: Currency, orderers: &[(&str, i64)], payer: &str) -> Bill {
+        let mut bill = Bill::new("dinner".to_string(), currency);
+        let mut total = 0;
+        for (orderer, price) in orderers {
+            let item = LineItem::from("dish".to_string(), Money::new(*price, currency), Some(orderer.to_string()));
+            bill.add_item(item).unwrap();
+            total += price;
+        }
+        bill.set_payers(vec![Payer::new(payer.to_string(), Money::new(total, currency))]).unwrap();
+        bill
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut group = Group::new("team".to_string());
+        group.members = vec!["alice".to_string(), "bob".to_string(), "alice".to_string()];
+        let id = Uuid::new_v4();
+        group.bills = vec![id, Uuid::new_v4(), id];
+        let paths = group.validate().unwrap_err().errors.into_iter().map(|error| error.path).collect::<Vec<String>>();
+        assert_eq!(paths, vec!["members.2", "bills.2"]);
+    }
+
+    #[test]
+    fn test_balance_sheets() {
+        let members = vec!["alice".to_string(), "bob".to_string(), "carol".to_string(), "dave".to_string()];
+        let plans = [
+            bill(Currency::USD, &[("alice", 1000), ("bob", 2000)], "alice"),
+            bill(Currency::USD, &[("bob", 500), ("carol", 1500)], "carol"),
+            bill(Currency::EUR, &[("alice", 700)], "bob"),
+        ].iter().map(Bill::get_settlement_plan).collect::<Result<Vec<SettlementPlan>, MoneyError>>().unwrap();
+        let sheets = balance_sheets(&members, &plans, &[]);
+        assert_eq!(sheets.iter().map(|sheet| sheet.currency).collect::<Vec<Currency>>(), vec![Currency::EUR, Currency::USD]);
+
+        let usd = &sheets[1];
+        let nets = usd.balances.iter()
+            .map(|balance| (balance.person.as_str(), balance.net.amount()))
+            .collect::<Vec<(&str, i64)>>();
+        assert_eq!(nets, vec![("alice", 2000), ("bob", -2500), ("carol", 500), ("dave", 0)]);
+        let transfers = usd.transfers.iter()
+            .map(|transfer| (transfer.from.as_str(), transfer.to.as_str(), transfer.amount.amount()))
+            .collect::<Vec<(&str, &str, i64)>>();
+        assert_eq!(transfers, vec![("bob", "alice", 2000), ("bob", "carol", 500)]);
+
+        assert_eq!(sheets[0].transfers.len(), 1);
+        assert!(balance_sheets(&members, &[], &[]).is_empty());
+
+        // bob pays alice back in full, and carol half of what he owes her
+        let date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        let payments = vec![
+            Payment::new("bob".to_string(), "alice".to_string(), Money::new(2000, Currency::USD), date),
+            Payment::new("bob".to_string(), "carol".to_string(), Money::new(250, Currency::USD), date),
+        ];
+        let sheets = balance_sheets(&members, &plans, &payments);
+        let transfers = sheets[1].transfers.iter()
+            .map(|transfer| (transfer.from.as_str(), transfer.to.as_str(), transfer.amount.amount()))
+            .collect::<Vec<(&str, &str, i64)>>();
+        assert_eq!(transfers, vec![("bob", "carol", 250)]);
+    }
+}
@@ -1,55 +1,363 @@
+use std::collections::BTreeMap;
 use serde::{Deserialize, Serialize};
-use crate::models::currency::Currency;
+use crate::models::currency::{Currency, Money, MoneyError};
+use crate::models::split::{split, Allocation, RoundingPolicy};
+use crate::models::validate::{finish, Report, ValidationError, MAX_ORDERERS};
 
+/// How much of an item one of its orderers pays for.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, Eq, Hash, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Portion {
+    /// An equal share of whatever is left after fixed amounts and percentages.
+    #[default]
+    Equal,
+    /// A weighted share of whatever is left after fixed amounts and percentages.
+    Weight(u32),
+    /// A fixed amount of the price.
+    Fixed(Money),
+    /// A percentage of the price, in hundredths of a percent (2500 = 25%).
+    Percent(u32),
+}
+
+impl Portion {
+    /// One portion for an orderer listed twice, if the two add up: equal
+    /// shares and weights add up as weights, fixed amounts and percentages
+    /// only with their own kind.
+    pub fn combine(self, other: Portion) -> Option<Portion> {
+        let weight = |portion| match portion {
+            Portion::Equal => Some(1u32),
+            Portion::Weight(weight) => Some(weight),
+            Portion::Fixed(_) | Portion::Percent(_) => None,
+        };
+        match (self, other) {
+            (Portion::Fixed(a), Portion::Fixed(b)) => a.checked_add(b).ok().map(Portion::Fixed),
+            (Portion::Percent(a), Portion::Percent(b)) => a.checked_add(b).map(Portion::Percent),
+            _ => weight(self)?.checked_add(weight(other)?).map(Portion::Weight),
+        }
+    }
+}
+
+/// An item on a bill. Items from before they could be shared name a single
+/// `orderer` instead of `orderers`; they are still read, as that orderer
+/// taking an equal share.
 #[derive(Debug, Deserialize, Serialize, Clone, Eq, Hash, PartialEq)]
+#[serde(from = "LineItemWire")]
 pub struct LineItem {
     pub name: String,
-    pub price: Currency,
-    pub orderer: Option<String>,
+    pub price: Money,
+    pub orderers: BTreeMap<String, Portion>,
+}
+
+#[derive(Deserialize)]
+struct LineItemWire {
+    name: String,
+    price: Money,
+    #[serde(default)]
+    orderers: BTreeMap<String, Portion>,
+    #[serde(default)]
+    orderer: Option<String>,
+}
+
+impl From<LineItemWire> for LineItem {
+    fn from(wire: LineItemWire) -> Self {
+        let mut orderers = wire.orderers;
+        if let Some(orderer) = wire.orderer {
+            orderers.entry(orderer).or_insert(Portion::Equal);
+        }
+        Self {
+            name: wire.name,
+            price: wire.price,
+            orderers,
+        }
+    }
 }
 
 impl LineItem {
     pub fn new() -> Self {
         Self {
             name: "".to_string(),
-            price: 0,
-            orderer: None,
+            price: Money::zero(Currency::default()),
+            orderers: BTreeMap::new(),
         }
     }
 
     pub fn from(
         name: String,
-        price: u64,
+        price: Money,
         orderer: Option<String>,
+    ) -> Self {
+        let mut orderers = BTreeMap::new();
+        if let Some(orderer) = orderer {
+            orderers.insert(orderer, Portion::Equal);
+        }
+        Self {
+            name,
+            price,
+            orderers,
+        }
+    }
+
+    pub fn shared(
+        name: String,
+        price: Money,
+        orderers: BTreeMap<String, Portion>,
     ) -> Self {
         Self {
             name,
             price,
-            orderer,
+            orderers,
         }
     }
+
+    pub fn is_ordered_by(&self, orderer: &str) -> bool {
+        self.orderers.contains_key(orderer)
+    }
+
+    /// Fails unless the price and every fixed portion are in `currency`.
+    pub fn expect_currency(&self, currency: Currency) -> Result<(), MoneyError> {
+        self.price.expect_currency(currency)?;
+        for portion in self.orderers.values() {
+            if let Portion::Fixed(amount) = portion {
+                amount.expect_currency(currency)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Fails with every field that breaks the limits in
+    /// `crate::models::validate`.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let mut errors = vec![];
+        self.check(&mut Report::new(&mut errors));
+        finish(errors)
+    }
+
+    pub(crate) fn check(&self, report: &mut Report) {
+        report.check_name("name", &self.name);
+        report.check_amount("price", self.price);
+        report.check_count("orderers", self.orderers.len(), MAX_ORDERERS);
+        let mut orderers = report.nested("orderers");
+        for (orderer, portion) in &self.orderers {
+            orderers.check_name(orderer, orderer);
+            match portion {
+                Portion::Percent(basis_points) if *basis_points > 10_000 => {
+                    orderers.push(orderer, "percent must be at most 10000 (100%)");
+                },
+                Portion::Fixed(amount) => orderers.check_amount(orderer, *amount),
+                _ => {},
+            }
+        }
+    }
+
+    /// Splits the price between the item's orderers, or evenly between
+    /// `everyone` if nobody in particular ordered it.
+    ///
+    /// Fixed amounts are taken first, then percentages of the price, and the
+    /// rest is divided by weight between the `Equal` and `Weight` orderers.
+    /// If nobody takes the rest it goes to the fixed and percentage orderers
+    /// in proportion, and if those ask for more than the price they are
+    /// scaled down, so the parts always add up to exactly the price.
+    /// Returns the orderers in name order alongside their parts. Fails if a
+    /// part is too large to hold.
+    pub fn allocate(&self, everyone: &[String], policy: RoundingPolicy) -> Result<(Vec<String>, Allocation), MoneyError> {
+        if self.orderers.is_empty() {
+            let allocation = split(self.price, &vec![1; everyone.len()], policy)?;
+            return Ok((everyone.to_vec(), allocation));
+        }
+
+        let orderers = self.orderers.keys().cloned().collect::<Vec<String>>();
+        let price = self.price.amount() as i128;
+        let claimed = self.orderers.values().map(|portion| match portion {
+            Portion::Fixed(amount) => amount.amount() as i128,
+            Portion::Percent(basis_points) => price * *basis_points as i128 / 10_000,
+            Portion::Equal | Portion::Weight(_) => 0,
+        }).collect::<Vec<i128>>();
+        let claimed_weights = claimed.iter()
+            .map(|amount| amount.unsigned_abs() as u64)
+            .collect::<Vec<u64>>();
+        let claimed_total = claimed.iter().sum::<i128>();
+        if claimed_total.abs() > price.abs() {
+            return Ok((orderers, split(self.price, &claimed_weights, policy)?));
+        }
+
+        let rest = i64::try_from(price - claimed_total).map_err(|_| MoneyError::Overflow)?;
+        let rest = Money::new(rest, self.price.currency());
+        let weights = self.orderers.values().map(|portion| match portion {
+            Portion::Equal => 1,
+            Portion::Weight(weight) => *weight as u64,
+            Portion::Fixed(_) | Portion::Percent(_) => 0,
+        }).collect::<Vec<u64>>();
+        let mut allocation = if weights.iter().any(|weight| *weight > 0) {
+            split(rest, &weights, policy)?
+        } else {
+            split(rest, &claimed_weights, policy)?
+        };
+        for (part, amount) in allocation.parts.iter_mut().zip(&claimed) {
+            let amount = i64::try_from(*amount).map_err(|_| MoneyError::Overflow)?;
+            *part = part.checked_add(Money::new(amount, part.currency()))?;
+        }
+        Ok((orderers, allocation))
+    }
+}
+
+impl Default for LineItem {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn usd(amount: i64) -> Money {
+        Money::new(amount, Currency::USD)
+    }
+
+    fn amounts(allocation: &Allocation) -> Vec<i64> {
+        allocation.parts.iter().map(|part| part.amount()).collect()
+    }
+
+    fn portions(orderers: &[(&str, Portion)]) -> BTreeMap<String, Portion> {
+        orderers.iter().map(|(name, portion)| (name.to_string(), *portion)).collect()
+    }
+
     #[test]
     fn test_new() {
         let item = LineItem::new();
         assert_eq!(item.name, "");
-        assert_eq!(item.price, 0);
+        assert!(item.price.is_zero());
     }
 
     #[test]
     fn test_from() {
         let item = LineItem::from(
             "test".to_string(),
-            100,
+            usd(100),
             Some("test".to_string()),
         );
         assert_eq!(item.name, "test");
-        assert_eq!(item.price, 100);
-        assert_eq!(item.orderer, Some("test".to_string()));
+        assert_eq!(item.price, usd(100));
+        assert_eq!(item.orderers, portions(&[("test", Portion::Equal)]));
+        assert!(item.is_ordered_by("test"));
+    }
+
+    #[test]
+    fn test_deserialize_single_orderer() {
+        let price = r#"{"amount": "1.00", "currency": "USD"}"#;
+        let old = format!(r#"{{"name": "test", "price": {}, "orderer": "test"}}"#, price);
+        let item: LineItem = serde_json::from_str(&old).unwrap();
+        assert_eq!(item, LineItem::from("test".to_string(), usd(100), Some("test".to_string())));
+
+        let unordered = format!(r#"{{"name": "test", "price": {}, "orderer": null}}"#, price);
+        let item: LineItem = serde_json::from_str(&unordered).unwrap();
+        assert!(item.orderers.is_empty());
+
+        let item = LineItem::shared("test".to_string(), usd(100), portions(&[("a", Portion::Weight(2))]));
+        let json = serde_json::to_string(&item).unwrap();
+        assert!(!json.contains("\"orderer\""));
+        assert_eq!(serde_json::from_str::<LineItem>(&json).unwrap(), item);
+    }
+
+    #[test]
+    fn test_combine() {
+        assert_eq!(Portion::Equal.combine(Portion::Equal), Some(Portion::Weight(2)));
+        assert_eq!(Portion::Weight(3).combine(Portion::Equal), Some(Portion::Weight(4)));
+        assert_eq!(Portion::Fixed(usd(100)).combine(Portion::Fixed(usd(50))), Some(Portion::Fixed(usd(150))));
+        assert_eq!(Portion::Percent(2500).combine(Portion::Percent(500)), Some(Portion::Percent(3000)));
+        assert_eq!(Portion::Fixed(usd(100)).combine(Portion::Percent(500)), None);
+        assert_eq!(Portion::Equal.combine(Portion::Fixed(usd(100))), None);
+    }
+
+    #[test]
+    fn test_expect_currency() {
+        let item = LineItem::shared(
+            "test".to_string(),
+            usd(100),
+            portions(&[("a", Portion::Fixed(Money::new(10, Currency::EUR)))]),
+        );
+        assert_eq!(
+            item.expect_currency(Currency::USD),
+            Err(MoneyError::CurrencyMismatch { expected: Currency::USD, found: Currency::EUR })
+        );
+        assert!(LineItem::from("test".to_string(), usd(100), None).expect_currency(Currency::USD).is_ok());
+    }
+
+    #[test]
+    fn test_allocate_equal() {
+        let item = LineItem::shared(
+            "test".to_string(),
+            usd(100),
+            portions(&[("a", Portion::Equal), ("b", Portion::Equal), ("c", Portion::Equal)]),
+        );
+        let (orderers, allocation) = item.allocate(&[], RoundingPolicy::LargestRemainder).unwrap();
+        assert_eq!(orderers, vec!["a", "b", "c"]);
+        assert_eq!(amounts(&allocation), vec![34, 33, 33]);
+        assert_eq!(allocation.rounding, vec![1, 0, 0]);
+    }
+
+    #[test]
+    fn test_allocate_mixed() {
+        let item = LineItem::shared(
+            "test".to_string(),
+            usd(1000),
+            portions(&[
+                ("a", Portion::Fixed(usd(100))),
+                ("b", Portion::Percent(2500)),
+                ("c", Portion::Weight(2)),
+                ("d", Portion::Weight(1)),
+            ]),
+        );
+        let (_, allocation) = item.allocate(&[], RoundingPolicy::LargestRemainder).unwrap();
+        assert_eq!(amounts(&allocation), vec![100, 250, 433, 217]);
+    }
+
+    #[test]
+    fn test_allocate_without_rest_takers() {
+        let item = LineItem::shared(
+            "test".to_string(),
+            usd(1000),
+            portions(&[("a", Portion::Fixed(usd(200))), ("b", Portion::Percent(4000))]),
+        );
+        let (_, allocation) = item.allocate(&[], RoundingPolicy::LargestRemainder).unwrap();
+        assert_eq!(amounts(&allocation), vec![333, 667]);
+
+        let item = LineItem::shared(
+            "test".to_string(),
+            usd(100),
+            portions(&[("a", Portion::Fixed(usd(150))), ("b", Portion::Fixed(usd(50)))]),
+        );
+        let (_, allocation) = item.allocate(&[], RoundingPolicy::LargestRemainder).unwrap();
+        assert_eq!(amounts(&allocation), vec![75, 25]);
+    }
+
+    #[test]
+    fn test_allocate_unordered() {
+        let item = LineItem::from("test".to_string(), usd(100), None);
+        let everyone = vec!["a".to_string(), "b".to_string()];
+        let (orderers, allocation) = item.allocate(&everyone, RoundingPolicy::LargestRemainder).unwrap();
+        assert_eq!(orderers, everyone);
+        assert_eq!(amounts(&allocation), vec![50, 50]);
+    }
+
+    #[test]
+    fn test_allocate_refund() {
+        let item = LineItem::shared(
+            "refund".to_string(),
+            usd(-100),
+            portions(&[("a", Portion::Equal), ("b", Portion::Percent(2500))]),
+        );
+        let (_, allocation) = item.allocate(&[], RoundingPolicy::LargestRemainder).unwrap();
+        assert_eq!(amounts(&allocation), vec![-75, -25]);
+    }
+
+    #[test]
+    fn test_allocate_overflow() {
+        let item = LineItem::shared(
+            "voucher".to_string(),
+            usd(100),
+            portions(&[("a", Portion::Fixed(usd(i64::MAX))), ("b", Portion::Fixed(usd(-i64::MAX)))]),
+        );
+        assert_eq!(item.allocate(&[], RoundingPolicy::LargestRemainder), Err(MoneyError::Overflow));
     }
 }
@@ -1,3 +1,15 @@
 pub mod bill;
 pub mod item;
 pub mod currency;
+pub mod breakdown;
+pub mod split;
+pub mod adjustment;
+pub mod exchange;
+pub mod group;
+pub mod participant;
+pub mod patch;
+pub mod payer;
+pub mod payment;
+pub mod settlement;
+pub mod user;
+pub mod validate;
@@ -0,0 +1,265 @@
+//! People on a bill or in a group, so that "Bob", "bob" and "Bob " are one
+//! person. Once someone is registered, items, adjustments, payers and group
+//! members refer to them by id; names that match nobody are kept as they
+//! are.
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::models::validate::{Report, MAX_ALIASES, MAX_PARTICIPANTS};
+
+#[derive(Debug, Deserialize, Serialize, Clone, Eq, Hash, PartialEq)]
+pub struct Participant {
+    /// How to show them.
+    pub name: String,
+    /// Other names they go by. Names and aliases are matched ignoring case
+    /// and surrounding whitespace.
+    #[serde(default)]
+    pub aliases: Vec<String>,
+}
+
+impl Participant {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            aliases: vec![],
+        }
+    }
+
+    /// The name, then every alias.
+    fn names(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.name.as_str()).chain(self.aliases.iter().map(String::as_str))
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ParticipantError {
+    NotFound,
+    /// A participant cannot be merged into themselves.
+    SelfMerge,
+    /// Two orderers of one item turned out to be the same participant, with
+    /// portions that do not add up, e.g. a fixed amount and a percentage.
+    Clash { item_id: u16, orderer: String },
+}
+
+impl fmt::Display for ParticipantError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParticipantError::NotFound => write!(f, "Participant not found"),
+            ParticipantError::SelfMerge => write!(f, "A participant cannot be merged into themselves"),
+            ParticipantError::Clash { item_id, orderer } => {
+                write!(f, "Item {} lists {} twice with portions that cannot be added up", item_id, orderer)
+            },
+        }
+    }
+}
+
+impl std::error::Error for ParticipantError {}
+
+/// A change to a set of participants. A new participant's id is part of
+/// the change, so applying the same change again gives the same result.
+#[derive(Debug, Deserialize, Serialize, Clone, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ParticipantChange {
+    Added { id: Uuid, participant: Participant },
+    Updated { id: Uuid, participant: Participant },
+    /// Whatever referred to them goes back to their name.
+    Removed { id: Uuid },
+    /// `from` becomes an alias of `into`, along with their name, aliases
+    /// and id, so whatever referred to `from` now refers to `into`.
+    Merged { from: Uuid, into: Uuid },
+}
+
+/// Registered participants by id.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, Eq, PartialEq)]
+#[serde(transparent)]
+pub struct Participants(BTreeMap<Uuid, Participant>);
+
+/// What names and aliases are compared by.
+pub(crate) fn normalize(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+impl Participants {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<&Participant> {
+        self.0.get(&id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Uuid, &Participant)> {
+        self.0.iter()
+    }
+
+    /// The participant `reference` names: by id, or by name or alias.
+    pub fn resolve(&self, reference: &str) -> Option<Uuid> {
+        if let Ok(id) = Uuid::parse_str(reference.trim()) {
+            if self.0.contains_key(&id) {
+                return Some(id);
+            }
+        }
+        let key = normalize(reference);
+        self.0.iter()
+            .find(|(_, participant)| participant.names().any(|name| normalize(name) == key))
+            .map(|(id, _)| *id)
+    }
+
+    /// What to store for `reference`: the id of the participant it names,
+    /// or `reference` itself if it names nobody.
+    pub fn reference(&self, reference: &str) -> String {
+        self.resolve(reference).map_or_else(|| reference.to_string(), |id| id.to_string())
+    }
+
+    /// How to show `reference`: the name of the participant it is the id
+    /// of, or `reference` itself.
+    pub fn name<'a>(&'a self, reference: &'a str) -> &'a str {
+        match Uuid::parse_str(reference).ok().and_then(|id| self.get(id)) {
+            Some(participant) => &participant.name,
+            None => reference,
+        }
+    }
+
+    /// `reference`, made in `other`, as it would be made here: a participant
+    /// of `other` is matched here by their name or aliases, and keeps their
+    /// name if nobody here matches.
+    pub fn translate(&self, other: &Participants, reference: &str) -> String {
+        let participant = Uuid::parse_str(reference).ok().and_then(|id| other.get(id));
+        match participant {
+            Some(participant) => participant.names()
+                .find_map(|name| self.resolve(name))
+                .map_or_else(|| participant.name.clone(), |id| id.to_string()),
+            None => self.reference(reference),
+        }
+    }
+
+    /// Applies `change` to the participants alone. Returns the id and name
+    /// of anyone removed, for the caller to put back wherever they were
+    /// referred to.
+    pub fn apply(&mut self, change: ParticipantChange) -> Result<Option<(Uuid, String)>, ParticipantError> {
+        match change {
+            ParticipantChange::Added { id, participant } => {
+                self.0.insert(id, participant);
+            },
+            ParticipantChange::Updated { id, participant } => {
+                *self.0.get_mut(&id).ok_or(ParticipantError::NotFound)? = participant;
+            },
+            ParticipantChange::Removed { id } => {
+                let participant = self.0.remove(&id).ok_or(ParticipantError::NotFound)?;
+                return Ok(Some((id, participant.name)));
+            },
+            ParticipantChange::Merged { from, into } => {
+                if from == into {
+                    return Err(ParticipantError::SelfMerge);
+                }
+                if !self.0.contains_key(&into) {
+                    return Err(ParticipantError::NotFound);
+                }
+                let merged = self.0.remove(&from).ok_or(ParticipantError::NotFound)?;
+                let target = self.0.get_mut(&into).expect("checked above");
+                let names = merged.names().map(String::from).chain(std::iter::once(from.to_string())).collect::<Vec<String>>();
+                for name in names {
+                    if !target.names().any(|other| normalize(other) == normalize(&name)) {
+                        target.aliases.push(name);
+                    }
+                }
+            },
+        }
+        Ok(None)
+    }
+
+    /// Checks every participant under `participants`, including that no
+    /// two of them go by the same name.
+    pub(crate) fn check(&self, report: &mut Report) {
+        report.check_count("participants", self.0.len(), MAX_PARTICIPANTS);
+        let mut report = report.nested("participants");
+        let mut taken: HashMap<String, Uuid> = HashMap::new();
+        for (id, participant) in &self.0 {
+            let mut report = report.nested(&id.to_string());
+            report.check_count("aliases", participant.aliases.len(), MAX_ALIASES);
+            let fields = std::iter::once("name".to_string())
+                .chain((0..participant.aliases.len()).map(|index| format!("aliases.{}", index)));
+            for (field, name) in fields.zip(participant.names()) {
+                report.check_name(&field, name);
+                if *taken.entry(normalize(name)).or_insert(*id) != *id {
+                    report.push(&field, "is already taken by another participant");
+                }
+            }
+        }
+    }
+}
+
+impl FromIterator<(Uuid, Participant)> for Participants {
+    fn from_iter<I: IntoIterator<Item = (Uuid, Participant)>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::validate::finish;
+
+    fn registry(names: &[(&str, &[&str])]) -> (Participants, Vec<Uuid>) {
+        let mut participants = Participants::new();
+        let mut ids = vec![];
+        for (name, aliases) in names {
+            let id = Uuid::new_v4();
+            let mut participant = Participant::new(name.to_string());
+            participant.aliases = aliases.iter().map(|alias| alias.to_string()).collect();
+            participants.apply(ParticipantChange::Added { id, participant }).unwrap();
+            ids.push(id);
+        }
+        (participants, ids)
+    }
+
+    #[test]
+    fn test_resolve() {
+        let (participants, ids) = registry(&[("Bob", &["Robert"]), ("alice", &[])]);
+        for reference in ["Bob", "bob", "Bob ", " ROBERT", &ids[0].to_string()] {
+            assert_eq!(participants.resolve(reference), Some(ids[0]));
+        }
+        assert_eq!(participants.reference("Alice"), ids[1].to_string());
+        assert_eq!(participants.reference("carol"), "carol");
+        assert_eq!(participants.name(&ids[0].to_string()), "Bob");
+        assert_eq!(participants.name("carol"), "carol");
+
+        let (group, group_ids) = registry(&[("robert", &[])]);
+        assert_eq!(group.translate(&participants, &ids[0].to_string()), group_ids[0].to_string());
+        assert_eq!(group.translate(&participants, &ids[1].to_string()), "alice");
+        assert_eq!(group.translate(&participants, "Robert"), group_ids[0].to_string());
+    }
+
+    #[test]
+    fn test_merge() {
+        let (mut participants, ids) = registry(&[("Bob", &[]), ("bobby", &["Bob"])]);
+        let mut errors = vec![];
+        participants.check(&mut Report::new(&mut errors));
+        // whichever of the two comes second in id order has "Bob" taken
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].message.contains("already taken"));
+
+        let merge = |from, into| ParticipantChange::Merged { from, into };
+        assert_eq!(participants.apply(merge(ids[0], ids[0])), Err(ParticipantError::SelfMerge));
+        assert_eq!(participants.apply(merge(ids[1], Uuid::new_v4())), Err(ParticipantError::NotFound));
+        participants.apply(merge(ids[1], ids[0])).unwrap();
+        assert_eq!(participants.len(), 1);
+        assert_eq!(participants.get(ids[0]).unwrap().aliases, vec!["bobby".to_string(), ids[1].to_string()]);
+        assert_eq!(participants.resolve(&ids[1].to_string()), Some(ids[0]));
+
+        let mut errors = vec![];
+        participants.check(&mut Report::new(&mut errors));
+        assert_eq!(finish(errors), Ok(()));
+        assert_eq!(participants.apply(ParticipantChange::Removed { id: ids[0] }), Ok(Some((ids[0], "Bob".to_string()))));
+        assert!(participants.is_empty());
+    }
+}
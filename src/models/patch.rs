@@ -0,0 +1,137 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::models::bill::Bill;
+use crate::models::currency::MoneyError;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum PatchError {
+    /// An operation could not be applied, e.g. its path does not exist or a
+    /// `test` did not match.
+    Failed(String),
+    /// The patched document is not a bill.
+    Invalid(String),
+    Money(MoneyError),
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PatchError::Failed(message) => write!(f, "Patch could not be applied: {}", message),
+            PatchError::Invalid(message) => write!(f, "Patched bill is invalid: {}", message),
+            PatchError::Money(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+impl From<MoneyError> for PatchError {
+    fn from(err: MoneyError) -> Self {
+        PatchError::Money(err)
+    }
+}
+
+/// A partial update to a bill, applied to its JSON form. Items are reached
+/// by id under `/items`, e.g. `/items/3/price`.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(tag = "format", content = "patch", rename_all = "snake_case")]
+pub enum BillPatch {
+    /// An RFC 7396 merge patch.
+    Merge(Value),
+    /// An RFC 6902 JSON Patch.
+    Json(json_patch::Patch),
+}
+
+impl BillPatch {
+    /// The bill with this patch applied. Either every operation applies and
+    /// the result is a valid bill, or `bill` is left as it was.
+    ///
+    /// Item ids are never handed out twice: the next id stays past every id
+    /// `bill` has used and every item the patch adds.
+    pub fn apply(&self, bill: &Bill) -> Result<Bill, PatchError> {
+        let mut document = serde_json::to_value(bill).map_err(|err| PatchError::Invalid(err.to_string()))?;
+        match self {
+            BillPatch::Merge(patch) => json_patch::merge(&mut document, patch),
+            BillPatch::Json(patch) => {
+                json_patch::patch(&mut document, patch).map_err(|err| PatchError::Failed(err.to_string()))?
+            },
+        }
+        let mut patched: Bill = serde_json::from_value(document).map_err(|err| PatchError::Invalid(err.to_string()))?;
+        patched.check_currency()?;
+
+        let past_items = match patched.items().keys().max() {
+            Some(id) => id.checked_add(1).ok_or_else(|| {
+                PatchError::Invalid(format!("item ids must be below {}", u16::MAX))
+            })?,
+            None => 0,
+        };
+        patched.set_counter(bill.counter().max(patched.counter()).max(past_items));
+        Ok(patched)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::models::currency::{Currency, Money};
+    use crate::models::item::LineItem;
+
+    fn bill() -> Bill {
+        let mut bill = Bill::new("dinner".to_string(), Currency::USD);
+        bill.add_item(LineItem::from("pasta".to_string(), Money::new(1200, Currency::USD), None)).unwrap();
+        bill.add_item(LineItem::from("wine".to_string(), Money::new(3000, Currency::USD), None)).unwrap();
+        bill
+    }
+
+    fn json_patch(operations: Value) -> BillPatch {
+        BillPatch::Json(serde_json::from_value(operations).unwrap())
+    }
+
+    #[test]
+    fn test_merge() {
+        let patch = BillPatch::Merge(json!({
+            "name": "lunch",
+            "total": {"amount": "50.00", "currency": "USD"},
+            "items": {"0": {"name": "soup"}, "1": null},
+        }));
+        let patched = patch.apply(&bill()).unwrap();
+        assert_eq!(patched.name, "lunch");
+        assert_eq!(patched.total(), Some(Money::new(5000, Currency::USD)));
+        assert_eq!(patched.items().len(), 1);
+        assert_eq!(patched.items()[&0].name, "soup");
+        assert_eq!(patched.items()[&0].price, Money::new(1200, Currency::USD));
+        assert_eq!(patched.counter(), 2);
+    }
+
+    #[test]
+    fn test_json_patch() {
+        let patch = json_patch(json!([
+            {"op": "test", "path": "/name", "value": "dinner"},
+            {"op": "replace", "path": "/items/1/price/amount", "value": "25.00"},
+            {"op": "add", "path": "/items/7", "value": {"name": "cake", "price": {"amount": "6.00", "currency": "USD"}}},
+        ]));
+        let mut patched = patch.apply(&bill()).unwrap();
+        assert_eq!(patched.items()[&1].price, Money::new(2500, Currency::USD));
+        assert_eq!(patched.items()[&7].name, "cake");
+        assert_eq!(patched.add_item(LineItem::from("tea".to_string(), Money::new(300, Currency::USD), None)).unwrap(), 8);
+    }
+
+    #[test]
+    fn test_failures() {
+        let bill = bill();
+        let failed_test = json_patch(json!([
+            {"op": "replace", "path": "/name", "value": "lunch"},
+            {"op": "test", "path": "/name", "value": "dinner"},
+        ]));
+        assert!(matches!(failed_test.apply(&bill), Err(PatchError::Failed(_))));
+        let missing = json_patch(json!([{"op": "remove", "path": "/items/9"}]));
+        assert!(matches!(missing.apply(&bill), Err(PatchError::Failed(_))));
+
+        let not_a_bill = BillPatch::Merge(json!({"name": 3}));
+        assert!(matches!(not_a_bill.apply(&bill), Err(PatchError::Invalid(_))));
+        let wrong_currency = BillPatch::Merge(json!({"currency": "EUR"}));
+        assert!(matches!(wrong_currency.apply(&bill), Err(PatchError::Money(MoneyError::CurrencyMismatch { .. }))));
+    }
+}
@@ -0,0 +1,23 @@
+use serde::{Deserialize, Serialize};
+use crate::models::currency::{Currency, Money, MoneyError};
+
+/// Someone who paid towards a bill, e.g. by handing over their card. A
+/// bill can have several, and the same person more than once.
+#[derive(Debug, Deserialize, Serialize, Clone, Eq, Hash, PartialEq)]
+pub struct Payer {
+    pub name: String,
+    pub amount: Money,
+}
+
+impl Payer {
+    pub fn new(name: String, amount: Money) -> Self {
+        Self {
+            name,
+            amount,
+        }
+    }
+
+    pub fn expect_currency(&self, currency: Currency) -> Result<(), MoneyError> {
+        self.amount.expect_currency(currency)
+    }
+}
@@ -0,0 +1,117 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::models::currency::Money;
+use crate::models::validate::{finish, Report, ValidationError, MAX_NOTE_LENGTH};
+
+/// What a payment settles.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Eq, Hash, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Reference {
+    Bill(Uuid),
+    Group(Uuid),
+}
+
+/// Money one person gave another to settle up, e.g. "Alice paid me back
+/// $23.40". If the bill or group it refers to is deleted, the payment is
+/// kept without the reference.
+#[derive(Debug, Deserialize, Serialize, Clone, Eq, PartialEq)]
+pub struct Payment {
+    pub from: String,
+    pub to: String,
+    pub amount: Money,
+    pub date: NaiveDate,
+    #[serde(default)]
+    pub note: Option<String>,
+    #[serde(default)]
+    pub reference: Option<Reference>,
+}
+
+impl Payment {
+    pub fn new(from: String, to: String, amount: Money, date: NaiveDate) -> Self {
+        Self {
+            from,
+            to,
+            amount,
+            date,
+            note: None,
+            reference: None,
+        }
+    }
+
+    /// Fails with every field that breaks the limits in
+    /// `crate::models::validate`, or that makes it no payment at all.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let mut errors = vec![];
+        let mut report = Report::new(&mut errors);
+        report.check_name("from", &self.from);
+        report.check_name("to", &self.to);
+        if self.from == self.to {
+            report.push("to", "must be someone other than from");
+        }
+        report.check_amount("amount", self.amount);
+        if self.amount.amount() <= 0 {
+            report.push("amount", "must be more than zero");
+        }
+        if let Some(note) = &self.note {
+            if note.chars().count() > MAX_NOTE_LENGTH {
+                report.push("note", format!("must be at most {} characters", MAX_NOTE_LENGTH));
+            }
+        }
+        finish(errors)
+    }
+}
+
+/// A stored payment. The data layer hands out `id`; clients only ever send
+/// the `Payment`.
+#[derive(Debug, Deserialize, Serialize, Clone, Eq, PartialEq)]
+pub struct PaymentWithId {
+    pub id: Uuid,
+    pub payment: Payment,
+}
+
+/// The payments made towards bill `id`.
+pub fn for_bill(payments: &[PaymentWithId], id: Uuid) -> impl Iterator<Item = &Payment> {
+    payments.iter()
+        .map(|stored| &stored.payment)
+        .filter(move |payment| payment.reference == Some(Reference::Bill(id)))
+}
+
+/// The payments made towards group `id`.
+pub fn for_group(payments: &[PaymentWithId], id: Uuid) -> impl Iterator<Item = &Payment> {
+    payments.iter()
+        .map(|stored| &stored.payment)
+        .filter(move |payment| payment.reference == Some(Reference::Group(id)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::currency::Currency;
+
+    #[test]
+    fn test_validate() {
+        let date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        let payment = Payment::new("alice".to_string(), "bob".to_string(), Money::new(2340, Currency::USD), date);
+        assert_eq!(payment.validate(), Ok(()));
+
+        let mut payment = Payment::new("alice".to_string(), "alice".to_string(), Money::new(0, Currency::USD), date);
+        payment.note = Some("x".repeat(MAX_NOTE_LENGTH + 1));
+        let paths = payment.validate().unwrap_err().errors.into_iter().map(|error| error.path).collect::<Vec<String>>();
+        assert_eq!(paths, vec!["to", "amount", "note"]);
+    }
+
+    #[test]
+    fn test_serde() {
+        let payment: Payment = serde_json::from_value(serde_json::json!({
+            "from": "alice",
+            "to": "bob",
+            "amount": {"amount": "23.40", "currency": "USD"},
+            "date": "2024-05-01",
+            "reference": {"bill": "67e55044-10b1-426f-9247-bb680e5fe0c8"},
+        })).unwrap();
+        assert_eq!(payment.amount, Money::new(2340, Currency::USD));
+        assert_eq!(payment.reference, Some(Reference::Bill(Uuid::parse_str("67e55044-10b1-426f-9247-bb680e5fe0c8").unwrap())));
+        assert_eq!(payment.note, None);
+    }
+}
@@ -0,0 +1,378 @@
+//! Who pays whom to settle up, once everyone's share and payments are known.
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use crate::models::currency::{Currency, Money};
+use crate::models::payment::Payment;
+
+/// Above this many people with something to settle, transfers are matched
+/// greedily instead of searched for exactly.
+const EXACT_LIMIT: usize = 16;
+
+/// What one person paid and owes, and what they sent to or received from
+/// others since to settle up. `net` is what they paid and sent less what
+/// they owe and received: positive if they are owed money, negative if they
+/// owe it.
+#[derive(Debug, Deserialize, Serialize, Clone, Eq, PartialEq)]
+pub struct Balance {
+    pub person: String,
+    pub paid: Money,
+    pub owed: Money,
+    pub sent: Money,
+    pub received: Money,
+    pub net: Money,
+}
+
+impl Balance {
+    pub fn new(person: String, paid: Money, owed: Money) -> Self {
+        let currency = paid.currency();
+        Self::from_totals(person, currency, Totals {
+            paid: paid.amount(),
+            owed: owed.amount(),
+            ..Totals::default()
+        })
+    }
+
+    fn from_totals(person: String, currency: Currency, totals: Totals) -> Self {
+        let net = totals.paid + totals.sent - totals.owed - totals.received;
+        Self {
+            person,
+            paid: Money::new(totals.paid, currency),
+            owed: Money::new(totals.owed, currency),
+            sent: Money::new(totals.sent, currency),
+            received: Money::new(totals.received, currency),
+            net: Money::new(net, currency),
+        }
+    }
+}
+
+/// One person's running totals, in minor units.
+#[derive(Debug, Default, Clone, Copy)]
+struct Totals {
+    paid: i64,
+    owed: i64,
+    sent: i64,
+    received: i64,
+}
+
+/// Adds up balances in one currency, person by person, from bills and the
+/// payments made between people to settle them.
+#[derive(Debug, Clone)]
+pub struct Ledger {
+    currency: Currency,
+    people: BTreeMap<String, Totals>,
+}
+
+impl Ledger {
+    pub fn new(currency: Currency) -> Self {
+        Self {
+            currency,
+            people: BTreeMap::new(),
+        }
+    }
+
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    /// Makes sure `person` has a balance, even if it stays at zero.
+    pub fn add_person(&mut self, person: &str) {
+        self.totals(person);
+    }
+
+    pub fn add_paid(&mut self, person: &str, amount: Money) {
+        self.totals(person).paid += amount.amount();
+    }
+
+    pub fn add_owed(&mut self, person: &str, amount: Money) {
+        self.totals(person).owed += amount.amount();
+    }
+
+    pub fn add_balance(&mut self, balance: &Balance) {
+        let totals = self.totals(&balance.person);
+        totals.paid += balance.paid.amount();
+        totals.owed += balance.owed.amount();
+        totals.sent += balance.sent.amount();
+        totals.received += balance.received.amount();
+    }
+
+    /// Counts `payment` for both sides of it. Payments in another currency
+    /// are left out.
+    pub fn add_payment(&mut self, payment: &Payment) {
+        if payment.amount.currency() != self.currency {
+            return;
+        }
+        self.totals(&payment.from).sent += payment.amount.amount();
+        self.totals(&payment.to).received += payment.amount.amount();
+    }
+
+    /// Everyone's balance, sorted by name.
+    pub fn balances(&self) -> Vec<Balance> {
+        self.people.iter()
+            .map(|(person, totals)| Balance::from_totals(person.clone(), self.currency, *totals))
+            .collect()
+    }
+
+    fn totals(&mut self, person: &str) -> &mut Totals {
+        self.people.entry(person.to_string()).or_default()
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Eq, PartialEq)]
+pub struct Transfer {
+    pub from: String,
+    pub to: String,
+    pub amount: Money,
+}
+
+/// How to settle a bill: everyone's balance, and the transfers that even
+/// them out.
+///
+/// If the payments do not add up to `total`, the transfers even out as much
+/// as they can, and what is left is still owed to (or by) whoever took the
+/// bill's money.
+#[derive(Debug, Deserialize, Serialize, Clone, Eq, PartialEq)]
+pub struct SettlementPlan {
+    pub total: Money,
+    pub paid: Money,
+    /// The value of items nobody ordered, which nobody owes.
+    pub unassigned: Money,
+    /// Everyone who paid, owes, sent or received anything, sorted by name.
+    pub balances: Vec<Balance>,
+    pub transfers: Vec<Transfer>,
+    /// Whether the bill was paid in full and every share of it paid back,
+    /// so nobody owes anyone anything for it.
+    pub settled: bool,
+}
+
+impl SettlementPlan {
+    pub fn new(total: Money, paid: Money, unassigned: Money, ledger: &Ledger) -> Self {
+        let balances = ledger.balances();
+        let settled = paid == total && balances.iter().all(|balance| balance.net.is_zero());
+        Self {
+            total,
+            paid,
+            unassigned,
+            transfers: minimal_transfers(&balances),
+            balances,
+            settled,
+        }
+    }
+
+    /// The plan once `payments` between people are counted: each moves what
+    /// one person owes onto whoever they paid, and the transfers are worked
+    /// out again from what is left.
+    pub fn with_payments<'a>(self, payments: impl IntoIterator<Item = &'a Payment>) -> Self {
+        let mut ledger = Ledger::new(self.total.currency());
+        for balance in &self.balances {
+            ledger.add_balance(balance);
+        }
+        for payment in payments {
+            ledger.add_payment(payment);
+        }
+        Self::new(self.total, self.paid, self.unassigned, &ledger)
+    }
+
+    /// The plan with everyone called what `rename` calls them. Two people
+    /// given the same name become one.
+    pub fn renamed(self, rename: impl Fn(&str) -> String) -> Self {
+        let mut ledger = Ledger::new(self.total.currency());
+        for balance in self.balances {
+            ledger.add_balance(&Balance { person: rename(&balance.person), ..balance });
+        }
+        Self::new(self.total, self.paid, self.unassigned, &ledger)
+    }
+}
+
+/// The fewest transfers that bring every balance to zero, all in the
+/// currency of the balances.
+///
+/// Everyone in a group of people whose balances add up to zero can be
+/// settled with one transfer fewer than there are people in it, so the
+/// fewest transfers come from splitting people into as many such groups as
+/// possible. That search is exact for up to 16 people with something to
+/// settle; beyond that everyone is one group. Within a group, the largest
+/// debt is repeatedly paid to the largest credit.
+pub fn minimal_transfers(balances: &[Balance]) -> Vec<Transfer> {
+    let open = balances.iter()
+        .filter(|balance| !balance.net.is_zero())
+        .collect::<Vec<&Balance>>();
+    let nets = open.iter().map(|balance| balance.net.amount()).collect::<Vec<i64>>();
+    let groups = match open.len() <= EXACT_LIMIT {
+        true => zero_sum_groups(&nets),
+        false => vec![(0..open.len()).collect()],
+    };
+    groups.into_iter()
+        .flat_map(|group| settle_group(&group.into_iter().map(|index| open[index]).collect::<Vec<&Balance>>()))
+        .collect()
+}
+
+/// Splits `nets` into as many groups adding up to zero as possible, by
+/// finding the order of them with the most prefixes adding up to zero. If
+/// `nets` do not add up to zero, the last group holds what is left over.
+fn zero_sum_groups(nets: &[i64]) -> Vec<Vec<usize>> {
+    let full = (1usize << nets.len()) - 1;
+    let mut sums = vec![0i64; full + 1];
+    let mut most = vec![0u32; full + 1];
+    for mask in 1..=full {
+        sums[mask] = sums[mask & (mask - 1)] + nets[mask.trailing_zeros() as usize];
+        let best = (0..nets.len())
+            .filter(|index| mask & (1 << index) != 0)
+            .map(|index| most[mask ^ (1 << index)])
+            .max()
+            .unwrap_or(0);
+        most[mask] = best + u32::from(sums[mask] == 0);
+    }
+
+    let mut order = Vec::with_capacity(nets.len());
+    let mut mask = full;
+    while mask != 0 {
+        let bonus = u32::from(sums[mask] == 0);
+        let index = (0..nets.len())
+            .find(|index| mask & (1 << index) != 0 && most[mask ^ (1 << index)] + bonus == most[mask])
+            .unwrap_or_else(|| mask.trailing_zeros() as usize);
+        order.push(index);
+        mask ^= 1 << index;
+    }
+    order.reverse();
+
+    let mut groups = vec![];
+    let mut group = vec![];
+    let mut sum = 0;
+    for index in order {
+        group.push(index);
+        sum += nets[index];
+        if sum == 0 {
+            groups.push(std::mem::take(&mut group));
+        }
+    }
+    if !group.is_empty() {
+        groups.push(group);
+    }
+    groups
+}
+
+/// Pays the largest debt to the largest credit until one side runs out.
+/// Every transfer settles at least one person.
+fn settle_group(group: &[&Balance]) -> Vec<Transfer> {
+    let mut debtors = group.iter()
+        .filter(|balance| balance.net.is_negative())
+        .map(|balance| (balance.person.as_str(), -balance.net.amount()))
+        .collect::<Vec<(&str, i64)>>();
+    let mut creditors = group.iter()
+        .filter(|balance| !balance.net.is_negative())
+        .map(|balance| (balance.person.as_str(), balance.net.amount()))
+        .collect::<Vec<(&str, i64)>>();
+    // largest first, then by name
+    debtors.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    creditors.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    let Some(currency) = group.first().map(|balance| balance.net.currency()) else {
+        return vec![];
+    };
+
+    let mut transfers = vec![];
+    let (mut debtor, mut creditor) = (0, 0);
+    while debtor < debtors.len() && creditor < creditors.len() {
+        let amount = debtors[debtor].1.min(creditors[creditor].1);
+        transfers.push(Transfer {
+            from: debtors[debtor].0.to_string(),
+            to: creditors[creditor].0.to_string(),
+            amount: Money::new(amount, currency),
+        });
+        debtors[debtor].1 -= amount;
+        creditors[creditor].1 -= amount;
+        if debtors[debtor].1 == 0 {
+            debtor += 1;
+        }
+        if creditors[creditor].1 == 0 {
+            creditor += 1;
+        }
+    }
+    transfers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::currency::Currency;
+
+    fn usd(amount: i64) -> Money {
+        Money::new(amount, Currency::USD)
+    }
+
+    fn balances(nets: &[(&str, i64)]) -> Vec<Balance> {
+        nets.iter()
+            .map(|(person, net)| Balance::new(person.to_string(), usd((*net).max(0)), usd((-*net).max(0))))
+            .collect()
+    }
+
+    fn transfers(transfers: &[Transfer]) -> Vec<(&str, &str, i64)> {
+        transfers.iter()
+            .map(|transfer| (transfer.from.as_str(), transfer.to.as_str(), transfer.amount.amount()))
+            .collect()
+    }
+
+    #[test]
+    fn test_one_payer() {
+        let plan = minimal_transfers(&balances(&[("alice", 200), ("bob", -120), ("carol", -80), ("dave", 0)]));
+        assert_eq!(transfers(&plan), vec![("bob", "alice", 120), ("carol", "alice", 80)]);
+    }
+
+    #[test]
+    fn test_zero_sum_groups() {
+        // greedily, alice would pay carol first and it would take four
+        // transfers; pairing bob with carol takes three
+        let nets = [("alice", -60), ("bob", -50), ("carol", 50), ("dave", 30), ("erin", 30)];
+        let plan = minimal_transfers(&balances(&nets));
+        assert_eq!(plan.len(), 3);
+        assert!(transfers(&plan).contains(&("bob", "carol", 50)));
+        for (person, net) in nets {
+            let received = plan.iter().filter(|transfer| transfer.to == person).map(|transfer| transfer.amount.amount());
+            let sent = plan.iter().filter(|transfer| transfer.from == person).map(|transfer| transfer.amount.amount());
+            assert_eq!(received.sum::<i64>() - sent.sum::<i64>(), net);
+        }
+    }
+
+    #[test]
+    fn test_unbalanced() {
+        // bob and carol only paid 100 of the 150 they owe between them
+        let plan = minimal_transfers(&balances(&[("alice", 100), ("bob", -100), ("carol", -50)]));
+        assert_eq!(transfers(&plan), vec![("bob", "alice", 100)]);
+        assert!(minimal_transfers(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_with_payments() {
+        let mut ledger = Ledger::new(Currency::USD);
+        ledger.add_paid("alice", usd(3000));
+        ledger.add_owed("alice", usd(1000));
+        ledger.add_owed("bob", usd(2000));
+        let plan = SettlementPlan::new(usd(3000), usd(3000), usd(0), &ledger);
+        assert!(!plan.settled);
+
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        let mut payments = vec![Payment::new("bob".to_string(), "alice".to_string(), usd(1500), date)];
+        let partly = plan.clone().with_payments(&payments);
+        assert_eq!(transfers(&partly.transfers), vec![("bob", "alice", 500)]);
+        assert_eq!((partly.balances[0].received.amount(), partly.balances[1].sent.amount()), (1500, 1500));
+        assert!(!partly.settled);
+
+        payments.push(Payment::new("bob".to_string(), "alice".to_string(), Money::new(500, Currency::EUR), date));
+        assert!(!plan.clone().with_payments(&payments).settled);
+        payments.push(Payment::new("bob".to_string(), "alice".to_string(), usd(500), date));
+        let settled = plan.with_payments(&payments);
+        assert!(settled.transfers.is_empty());
+        assert!(settled.settled);
+    }
+
+    #[test]
+    fn test_many_people() {
+        let nets = (0..40).map(|index| (index, if index == 0 { 390 } else { -10 })).collect::<Vec<(i32, i64)>>();
+        let names = nets.iter().map(|(index, _)| format!("p{:02}", index)).collect::<Vec<String>>();
+        let balances = names.iter().zip(&nets)
+            .map(|(name, (_, net))| Balance::new(name.clone(), usd((*net).max(0)), usd((-*net).max(0))))
+            .collect::<Vec<Balance>>();
+        let plan = minimal_transfers(&balances);
+        assert_eq!(plan.len(), 39);
+        assert!(plan.iter().all(|transfer| transfer.to == "p00"));
+    }
+}
@@ -0,0 +1,177 @@
+use serde::{Deserialize, Serialize};
+use crate::models::currency::{Money, MoneyError};
+
+/// How the units left over after flooring every part are handed out.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, Eq, Hash, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundingPolicy {
+    /// One unit each to the parts with the largest fractional remainders.
+    #[default]
+    LargestRemainder,
+    /// All leftover units go to the part with the largest weight.
+    LargestPart,
+    /// One unit each to the parts in order, starting with the first.
+    InOrder,
+}
+
+/// The result of splitting an amount: each part, and how many leftover
+/// minor units each part absorbed while rounding.
+#[derive(Debug, Deserialize, Serialize, Clone, Eq, PartialEq)]
+pub struct Allocation {
+    pub parts: Vec<Money>,
+    pub rounding: Vec<u64>,
+}
+
+/// Splits `amount` into parts proportional to `weights`.
+///
+/// The parts always sum to exactly `amount` and are all in its currency. If
+/// every weight is zero the amount is split evenly instead. A negative amount
+/// is split as if it were positive and every part negated. Ties are broken in
+/// favour of the earlier part so the result is deterministic. Fails if a
+/// part is too large to hold.
+pub fn split(amount: Money, weights: &[u64], policy: RoundingPolicy) -> Result<Allocation, MoneyError> {
+    if weights.is_empty() {
+        return Ok(Allocation {
+            parts: vec![],
+            rounding: vec![],
+        });
+    }
+
+    let weights = if weights.iter().all(|weight| *weight == 0) {
+        vec![1; weights.len()]
+    } else {
+        weights.to_vec()
+    };
+    let sum = weights.iter().map(|weight| *weight as u128).sum::<u128>();
+    let units = amount.amount().unsigned_abs();
+
+    let mut parts = Vec::with_capacity(weights.len());
+    let mut remainders = Vec::with_capacity(weights.len());
+    for weight in &weights {
+        let exact = units as u128 * *weight as u128;
+        parts.push((exact / sum) as u64);
+        remainders.push(exact % sum);
+    }
+
+    let mut leftover = units - parts.iter().sum::<u64>();
+    let mut rounding = vec![0; weights.len()];
+    match policy {
+        RoundingPolicy::LargestRemainder => {
+            let mut order = (0..weights.len()).collect::<Vec<usize>>();
+            order.sort_by(|a, b| remainders[*b].cmp(&remainders[*a]).then(a.cmp(b)));
+            for index in order.into_iter().cycle() {
+                if leftover == 0 {
+                    break;
+                }
+                rounding[index] += 1;
+                leftover -= 1;
+            }
+        },
+        RoundingPolicy::LargestPart => {
+            let mut largest = 0;
+            for (index, weight) in weights.iter().enumerate() {
+                if *weight > weights[largest] {
+                    largest = index;
+                }
+            }
+            rounding[largest] = leftover;
+        },
+        RoundingPolicy::InOrder => {
+            for index in (0..weights.len()).cycle() {
+                if leftover == 0 {
+                    break;
+                }
+                rounding[index] += 1;
+                leftover -= 1;
+            }
+        },
+    }
+
+    let sign = if amount.is_negative() { -1 } else { 1 };
+    let parts = parts.iter().zip(&rounding)
+        .map(|(part, extra)| {
+            let part = i64::try_from(sign * (*part as i128 + *extra as i128)).map_err(|_| MoneyError::Overflow)?;
+            Ok(Money::new(part, amount.currency()))
+        })
+        .collect::<Result<Vec<Money>, MoneyError>>()?;
+    Ok(Allocation {
+        parts,
+        rounding,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::currency::Currency;
+
+    fn amounts(parts: &[Money]) -> Vec<i64> {
+        parts.iter().map(|part| part.amount()).collect()
+    }
+
+    fn usd(amount: i64) -> Money {
+        Money::new(amount, Currency::USD)
+    }
+
+    #[test]
+    fn test_split_exact() {
+        let allocation = split(usd(330), &[100, 200], RoundingPolicy::LargestRemainder).unwrap();
+        assert_eq!(allocation.parts, vec![usd(110), usd(220)]);
+        assert_eq!(allocation.rounding, vec![0, 0]);
+    }
+
+    #[test]
+    fn test_split_largest_remainder() {
+        let allocation = split(usd(100), &[1, 1, 1], RoundingPolicy::LargestRemainder).unwrap();
+        assert_eq!(amounts(&allocation.parts), vec![34, 33, 33]);
+        assert_eq!(allocation.rounding, vec![1, 0, 0]);
+
+        // 1000 * 2/7 = 285.71, 1000 * 5/7 = 714.28
+        let allocation = split(usd(1000), &[200, 500], RoundingPolicy::LargestRemainder).unwrap();
+        assert_eq!(amounts(&allocation.parts), vec![286, 714]);
+        assert_eq!(allocation.rounding, vec![1, 0]);
+    }
+
+    #[test]
+    fn test_split_largest_part() {
+        let allocation = split(usd(100), &[1, 2, 1, 2], RoundingPolicy::LargestPart).unwrap();
+        assert_eq!(amounts(&allocation.parts), vec![16, 35, 16, 33]);
+        assert_eq!(allocation.rounding, vec![0, 2, 0, 0]);
+    }
+
+    #[test]
+    fn test_split_in_order() {
+        let allocation = split(usd(101), &[1, 1, 1, 1], RoundingPolicy::InOrder).unwrap();
+        assert_eq!(amounts(&allocation.parts), vec![26, 25, 25, 25]);
+        assert_eq!(allocation.rounding, vec![1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_split_negative() {
+        let allocation = split(usd(-100), &[1, 1, 1], RoundingPolicy::LargestRemainder).unwrap();
+        assert_eq!(amounts(&allocation.parts), vec![-34, -33, -33]);
+        assert_eq!(allocation.rounding, vec![1, 0, 0]);
+
+        let allocation = split(usd(i64::MIN), &[1], RoundingPolicy::LargestRemainder).unwrap();
+        assert_eq!(allocation.parts, vec![usd(i64::MIN)]);
+    }
+
+    #[test]
+    fn test_split_sums_to_amount() {
+        let weights = [137, 251, 3, 999, 42];
+        for amount in [0, 1, 7, 999, 12345, 1_000_003, -4321] {
+            for policy in [RoundingPolicy::LargestRemainder, RoundingPolicy::LargestPart, RoundingPolicy::InOrder] {
+                let allocation = split(usd(amount), &weights, policy).unwrap();
+                assert_eq!(Money::checked_sum(Currency::USD, &allocation.parts), Ok(usd(amount)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_split_zero_weights() {
+        let allocation = split(Money::new(10, Currency::JPY), &[0, 0, 0], RoundingPolicy::LargestRemainder).unwrap();
+        assert_eq!(amounts(&allocation.parts), vec![4, 3, 3]);
+        assert_eq!(allocation.parts[0].currency(), Currency::JPY);
+        assert_eq!(split(usd(10), &[], RoundingPolicy::LargestRemainder).unwrap().parts, vec![]);
+    }
+}
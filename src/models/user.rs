@@ -0,0 +1,131 @@
+//! Accounts people sign in to the API with. Only a hash of each password is
+//! ever stored.
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{self, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use uuid::Uuid;
+use crate::models::validate::{finish, Report, ValidationError, MAX_PASSWORD_LENGTH, MAX_USERNAME_LENGTH, MIN_PASSWORD_LENGTH};
+
+/// What someone signs up or signs in with.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl Credentials {
+    pub fn new(username: String, password: String) -> Self {
+        Self { username, password }
+    }
+
+    /// Fails unless the username is one a new account can take and the
+    /// password is long enough to be worth hashing.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let mut errors = vec![];
+        let mut report = Report::new(&mut errors);
+        check_username(&mut report, "username", &self.username);
+        let length = self.password.chars().count();
+        if length < MIN_PASSWORD_LENGTH {
+            report.push("password", format!("must be at least {} characters", MIN_PASSWORD_LENGTH));
+        } else if length > MAX_PASSWORD_LENGTH {
+            report.push("password", format!("must be at most {} characters", MAX_PASSWORD_LENGTH));
+        }
+        finish(errors)
+    }
+}
+
+/// Usernames end up in paths such as `/bill/:id/members/:username`, so they
+/// are kept to characters that never need escaping there, in lower case so
+/// that "Alice" and "alice" cannot be two people.
+fn check_username(report: &mut Report, field: &str, username: &str) {
+    let allowed = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '.' | '_' | '-');
+    if username.is_empty() {
+        report.push(field, "must not be empty");
+    } else if username.len() > MAX_USERNAME_LENGTH {
+        report.push(field, format!("must be at most {} characters", MAX_USERNAME_LENGTH));
+    } else if !username.chars().all(allowed) {
+        report.push(field, "must only have lowercase letters, digits, '.', '_' and '-'");
+    }
+}
+
+/// A stored account.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct User {
+    pub username: String,
+    /// The password hashed with Argon2id, in PHC string format, salt and
+    /// parameters included.
+    pub password_hash: String,
+}
+
+impl User {
+    /// An account for `credentials`, with the password hashed under a new
+    /// random salt. Hashing is slow on purpose, so callers on an async
+    /// runtime should do it on a blocking thread.
+    pub fn register(credentials: &Credentials) -> Result<Self, password_hash::Error> {
+        let salt = SaltString::generate(&mut OsRng);
+        let hash = Argon2::default().hash_password(credentials.password.as_bytes(), &salt)?;
+        Ok(Self {
+            username: credentials.username.clone(),
+            password_hash: hash.to_string(),
+        })
+    }
+
+    /// Whether `password` is this account's password. A stored hash that
+    /// cannot be read matches nothing.
+    pub fn verify(&self, password: &str) -> bool {
+        PasswordHash::new(&self.password_hash)
+            .map(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+            .unwrap_or(false)
+    }
+
+    /// An account no one can sign in to, hashed the same way as real ones.
+    /// Checking a password against it when there is no such user takes as
+    /// long as checking a real account, so timing does not give away which
+    /// usernames are taken.
+    pub fn nobody() -> Option<&'static User> {
+        static NOBODY: OnceLock<Option<User>> = OnceLock::new();
+        NOBODY.get_or_init(|| {
+            User::register(&Credentials::new(String::new(), Uuid::new_v4().to_string())).ok()
+        }).as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(Credentials::new("alice".to_string(), "correct horse".to_string()).validate(), Ok(()));
+        let err = Credentials::new("Alice".to_string(), "short".to_string()).validate().unwrap_err();
+        let paths = err.errors.iter().map(|error| error.path.as_str()).collect::<Vec<&str>>();
+        assert_eq!(paths, vec!["username", "password"]);
+        assert!(Credentials::new("al/ice".to_string(), "correct horse".to_string()).validate().is_err());
+        assert!(Credentials::new("".to_string(), "correct horse".to_string()).validate().is_err());
+    }
+
+    #[test]
+    fn test_register_and_verify() {
+        let user = User::register(&Credentials::new("alice".to_string(), "correct horse".to_string())).unwrap();
+        assert!(user.password_hash.starts_with("$argon2id$"));
+        assert!(!user.password_hash.contains("correct horse"));
+        assert!(user.verify("correct horse"));
+        assert!(!user.verify("correct horse "));
+
+        // the same password never hashes the same way twice
+        let again = User::register(&Credentials::new("alice".to_string(), "correct horse".to_string())).unwrap();
+        assert_ne!(again.password_hash, user.password_hash);
+        let broken = User { password_hash: "plain".to_string(), ..user };
+        assert!(!broken.verify("plain"));
+    }
+
+    #[test]
+    fn test_nobody() {
+        let nobody = User::nobody().unwrap();
+        assert!(nobody.password_hash.starts_with("$argon2id$"));
+        assert!(!nobody.verify(""));
+        assert_eq!(User::nobody(), Some(nobody));
+    }
+}
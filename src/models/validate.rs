@@ -0,0 +1,222 @@
+//! Limits on bills and items beyond what deserializing them checks. Every
+//! problem is reported against the path of the field it is in, e.g.
+//! `items.3.price`.
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::models::currency::Money;
+
+/// The longest name, in characters, of a bill, item, adjustment or orderer.
+pub const MAX_NAME_LENGTH: usize = 200;
+pub const MAX_ITEMS: usize = 500;
+pub const MAX_ORDERERS: usize = 100;
+pub const MAX_ADJUSTMENTS: usize = 50;
+pub const MAX_PAYERS: usize = 100;
+pub const MAX_GROUP_BILLS: usize = 1000;
+pub const MAX_PARTICIPANTS: usize = 100;
+pub const MAX_ALIASES: usize = 20;
+/// The longest note on a payment, in characters.
+pub const MAX_NOTE_LENGTH: usize = 1000;
+pub const MAX_USERNAME_LENGTH: usize = 64;
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+/// The longest password, in characters. Hashing takes longer the longer it
+/// is, so this keeps one request from tying up a thread.
+pub const MAX_PASSWORD_LENGTH: usize = 256;
+/// How many people besides its owner a bill can be shared with.
+pub const MAX_MEMBERS: usize = 100;
+/// The largest price or total either way, in minor units. Far below where
+/// adding up a bill could overflow.
+pub const MAX_AMOUNT: i64 = 1_000_000_000_000;
+
+/// One problem with one field.
+#[derive(Debug, Deserialize, Serialize, Clone, Eq, PartialEq)]
+pub struct FieldError {
+    /// Where the field is, with map keys and list indexes joined by dots.
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Every problem found, in the order the fields were checked.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ValidationError {
+    pub errors: Vec<FieldError>,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let errors = self.errors.iter().map(FieldError::to_string).collect::<Vec<String>>();
+        write!(f, "Validation failed: {}", errors.join("; "))
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Collects problems for fields under a common path.
+pub(crate) struct Report<'a> {
+    prefix: String,
+    errors: &'a mut Vec<FieldError>,
+}
+
+impl<'a> Report<'a> {
+    pub(crate) fn new(errors: &'a mut Vec<FieldError>) -> Self {
+        Self { prefix: String::new(), errors }
+    }
+
+    fn path(&self, field: &str) -> String {
+        match (self.prefix.is_empty(), field.is_empty()) {
+            (true, _) => field.to_string(),
+            (false, true) => self.prefix.clone(),
+            (false, false) => format!("{}.{}", self.prefix, field),
+        }
+    }
+
+    /// A report for the fields of `field`.
+    pub(crate) fn nested(&mut self, field: &str) -> Report<'_> {
+        Report { prefix: self.path(field), errors: self.errors }
+    }
+
+    pub(crate) fn push(&mut self, field: &str, message: impl Into<String>) {
+        let path = self.path(field);
+        self.errors.push(FieldError { path, message: message.into() });
+    }
+
+    /// Names must have something in them and must not run on.
+    pub(crate) fn check_name(&mut self, field: &str, name: &str) {
+        if name.trim().is_empty() {
+            self.push(field, "must not be empty");
+        } else if name.chars().count() > MAX_NAME_LENGTH {
+            self.push(field, format!("must be at most {} characters", MAX_NAME_LENGTH));
+        }
+    }
+
+    pub(crate) fn check_amount(&mut self, field: &str, amount: Money) {
+        if amount.amount().abs() > MAX_AMOUNT {
+            self.push(field, "is too large");
+        }
+    }
+
+    pub(crate) fn check_count(&mut self, field: &str, count: usize, max: usize) {
+        if count > max {
+            self.push(field, format!("must have at most {} entries", max));
+        }
+    }
+}
+
+/// `Ok` if nothing was found.
+pub(crate) fn finish(errors: Vec<FieldError>) -> Result<(), ValidationError> {
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(ValidationError { errors }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use super::*;
+    use crate::models::adjustment::{Adjustment, AdjustmentKind};
+    use crate::models::bill::Bill;
+    use crate::models::currency::{Currency, Money};
+    use crate::models::item::{LineItem, Portion};
+
+    fn usd(amount: i64) -> Money {
+        Money::new(amount, Currency::USD)
+    }
+
+    fn paths(err: ValidationError) -> Vec<String> {
+        err.errors.into_iter().map(|error| error.path).collect()
+    }
+
+    #[test]
+    fn test_valid() {
+        let mut bill = Bill::from("dinner".to_string(), usd(1000));
+        bill.add_item(LineItem::from("pasta".to_string(), usd(900), Some("alice".to_string()))).unwrap();
+        assert_eq!(bill.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_item() {
+        let orderers = BTreeMap::from([(" ".to_string(), Portion::Equal), ("bob".to_string(), Portion::Percent(10_001))]);
+        let item = LineItem::shared("x".repeat(MAX_NAME_LENGTH + 1), usd(MAX_AMOUNT + 1), orderers);
+        let err = item.validate().unwrap_err();
+        assert_eq!(paths(err.clone()), vec!["name", "price", "orderers. ", "orderers.bob"]);
+        assert_eq!(err.errors[0].message, "must be at most 200 characters");
+
+        let mut bill = Bill::new("dinner".to_string(), Currency::USD);
+        bill.add_item(LineItem::from("a".to_string(), usd(100), None)).unwrap();
+        bill.add_item(item).unwrap();
+        assert_eq!(paths(bill.validate().unwrap_err()), vec!["items.1.name", "items.1.price", "items.1.orderers. ", "items.1.orderers.bob"]);
+    }
+
+    #[test]
+    fn test_bill() {
+        let bill: Bill = serde_json::from_value(serde_json::json!({
+            "name": "",
+            "currency": "USD",
+            "total": {"amount": "1.00", "currency": "USD"},
+            "items": {"3": {"name": "pasta", "price": {"amount": "2.00", "currency": "USD"}}},
+            "counter": 3,
+        })).unwrap();
+        let err = bill.validate().unwrap_err();
+        assert_eq!(paths(err.clone()), vec!["name", "counter", "total"]);
+        assert_eq!(
+            err.to_string(),
+            "Validation failed: name: must not be empty; counter: must be greater than every item id, but item 3 exists; \
+            total: must be at least the subtotal less discounts (2.00 USD)"
+        );
+
+        // discounts can bring the total below the subtotal
+        let mut bill = Bill::from("dinner".to_string(), usd(90));
+        bill.add_item(LineItem::from("pasta".to_string(), usd(100), None)).unwrap();
+        bill.add_adjustment(Adjustment::new("coupon".to_string(), AdjustmentKind::Discount(usd(10)))).unwrap();
+        assert_eq!(bill.validate(), Ok(()));
+        bill.add_adjustment(Adjustment::new("".to_string(), AdjustmentKind::DiscountRate(20_000))).unwrap();
+        assert_eq!(paths(bill.validate().unwrap_err()), vec!["adjustments.1.name", "adjustments.1.kind"]);
+    }
+
+    #[test]
+    fn test_fixed_adjustments() {
+        let kinds: [fn(Money) -> AdjustmentKind; 4] = [
+            AdjustmentKind::Discount,
+            AdjustmentKind::Fee,
+            AdjustmentKind::TaxAmount,
+            AdjustmentKind::TipAmount,
+        ];
+        for kind in kinds {
+            for amount in [usd(MAX_AMOUNT + 1), usd(-1)] {
+                let mut bill = Bill::new("dinner".to_string(), Currency::USD);
+                bill.add_adjustment(Adjustment::new("extra".to_string(), kind(amount))).unwrap();
+                assert_eq!(paths(bill.validate().unwrap_err()), vec!["adjustments.0.kind"]);
+            }
+            let mut bill = Bill::new("dinner".to_string(), Currency::USD);
+            bill.add_adjustment(Adjustment::new("extra".to_string(), kind(usd(MAX_AMOUNT)))).unwrap();
+            assert_eq!(bill.validate(), Ok(()));
+        }
+
+        // amounts that would overflow adding up are caught before they are
+        let mut bill = Bill::from("dinner".to_string(), usd(100));
+        for _ in 0..2 {
+            bill.add_adjustment(Adjustment::new("coupon".to_string(), AdjustmentKind::Discount(usd(i64::MAX)))).unwrap();
+        }
+        assert_eq!(paths(bill.validate().unwrap_err()), vec!["adjustments.0.kind", "adjustments.1.kind", "total"]);
+    }
+
+    #[test]
+    fn test_fixed_portions() {
+        let orderers = BTreeMap::from([
+            ("alice".to_string(), Portion::Fixed(usd(i64::MAX))),
+            ("bob".to_string(), Portion::Fixed(usd(-i64::MAX))),
+        ]);
+        let item = LineItem::shared("pizza".to_string(), usd(100), orderers);
+        assert_eq!(paths(item.validate().unwrap_err()), vec!["orderers.alice", "orderers.bob"]);
+
+        let orderers = BTreeMap::from([("alice".to_string(), Portion::Fixed(usd(-MAX_AMOUNT)))]);
+        let item = LineItem::shared("refund".to_string(), usd(-MAX_AMOUNT), orderers);
+        assert_eq!(item.validate(), Ok(()));
+    }
+}
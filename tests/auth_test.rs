@@ -0,0 +1,113 @@
+// Integration tests for accounts, tokens and sharing bills
+use tokio::task;
+use uuid::Uuid;
+use billsplit::api::auth::Token;
+use billsplit::api::error::ErrorBody;
+use billsplit::api::handlers::member_handler::Members;
+use billsplit::models::bill::Bill;
+use billsplit::models::currency::Currency;
+use billsplit::models::user::Credentials;
+
+fn start_server() -> task::JoinHandle<()> {
+    task::spawn(async {
+        let config = billsplit::config::Config::new();
+        billsplit::start_server(config).await;
+    })
+}
+
+fn stop_server(server: task::JoinHandle<()>) {
+    server.abort();
+}
+
+/// Signs up as `username` and returns a client that sends their token.
+async fn sign_in(username: &str) -> reqwest::Client {
+    let client = reqwest::Client::new();
+    let credentials = Credentials::new(username.to_string(), "correct horse".to_string());
+    client.post("http://localhost:3000/auth/register").json(&credentials).send().await.unwrap();
+    let token: Token = client
+        .post("http://localhost:3000/auth/login")
+        .json(&credentials)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(reqwest::header::AUTHORIZATION, format!("Bearer {}", token.token).parse().unwrap());
+    reqwest::Client::builder().default_headers(headers).build().unwrap()
+}
+
+#[tokio::test]
+async fn test_auth() {
+    let server = start_server();
+    let anonymous = reqwest::Client::new();
+
+    let response = anonymous.get("http://localhost:3000/bills").send().await.unwrap();
+    assert_eq!(response.status(), 401);
+    assert_eq!(response.headers()["www-authenticate"], "Bearer");
+    let body: ErrorBody = response.json().await.unwrap();
+    assert_eq!(body.code, "unauthorized");
+    let response = anonymous
+        .get("http://localhost:3000/bills")
+        .bearer_auth("not a token")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+
+    let credentials = Credentials::new("Alice".to_string(), "short".to_string());
+    let response = anonymous.post("http://localhost:3000/auth/register").json(&credentials).send().await.unwrap();
+    assert_eq!(response.status(), 422);
+    let alice = sign_in("alice").await;
+    let bob = sign_in("bob").await;
+    let credentials = Credentials::new("alice".to_string(), "correct horse".to_string());
+    let response = anonymous.post("http://localhost:3000/auth/register").json(&credentials).send().await.unwrap();
+    assert_eq!(response.status(), 409);
+
+    // a wrong password and an unknown user look the same
+    for (username, password) in [("alice", "wrong horse"), ("nobody", "correct horse")] {
+        let credentials = Credentials::new(username.to_string(), password.to_string());
+        let response = anonymous.post("http://localhost:3000/auth/login").json(&credentials).send().await.unwrap();
+        assert_eq!(response.status(), 401);
+        let body: ErrorBody = response.json().await.unwrap();
+        assert_eq!(body.code, "invalid_credentials");
+    }
+
+    let id: Uuid = alice
+        .post("http://localhost:3000/bill/insert")
+        .json(&Bill::new("dinner".to_string(), Currency::USD))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let url = format!("http://localhost:3000/bill/{}", id);
+    assert_eq!(bob.get(&url).send().await.unwrap().status(), 404);
+    let bills: Vec<Bill> = bob.get("http://localhost:3000/bills").send().await.unwrap().json().await.unwrap();
+    assert!(bills.is_empty());
+
+    let response = alice.put(format!("{}/members/bob", url)).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let members: Members = response.json().await.unwrap();
+    assert_eq!(members, Members { owner: Some("alice".to_string()), members: vec!["bob".to_string()] });
+    assert_eq!(bob.get(&url).send().await.unwrap().status(), 200);
+    let response = alice.put(format!("{}/members/carol", url)).send().await.unwrap();
+    assert_eq!(response.status(), 404);
+    let body: ErrorBody = response.json().await.unwrap();
+    assert_eq!(body.code, "user_not_found");
+
+    // members can see the bill, but only its owner can delete it
+    let response = bob.delete(&url).header("If-Match", "\"2\"").send().await.unwrap();
+    assert_eq!(response.status(), 403);
+    let body: ErrorBody = response.json().await.unwrap();
+    assert_eq!(body.code, "forbidden");
+    let response = bob.delete(format!("{}/members/bob", url)).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(bob.get(&url).send().await.unwrap().status(), 404);
+    let response = alice.delete(&url).header("If-Match", "\"3\"").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    stop_server(server);
+}
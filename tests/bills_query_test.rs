@@ -0,0 +1,91 @@
+// Integration tests for listing bills a page at a time
+use tokio::task;
+use billsplit::api::auth::Token;
+use billsplit::api::error::ErrorBody;
+use billsplit::models::bill::{Bill, BillWithId};
+use billsplit::models::currency::{Currency, Money};
+use billsplit::models::item::LineItem;
+use billsplit::models::user::Credentials;
+
+fn start_server() -> task::JoinHandle<()> {
+    task::spawn(async {
+        let config = billsplit::config::Config::new();
+        billsplit::start_server(config).await;
+    })
+}
+
+fn stop_server(server: task::JoinHandle<()>) {
+    server.abort();
+}
+
+/// Signs up as `username` and returns a client that sends their token.
+async fn sign_in(username: &str) -> reqwest::Client {
+    let client = reqwest::Client::new();
+    let credentials = Credentials::new(username.to_string(), "correct horse".to_string());
+    client.post("http://localhost:3000/auth/register").json(&credentials).send().await.unwrap();
+    let token: Token = client
+        .post("http://localhost:3000/auth/login")
+        .json(&credentials)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(reqwest::header::AUTHORIZATION, format!("Bearer {}", token.token).parse().unwrap());
+    reqwest::Client::builder().default_headers(headers).build().unwrap()
+}
+
+fn next_link(response: &reqwest::Response) -> Option<String> {
+    let link = response.headers().get("link")?.to_str().unwrap();
+    let url = link.strip_prefix('<')?.split('>').next()?;
+    Some(format!("http://localhost:3000{}", url))
+}
+
+#[tokio::test]
+async fn test_query_bills() {
+    let server = start_server();
+    let client = sign_in("alice").await;
+
+    for (name, price, orderer) in [("dinner", 3000, "alice"), ("lunch", 1000, "bob"), ("late dinner", 2000, "bob")] {
+        let mut bill = Bill::new(name.to_string(), Currency::USD);
+        bill.add_item(LineItem::from("item".to_string(), Money::new(price, Currency::USD), Some(orderer.to_string()))).unwrap();
+        let response = client.post("http://localhost:3000/bill/insert").json(&bill).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    let response = client.get("http://localhost:3000/bills").send().await.unwrap();
+    assert!(response.headers().get("link").is_none());
+    let bills: Vec<BillWithId> = response.json().await.unwrap();
+    let names = bills.iter().map(|stored| stored.bill.name.as_str()).collect::<Vec<&str>>();
+    assert_eq!(names, vec!["dinner", "lunch", "late dinner"]);
+
+    let response = client
+        .get("http://localhost:3000/bills?participant=bob&sort=total&order=desc")
+        .send()
+        .await
+        .unwrap();
+    let bills: Vec<BillWithId> = response.json().await.unwrap();
+    let names = bills.iter().map(|stored| stored.bill.name.as_str()).collect::<Vec<&str>>();
+    assert_eq!(names, vec!["late dinner", "lunch"]);
+
+    // follow the links one bill at a time
+    let mut url = Some("http://localhost:3000/bills?sort=name&limit=1".to_string());
+    let mut names = vec![];
+    while let Some(next) = url {
+        let response = client.get(&next).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+        url = next_link(&response);
+        let bills: Vec<BillWithId> = response.json().await.unwrap();
+        names.extend(bills.into_iter().map(|stored| stored.bill.name));
+    }
+    assert_eq!(names, vec!["dinner", "late dinner", "lunch"]);
+
+    let response = client.get("http://localhost:3000/bills?cursor=nonsense").send().await.unwrap();
+    assert_eq!(response.status(), 400);
+    let body: ErrorBody = response.json().await.unwrap();
+    assert_eq!(body.code, "invalid_query");
+
+    stop_server(server);
+}
@@ -0,0 +1,114 @@
+// Integration tests for the per-person share endpoints
+use tokio::task;
+use uuid::Uuid;
+use billsplit::api::auth::Token;
+use billsplit::api::error::ErrorBody;
+use billsplit::models::bill::Bill;
+use billsplit::models::breakdown::Breakdown;
+use billsplit::models::currency::{Currency, Money};
+use billsplit::models::item::LineItem;
+use billsplit::models::user::Credentials;
+
+fn start_server() -> task::JoinHandle<()> {
+    task::spawn(async {
+        let config = billsplit::config::Config::new();
+        billsplit::start_server(config).await;
+    })
+}
+
+fn usd(amount: i64) -> Money {
+    Money::new(amount, Currency::USD)
+}
+
+fn stop_server(server: task::JoinHandle<()>) {
+    server.abort();
+}
+
+/// Signs up as `username` and returns a client that sends their token.
+async fn sign_in(username: &str) -> reqwest::Client {
+    let client = reqwest::Client::new();
+    let credentials = Credentials::new(username.to_string(), "correct horse".to_string());
+    client.post("http://localhost:3000/auth/register").json(&credentials).send().await.unwrap();
+    let token: Token = client
+        .post("http://localhost:3000/auth/login")
+        .json(&credentials)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(reqwest::header::AUTHORIZATION, format!("Bearer {}", token.token).parse().unwrap());
+    reqwest::Client::builder().default_headers(headers).build().unwrap()
+}
+
+#[tokio::test]
+async fn test_breakdown() {
+    let server = start_server();
+    let client = sign_in("alice").await;
+
+    let mut new_bill = Bill::from("dinner".to_string(), usd(330));
+    new_bill.add_item(LineItem::from("pasta".to_string(), usd(100), Some("alice".to_string()))).unwrap();
+    new_bill.add_item(LineItem::from("steak".to_string(), usd(200), Some("bob".to_string()))).unwrap();
+
+    let response = client
+        .post("http://localhost:3000/bill/insert")
+        .json(&new_bill)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let uuid: Uuid = response.json().await.unwrap();
+
+    let response = client
+        .get(format!("http://localhost:3000/bill/{}/for/alice", uuid))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let bill: Bill = response.json().await.unwrap();
+    assert_eq!(bill, new_bill.get_bill_for("alice").unwrap());
+    assert_eq!(bill.total(), Some(usd(110)));
+
+    let response = client
+        .get(format!("http://localhost:3000/bill/{}/breakdown", uuid))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let breakdown: Breakdown = response.json().await.unwrap();
+    assert_eq!(breakdown, new_bill.get_breakdown().unwrap());
+    assert_eq!(breakdown.shares.len(), 2);
+    assert_eq!(breakdown.shares[1].orderer, "bob");
+    assert_eq!(breakdown.shares[1].owed, usd(220));
+
+    // a bill mixing currencies is rejected
+    let mixed = r#"{"name": "mixed", "currency": "EUR", "total": {"amount": "1.00", "currency": "USD"}, "items": {}, "counter": 0}"#;
+    let response = client
+        .post("http://localhost:3000/bill/insert")
+        .header("content-type", "application/json")
+        .body(mixed)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 422);
+
+    let response = client
+        .get(format!("http://localhost:3000/bill/{}/breakdown", Uuid::new_v4()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+
+    let response = client
+        .get("http://localhost:3000/bill/not-a-uuid/for/alice")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let error: ErrorBody = response.json().await.unwrap();
+    assert_eq!(error, ErrorBody { code: "invalid_uuid".to_string(), message: "Invalid UUID".to_string(), errors: vec![] });
+
+    stop_server(server);
+}
@@ -0,0 +1,134 @@
+// Integration tests for bill versions, ETag and If-Match
+use tokio::task;
+use uuid::Uuid;
+use billsplit::api::auth::Token;
+use billsplit::api::error::ErrorBody;
+use billsplit::models::bill::Bill;
+use billsplit::models::currency::{Currency, Money};
+use billsplit::models::item::LineItem;
+use billsplit::models::participant::Participant;
+use billsplit::models::user::Credentials;
+
+fn start_server() -> task::JoinHandle<()> {
+    task::spawn(async {
+        let config = billsplit::config::Config::new();
+        billsplit::start_server(config).await;
+    })
+}
+
+fn stop_server(server: task::JoinHandle<()>) {
+    server.abort();
+}
+
+/// Signs up as `username` and returns a client that sends their token.
+async fn sign_in(username: &str) -> reqwest::Client {
+    let client = reqwest::Client::new();
+    let credentials = Credentials::new(username.to_string(), "correct horse".to_string());
+    client.post("http://localhost:3000/auth/register").json(&credentials).send().await.unwrap();
+    let token: Token = client
+        .post("http://localhost:3000/auth/login")
+        .json(&credentials)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(reqwest::header::AUTHORIZATION, format!("Bearer {}", token.token).parse().unwrap());
+    reqwest::Client::builder().default_headers(headers).build().unwrap()
+}
+
+#[tokio::test]
+async fn test_etag() {
+    let server = start_server();
+    let client = sign_in("alice").await;
+
+    let bill = Bill::new("dinner".to_string(), Currency::USD);
+    let uuid: Uuid = client
+        .post("http://localhost:3000/bill/insert")
+        .json(&bill)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let url = format!("http://localhost:3000/bill/{}", uuid);
+
+    let response = client.get(&url).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["etag"], "\"1\"");
+
+    // changing a bill needs If-Match
+    let renamed = Bill::new("lunch".to_string(), Currency::USD);
+    let response = client.put(&url).json(&renamed).send().await.unwrap();
+    assert_eq!(response.status(), 428);
+    let body: ErrorBody = response.json().await.unwrap();
+    assert_eq!(body.code, "precondition_required");
+
+    let response = client.put(&url).header("If-Match", "\"1\"").json(&renamed).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["etag"], "\"2\"");
+
+    // the first version is now stale
+    let response = client.put(&url).header("If-Match", "\"1\"").json(&bill).send().await.unwrap();
+    assert_eq!(response.status(), 412);
+    let body: ErrorBody = response.json().await.unwrap();
+    assert_eq!(body.code, "version_mismatch");
+
+    let response = client.delete(&url).header("If-Match", "W/\"2\"").send().await.unwrap();
+    assert_eq!(response.status(), 412);
+    let body: ErrorBody = response.json().await.unwrap();
+    assert_eq!(body.code, "precondition_failed");
+
+    let response = client.get(&url).send().await.unwrap();
+    assert_eq!(response.headers()["etag"], "\"2\"");
+    let current: Bill = response.json().await.unwrap();
+    assert_eq!(current, renamed);
+
+    // items, participants and members only check If-Match when it is sent
+    let items = format!("{}/items", url);
+    let item = LineItem::from("pasta".to_string(), Money::new(1200, Currency::USD), None);
+    let response = client.post(&items).header("If-Match", "\"1\"").json(&item).send().await.unwrap();
+    assert_eq!(response.status(), 412);
+    let body: ErrorBody = response.json().await.unwrap();
+    assert_eq!(body.code, "version_mismatch");
+
+    let response = client.post(&items).json(&item).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["etag"], "\"3\"");
+    let item_id: u16 = response.json().await.unwrap();
+
+    let item_url = format!("{}/{}", items, item_id);
+    let response = client.put(&item_url).header("If-Match", "\"2\"").json(&item).send().await.unwrap();
+    assert_eq!(response.status(), 412);
+    let response = client.put(&item_url).header("If-Match", "\"3\"").json(&item).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["etag"], "\"4\"");
+    let response = client.delete(&item_url).header("If-Match", "\"4\"").send().await.unwrap();
+    assert_eq!(response.headers()["etag"], "\"5\"");
+
+    let participants = format!("{}/participants", url);
+    let bob = Participant::new("Bob".to_string());
+    let response = client.post(&participants).header("If-Match", "\"4\"").json(&bob).send().await.unwrap();
+    assert_eq!(response.status(), 412);
+    let response = client.post(&participants).header("If-Match", "\"5\"").json(&bob).send().await.unwrap();
+    assert_eq!(response.headers()["etag"], "\"6\"");
+
+    sign_in("bob").await;
+    let member = format!("{}/members/bob", url);
+    let response = client.put(&member).header("If-Match", "\"5\"").send().await.unwrap();
+    assert_eq!(response.status(), 412);
+    let response = client.put(&member).header("If-Match", "\"6\"").send().await.unwrap();
+    assert_eq!(response.headers()["etag"], "\"7\"");
+    let response = client.delete(&member).send().await.unwrap();
+    assert_eq!(response.headers()["etag"], "\"8\"");
+
+    let response = client.delete(&url).header("If-Match", "*").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let response = client.get(&url).send().await.unwrap();
+    assert_eq!(response.status(), 404);
+
+    stop_server(server);
+}
@@ -0,0 +1,115 @@
+// Integration tests for groups and their balances across bills
+use tokio::task;
+use uuid::Uuid;
+use billsplit::api::auth::Token;
+use billsplit::api::error::ErrorBody;
+use billsplit::models::bill::Bill;
+use billsplit::models::currency::{Currency, Money};
+use billsplit::models::group::{BalanceSheet, Group, GroupWithId};
+use billsplit::models::item::LineItem;
+use billsplit::models::payer::Payer;
+use billsplit::models::settlement::Transfer;
+use billsplit::models::user::Credentials;
+
+fn start_server() -> task::JoinHandle<()> {
+    task::spawn(async {
+        let config = billsplit::config::Config::new();
+        billsplit::start_server(config).await;
+    })
+}
+
+fn stop_server(server: task::JoinHandle<()>) {
+    server.abort();
+}
+
+/// Signs up as `username` and returns a client that sends their token.
+async fn sign_in(username: &str) -> reqwest::Client {
+    let client = reqwest::Client::new();
+    let credentials = Credentials::new(username.to_string(), "correct horse".to_string());
+    client.post("http://localhost:3000/auth/register").json(&credentials).send().await.unwrap();
+    let token: Token = client
+        .post("http://localhost:3000/auth/login")
+        .json(&credentials)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(reqwest::header::AUTHORIZATION, format!("Bearer {}", token.token).parse().unwrap());
+    reqwest::Client::builder().default_headers(headers).build().unwrap()
+}
+
+fn usd(amount: i64) -> Money {
+    Money::new(amount, Currency::USD)
+}
+
+async fn insert_bill(client: &reqwest::Client, orderers: &[(&str, i64)], payer: &str) -> Uuid {
+    let mut bill = Bill::new("dinner".to_string(), Currency::USD);
+    for (orderer, price) in orderers {
+        bill.add_item(LineItem::from("dish".to_string(), usd(*price), Some(orderer.to_string()))).unwrap();
+    }
+    bill.set_payers(vec![Payer::new(payer.to_string(), bill.calculate_total().unwrap())]).unwrap();
+    client
+        .post("http://localhost:3000/bill/insert")
+        .json(&bill)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_groups() {
+    let server = start_server();
+    let client = sign_in("alice").await;
+
+    let lunch = insert_bill(&client, &[("alice", 1000), ("bob", 1000), ("carol", 1000)], "alice").await;
+    let dinner = insert_bill(&client, &[("alice", 3000), ("bob", 1500)], "bob").await;
+
+    let mut group = Group::new("team".to_string());
+    group.members = vec!["alice".to_string(), "bob".to_string(), "carol".to_string()];
+    group.bills = vec![lunch, Uuid::new_v4()];
+    let response = client.post("http://localhost:3000/groups").json(&group).send().await.unwrap();
+    assert_eq!(response.status(), 422);
+    let body: ErrorBody = response.json().await.unwrap();
+    assert_eq!(body.errors[0].path, "bills.1");
+
+    group.bills = vec![lunch];
+    let response = client.post("http://localhost:3000/groups").json(&group).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let id: Uuid = response.json().await.unwrap();
+    let url = format!("http://localhost:3000/groups/{}", id);
+
+    group.bills.push(dinner);
+    let response = client.put(&url).json(&group).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let stored: Group = client.get(&url).send().await.unwrap().json().await.unwrap();
+    assert_eq!(stored, group);
+    let groups: Vec<GroupWithId> = client.get("http://localhost:3000/groups").send().await.unwrap().json().await.unwrap();
+    assert!(groups.contains(&GroupWithId { id, group: group.clone() }));
+
+    // alice is owed 2000 for lunch and owes 3000 for dinner; bob the reverse
+    let response = client.get(format!("{}/balances", url)).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let sheets: Vec<BalanceSheet> = response.json().await.unwrap();
+    assert_eq!(sheets.len(), 1);
+    let nets = sheets[0].balances.iter().map(|balance| balance.net.amount()).collect::<Vec<i64>>();
+    assert_eq!(nets, vec![-1000, 2000, -1000]);
+    assert_eq!(sheets[0].transfers, vec![
+        Transfer { from: "alice".to_string(), to: "bob".to_string(), amount: usd(1000) },
+        Transfer { from: "carol".to_string(), to: "bob".to_string(), amount: usd(1000) },
+    ]);
+
+    let response = client.delete(&url).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let response = client.get(&url).send().await.unwrap();
+    assert_eq!(response.status(), 404);
+    let body: ErrorBody = response.json().await.unwrap();
+    assert_eq!(body.code, "group_not_found");
+
+    stop_server(server);
+}
@@ -0,0 +1,96 @@
+// Integration tests for bill history and reverting to earlier versions
+use tokio::task;
+use uuid::Uuid;
+use billsplit::api::auth::Token;
+use billsplit::api::error::ErrorBody;
+use billsplit::data::history::{Change, Revision};
+use billsplit::models::bill::Bill;
+use billsplit::models::currency::{Currency, Money};
+use billsplit::models::item::LineItem;
+use billsplit::models::user::Credentials;
+
+fn start_server() -> task::JoinHandle<()> {
+    task::spawn(async {
+        let config = billsplit::config::Config::new();
+        billsplit::start_server(config).await;
+    })
+}
+
+fn stop_server(server: task::JoinHandle<()>) {
+    server.abort();
+}
+
+/// Signs up as `username` and returns a client that sends their token.
+async fn sign_in(username: &str) -> reqwest::Client {
+    let client = reqwest::Client::new();
+    let credentials = Credentials::new(username.to_string(), "correct horse".to_string());
+    client.post("http://localhost:3000/auth/register").json(&credentials).send().await.unwrap();
+    let token: Token = client
+        .post("http://localhost:3000/auth/login")
+        .json(&credentials)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(reqwest::header::AUTHORIZATION, format!("Bearer {}", token.token).parse().unwrap());
+    reqwest::Client::builder().default_headers(headers).build().unwrap()
+}
+
+#[tokio::test]
+async fn test_history() {
+    let server = start_server();
+    let client = sign_in("alice").await;
+
+    let bill = Bill::new("dinner".to_string(), Currency::USD);
+    let uuid: Uuid = client
+        .post("http://localhost:3000/bill/insert")
+        .json(&bill)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let url = format!("http://localhost:3000/bill/{}", uuid);
+
+    let item = LineItem::from("pasta".to_string(), Money::new(1200, Currency::USD), Some("alice".to_string()));
+    let response = client.post(format!("{}/items", url)).json(&item).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    // the wrong item gets deleted
+    let response = client.delete(format!("{}/items/0", url)).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    let response = client.get(format!("{}/history", url)).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let history: Vec<Revision> = response.json().await.unwrap();
+    let changes = history.iter().map(|revision| revision.change.clone()).collect::<Vec<Change>>();
+    assert_eq!(changes, vec![Change::Created, Change::ItemAdded { item_id: 0 }, Change::ItemDeleted { item_id: 0 }]);
+    assert_eq!(history[0].after, bill);
+    assert_eq!(history[2].before.as_ref().unwrap().items()[&0], item);
+    assert!(history[2].after.items().is_empty());
+
+    let revert_url = format!("{}/revert/2", url);
+    let response = client.post(&revert_url).send().await.unwrap();
+    assert_eq!(response.status(), 428);
+    let response = client.post(&revert_url).header("If-Match", "\"2\"").send().await.unwrap();
+    assert_eq!(response.status(), 412);
+
+    let response = client.post(&revert_url).header("If-Match", "\"3\"").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["etag"], "\"4\"");
+    let reverted: Bill = response.json().await.unwrap();
+    assert_eq!(reverted.items()[&0], item);
+
+    let response = client.post(format!("{}/revert/9", url)).header("If-Match", "*").send().await.unwrap();
+    assert_eq!(response.status(), 404);
+    let body: ErrorBody = response.json().await.unwrap();
+    assert_eq!(body.code, "revision_not_found");
+
+    let history: Vec<Revision> = client.get(format!("{}/history", url)).send().await.unwrap().json().await.unwrap();
+    assert_eq!(history.last().unwrap().change, Change::Reverted { to: 2 });
+
+    stop_server(server);
+}
@@ -1,8 +1,12 @@
 // Integration tests for API endpoints
+#![allow(clippy::needless_borrow, clippy::single_component_path_imports)]
 use tokio::task;
 use tokio;
 use uuid::Uuid;
+use billsplit::api::auth::Token;
 use billsplit::models::bill::{Bill, BillWithId};
+use billsplit::models::currency::Currency;
+use billsplit::models::user::Credentials;
 // use axum::Server;
 
 fn start_server() -> task::JoinHandle<()> {
@@ -16,10 +20,29 @@ fn stop_server(server: task::JoinHandle<()>) {
     server.abort();
 }
 
+/// Signs up as `username` and returns a client that sends their token.
+async fn sign_in(username: &str) -> reqwest::Client {
+    let client = reqwest::Client::new();
+    let credentials = Credentials::new(username.to_string(), "correct horse".to_string());
+    client.post("http://localhost:3000/auth/register").json(&credentials).send().await.unwrap();
+    let token: Token = client
+        .post("http://localhost:3000/auth/login")
+        .json(&credentials)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(reqwest::header::AUTHORIZATION, format!("Bearer {}", token.token).parse().unwrap());
+    reqwest::Client::builder().default_headers(headers).build().unwrap()
+}
+
 #[tokio::test]
 async fn test_get_bills() {
     let server = start_server();
-    let client = reqwest::Client::new();
+    let client = sign_in("alice").await;
 
     let response = client
         .get("http://localhost:3000/bills")
@@ -32,7 +55,7 @@ async fn test_get_bills() {
     let bills: Vec<BillWithId> = serde_json::from_str(&body).unwrap();
     assert_eq!(bills.len(), 0);
 
-    let new_bill = Bill::new("test".to_string());
+    let new_bill = Bill::new("test".to_string(), Currency::USD);
     let response = client
         .post("http://localhost:3000/bill/insert")
         .json(&new_bill)
@@ -69,6 +92,7 @@ async fn test_get_bills() {
     // delete
     let response = client
         .delete("http://localhost:3000/bill/".to_string() + &uuid.to_string())
+        .header("If-Match", "\"1\"")
         .send()
         .await
         .unwrap();
@@ -115,6 +139,24 @@ async fn test_get_bills() {
     let body = response.text().await.unwrap();
     let bill: Bill = serde_json::from_str(&body).unwrap();
     assert_eq!(bill.name, "test");
+    assert_eq!(bill.currency(), Currency::USD);
+
+    let response = client
+        .post("http://localhost:3000/bill/new?currency=JPY")
+        .json("test")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let uuid: Uuid = response.json().await.unwrap();
+
+    let response = client
+        .get("http://localhost:3000/bill/".to_string() + &uuid.to_string())
+        .send()
+        .await
+        .unwrap();
+    let bill: Bill = response.json().await.unwrap();
+    assert_eq!(bill.currency(), Currency::JPY);
 
     stop_server(server);
 }
@@ -0,0 +1,147 @@
+// Integration tests for the line item endpoints
+use std::collections::HashMap;
+use tokio::task;
+use uuid::Uuid;
+use billsplit::api::auth::Token;
+use billsplit::api::error::ErrorBody;
+use billsplit::models::bill::Bill;
+use billsplit::models::currency::{Currency, Money};
+use billsplit::models::item::LineItem;
+use billsplit::models::user::Credentials;
+
+fn start_server() -> task::JoinHandle<()> {
+    task::spawn(async {
+        let config = billsplit::config::Config::new();
+        billsplit::start_server(config).await;
+    })
+}
+
+fn usd(amount: i64) -> Money {
+    Money::new(amount, Currency::USD)
+}
+
+fn stop_server(server: task::JoinHandle<()>) {
+    server.abort();
+}
+
+/// Signs up as `username` and returns a client that sends their token.
+async fn sign_in(username: &str) -> reqwest::Client {
+    let client = reqwest::Client::new();
+    let credentials = Credentials::new(username.to_string(), "correct horse".to_string());
+    client.post("http://localhost:3000/auth/register").json(&credentials).send().await.unwrap();
+    let token: Token = client
+        .post("http://localhost:3000/auth/login")
+        .json(&credentials)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(reqwest::header::AUTHORIZATION, format!("Bearer {}", token.token).parse().unwrap());
+    reqwest::Client::builder().default_headers(headers).build().unwrap()
+}
+
+#[tokio::test]
+async fn test_items() {
+    let server = start_server();
+    let client = sign_in("alice").await;
+
+    let response = client
+        .post("http://localhost:3000/bill/new")
+        .json("test")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let uuid: Uuid = response.json().await.unwrap();
+    let items_url = format!("http://localhost:3000/bill/{}/items", uuid);
+
+    // add two items
+    let first = LineItem::from("first".to_string(), usd(100), Some("alice".to_string()));
+    let response = client.post(&items_url).json(&first).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let first_id: u16 = response.json().await.unwrap();
+
+    let second = LineItem::from("second".to_string(), usd(200), Some("bob".to_string()));
+    let response = client.post(&items_url).json(&second).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let second_id: u16 = response.json().await.unwrap();
+    assert_ne!(first_id, second_id);
+
+    let response = client.get(&items_url).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let items: HashMap<u16, LineItem> = response.json().await.unwrap();
+    assert_eq!(items.len(), 2);
+    assert_eq!(items.get(&first_id), Some(&first));
+    assert_eq!(items.get(&second_id), Some(&second));
+
+    // update
+    let updated = LineItem::from("first".to_string(), usd(150), Some("alice".to_string()));
+    let response = client
+        .put(format!("{}/{}", items_url, first_id))
+        .json(&updated)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let response = client
+        .get(format!("http://localhost:3000/bill/{}", uuid))
+        .send()
+        .await
+        .unwrap();
+    let bill: Bill = response.json().await.unwrap();
+    assert_eq!(bill.items().get(&first_id), Some(&updated));
+    assert_eq!(bill.calculate_subtotal().unwrap(), usd(350));
+
+    // delete
+    let response = client
+        .delete(format!("{}/{}", items_url, second_id))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let response = client
+        .delete(format!("{}/{}", items_url, second_id))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+    let error: ErrorBody = response.json().await.unwrap();
+    assert_eq!(error.code, "item_not_found");
+
+    let response = client
+        .put(format!("{}/{}", items_url, second_id))
+        .json(&updated)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+
+    let response = client.get(&items_url).send().await.unwrap();
+    let items: HashMap<u16, LineItem> = response.json().await.unwrap();
+    assert_eq!(items.len(), 1);
+
+    // items must be in the bill's currency
+    let euros = LineItem::from("euros".to_string(), Money::new(100, Currency::EUR), None);
+    let response = client.post(&items_url).json(&euros).send().await.unwrap();
+    assert_eq!(response.status(), 422);
+    let error: ErrorBody = response.json().await.unwrap();
+    assert_eq!(error.code, "currency_mismatch");
+
+    // unknown bill
+    let response = client
+        .post(format!("http://localhost:3000/bill/{}/items", Uuid::new_v4()))
+        .json(&first)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+    let error: ErrorBody = response.json().await.unwrap();
+    assert_eq!(error.code, "bill_not_found");
+
+    stop_server(server);
+}
@@ -0,0 +1,104 @@
+// Integration tests for the timestamps the server keeps on bills
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use tokio::task;
+use uuid::Uuid;
+use billsplit::api::auth::Token;
+use billsplit::models::bill::BillWithId;
+use billsplit::models::user::Credentials;
+
+fn start_server() -> task::JoinHandle<()> {
+    task::spawn(async {
+        let config = billsplit::config::Config::new();
+        billsplit::start_server(config).await;
+    })
+}
+
+fn stop_server(server: task::JoinHandle<()>) {
+    server.abort();
+}
+
+/// Signs up as `username` and returns a client that sends their token.
+async fn sign_in(username: &str) -> reqwest::Client {
+    let client = reqwest::Client::new();
+    let credentials = Credentials::new(username.to_string(), "correct horse".to_string());
+    client.post("http://localhost:3000/auth/register").json(&credentials).send().await.unwrap();
+    let token: Token = client
+        .post("http://localhost:3000/auth/login")
+        .json(&credentials)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(reqwest::header::AUTHORIZATION, format!("Bearer {}", token.token).parse().unwrap());
+    reqwest::Client::builder().default_headers(headers).build().unwrap()
+}
+
+async fn get_stored(client: &reqwest::Client, id: Uuid) -> BillWithId {
+    let bills: Vec<BillWithId> = client
+        .get("http://localhost:3000/bills")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    bills.into_iter().find(|stored| stored.id == id).unwrap()
+}
+
+#[tokio::test]
+async fn test_metadata() {
+    let server = start_server();
+    let client = sign_in("alice").await;
+    let before = Utc::now();
+
+    // timestamps sent with a bill are not the client's to set
+    let forged: DateTime<Utc> = "2000-01-01T00:00:00Z".parse().unwrap();
+    let id: Uuid = client
+        .post("http://localhost:3000/bill/insert")
+        .json(&json!({
+            "name": "dinner",
+            "currency": "USD",
+            "total": null,
+            "items": {},
+            "counter": 0,
+            "created_at": forged,
+            "updated_at": forged,
+            "version": 7,
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let created = get_stored(&client, id).await;
+    assert_eq!(created.version, 1);
+    assert!(created.created_at >= before);
+    assert_eq!(created.updated_at, created.created_at);
+
+    let url = format!("http://localhost:3000/bill/{}", id);
+    let response = client.get(&url).send().await.unwrap();
+    let last_modified = response.headers()["last-modified"].to_str().unwrap();
+    let last_modified = DateTime::parse_from_rfc2822(last_modified).unwrap();
+    assert_eq!(last_modified.timestamp(), created.updated_at.timestamp());
+
+    let response = client
+        .patch(&url)
+        .header("If-Match", "\"1\"")
+        .header("Content-Type", "application/merge-patch+json")
+        .body(json!({"name": "lunch", "created_at": forged}).to_string())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let updated = get_stored(&client, id).await;
+    assert_eq!(updated.created_at, created.created_at);
+    assert!(updated.updated_at >= created.updated_at);
+    assert_eq!(updated.version, 2);
+
+    stop_server(server);
+}
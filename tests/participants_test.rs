@@ -0,0 +1,118 @@
+// Integration tests for registering, merging and removing participants
+use serde_json::json;
+use tokio::task;
+use uuid::Uuid;
+use billsplit::api::auth::Token;
+use billsplit::api::error::ErrorBody;
+use billsplit::models::bill::{Bill, BillWithId};
+use billsplit::models::currency::{Currency, Money};
+use billsplit::models::item::LineItem;
+use billsplit::models::participant::{Participant, Participants};
+use billsplit::models::user::Credentials;
+
+fn start_server() -> task::JoinHandle<()> {
+    task::spawn(async {
+        let config = billsplit::config::Config::new();
+        billsplit::start_server(config).await;
+    })
+}
+
+fn stop_server(server: task::JoinHandle<()>) {
+    server.abort();
+}
+
+/// Signs up as `username` and returns a client that sends their token.
+async fn sign_in(username: &str) -> reqwest::Client {
+    let client = reqwest::Client::new();
+    let credentials = Credentials::new(username.to_string(), "correct horse".to_string());
+    client.post("http://localhost:3000/auth/register").json(&credentials).send().await.unwrap();
+    let token: Token = client
+        .post("http://localhost:3000/auth/login")
+        .json(&credentials)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(reqwest::header::AUTHORIZATION, format!("Bearer {}", token.token).parse().unwrap());
+    reqwest::Client::builder().default_headers(headers).build().unwrap()
+}
+
+fn usd(amount: i64) -> Money {
+    Money::new(amount, Currency::USD)
+}
+
+async fn bill_for(client: &reqwest::Client, bill: Uuid, orderer: &str) -> Bill {
+    client
+        .get(format!("http://localhost:3000/bill/{}/for/{}", bill, orderer))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_participants() {
+    let server = start_server();
+    let client = sign_in("alice").await;
+
+    let mut bill = Bill::new("dinner".to_string(), Currency::USD);
+    bill.add_item(LineItem::from("pasta".to_string(), usd(1200), Some("Bob".to_string()))).unwrap();
+    bill.add_item(LineItem::from("wine".to_string(), usd(800), Some("bob".to_string()))).unwrap();
+    bill.add_item(LineItem::from("dessert".to_string(), usd(500), Some("Bob ".to_string()))).unwrap();
+    bill.add_item(LineItem::from("coffee".to_string(), usd(300), Some("robert".to_string()))).unwrap();
+    let id: Uuid = client
+        .post("http://localhost:3000/bill/insert")
+        .json(&bill)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(bill_for(&client, id, "bob").await.calculate_total().unwrap(), usd(800));
+
+    // "Bob", "bob" and "Bob " are one person once Bob is registered
+    let url = format!("http://localhost:3000/bill/{}/participants", id);
+    let response = client.post(&url).json(&Participant::new("Bob".to_string())).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let bob: Uuid = response.json().await.unwrap();
+    assert_eq!(bill_for(&client, id, "bob").await.calculate_total().unwrap(), usd(2500));
+    assert_eq!(bill_for(&client, id, &bob.to_string()).await.calculate_total().unwrap(), usd(2500));
+
+    let robert: Uuid = client
+        .post(&url)
+        .json(&Participant::new("Robert".to_string()))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let merge = format!("{}/{}/merge", url, robert);
+    let response = client.post(&merge).json(&json!({ "into": bob })).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(bill_for(&client, id, "Robert").await.calculate_total().unwrap(), usd(2800));
+
+    // the bill refers to Bob by id now, but is still found by his names
+    for name in ["bob", "Robert"] {
+        let query = format!("http://localhost:3000/bills?participant={}", name);
+        let bills: Vec<BillWithId> = client.get(&query).send().await.unwrap().json().await.unwrap();
+        assert_eq!(bills.iter().map(|stored| stored.id).collect::<Vec<Uuid>>(), vec![id]);
+    }
+
+    let participants: Participants = client.get(&url).send().await.unwrap().json().await.unwrap();
+    assert_eq!(participants.len(), 1);
+    assert!(participants.get(bob).unwrap().aliases.contains(&"Robert".to_string()));
+
+    let response = client.post(&merge).json(&json!({ "into": bob })).send().await.unwrap();
+    assert_eq!(response.status(), 404);
+    let body: ErrorBody = response.json().await.unwrap();
+    assert_eq!(body.code, "participant_not_found");
+
+    stop_server(server);
+}
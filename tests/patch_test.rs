@@ -0,0 +1,145 @@
+// Integration tests for partial bill updates with PATCH
+use serde_json::json;
+use tokio::task;
+use uuid::Uuid;
+use billsplit::api::auth::Token;
+use billsplit::api::error::ErrorBody;
+use billsplit::models::bill::Bill;
+use billsplit::models::currency::{Currency, Money};
+use billsplit::models::item::LineItem;
+use billsplit::models::user::Credentials;
+
+fn start_server() -> task::JoinHandle<()> {
+    task::spawn(async {
+        let config = billsplit::config::Config::new();
+        billsplit::start_server(config).await;
+    })
+}
+
+fn stop_server(server: task::JoinHandle<()>) {
+    server.abort();
+}
+
+/// Signs up as `username` and returns a client that sends their token.
+async fn sign_in(username: &str) -> reqwest::Client {
+    let client = reqwest::Client::new();
+    let credentials = Credentials::new(username.to_string(), "correct horse".to_string());
+    client.post("http://localhost:3000/auth/register").json(&credentials).send().await.unwrap();
+    let token: Token = client
+        .post("http://localhost:3000/auth/login")
+        .json(&credentials)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(reqwest::header::AUTHORIZATION, format!("Bearer {}", token.token).parse().unwrap());
+    reqwest::Client::builder().default_headers(headers).build().unwrap()
+}
+
+#[tokio::test]
+async fn test_patch_bill() {
+    let server = start_server();
+    let client = sign_in("alice").await;
+
+    let mut bill = Bill::new("dinner".to_string(), Currency::USD);
+    bill.add_item(LineItem::from("pasta".to_string(), Money::new(1200, Currency::USD), None)).unwrap();
+    let uuid: Uuid = client
+        .post("http://localhost:3000/bill/insert")
+        .json(&bill)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let url = format!("http://localhost:3000/bill/{}", uuid);
+
+    let response = client
+        .patch(&url)
+        .header("If-Match", "\"1\"")
+        .header("Content-Type", "application/merge-patch+json")
+        .body(json!({"name": "lunch", "items": {"0": {"orderers": {"alice": "equal"}}}}).to_string())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["etag"], "\"2\"");
+    let patched: Bill = response.json().await.unwrap();
+    assert_eq!(patched.name, "lunch");
+    assert!(patched.items()[&0].is_ordered_by("alice"));
+
+    let response = client
+        .patch(&url)
+        .header("If-Match", "\"2\"")
+        .header("Content-Type", "application/json-patch+json")
+        .body(json!([
+            {"op": "replace", "path": "/items/0/price/amount", "value": "15.00"},
+            {"op": "add", "path": "/items/1", "value": {"name": "wine", "price": {"amount": "30.00", "currency": "USD"}}},
+        ]).to_string())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let patched: Bill = response.json().await.unwrap();
+    assert_eq!(patched.items()[&0].price, Money::new(1500, Currency::USD));
+    assert_eq!(patched.calculate_total().unwrap(), Money::new(4500, Currency::USD));
+
+    // a failed operation leaves the bill untouched
+    let response = client
+        .patch(&url)
+        .header("If-Match", "*")
+        .header("Content-Type", "application/json-patch+json")
+        .body(json!([
+            {"op": "replace", "path": "/name", "value": "breakfast"},
+            {"op": "test", "path": "/name", "value": "dinner"},
+        ]).to_string())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 409);
+    let body: ErrorBody = response.json().await.unwrap();
+    assert_eq!(body.code, "conflict");
+
+    let response = client
+        .patch(&url)
+        .header("If-Match", "*")
+        .header("Content-Type", "application/merge-patch+json")
+        .body(json!({"currency": "EUR"}).to_string())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 422);
+    let body: ErrorBody = response.json().await.unwrap();
+    assert_eq!(body.code, "currency_mismatch");
+
+    let response = client
+        .patch(&url)
+        .header("If-Match", "*")
+        .json(&json!({"name": "breakfast"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 415);
+
+    let response = client
+        .patch(&url)
+        .header("If-Match", "*")
+        .header("Content-Type", "application/json-patch+json")
+        .body(json!({"name": "breakfast"}).to_string())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let body: ErrorBody = response.json().await.unwrap();
+    assert_eq!(body.code, "invalid_patch");
+
+    let response = client.get(&url).send().await.unwrap();
+    assert_eq!(response.headers()["etag"], "\"3\"");
+    let current: Bill = response.json().await.unwrap();
+    assert_eq!(current, patched);
+
+    stop_server(server);
+}
@@ -0,0 +1,142 @@
+// Integration tests for recording payments and settling bills with them
+use chrono::NaiveDate;
+use tokio::task;
+use uuid::Uuid;
+use billsplit::api::auth::Token;
+use billsplit::api::error::ErrorBody;
+use billsplit::models::bill::{Bill, BillWithId};
+use billsplit::models::currency::{Currency, Money};
+use billsplit::models::item::LineItem;
+use billsplit::models::payer::Payer;
+use billsplit::models::payment::{Payment, PaymentWithId, Reference};
+use billsplit::models::settlement::{SettlementPlan, Transfer};
+use billsplit::models::user::Credentials;
+
+fn start_server() -> task::JoinHandle<()> {
+    task::spawn(async {
+        let config = billsplit::config::Config::new();
+        billsplit::start_server(config).await;
+    })
+}
+
+fn stop_server(server: task::JoinHandle<()>) {
+    server.abort();
+}
+
+/// Signs up as `username` and returns a client that sends their token.
+async fn sign_in(username: &str) -> reqwest::Client {
+    let client = reqwest::Client::new();
+    let credentials = Credentials::new(username.to_string(), "correct horse".to_string());
+    client.post("http://localhost:3000/auth/register").json(&credentials).send().await.unwrap();
+    let token: Token = client
+        .post("http://localhost:3000/auth/login")
+        .json(&credentials)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(reqwest::header::AUTHORIZATION, format!("Bearer {}", token.token).parse().unwrap());
+    reqwest::Client::builder().default_headers(headers).build().unwrap()
+}
+
+fn usd(amount: i64) -> Money {
+    Money::new(amount, Currency::USD)
+}
+
+async fn settlement(client: &reqwest::Client, bill: Uuid) -> SettlementPlan {
+    client
+        .get(format!("http://localhost:3000/bill/{}/settlement", bill))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+async fn settled_bills(client: &reqwest::Client) -> Vec<Uuid> {
+    let bills: Vec<BillWithId> = client
+        .get("http://localhost:3000/bills?settled=true")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    bills.into_iter().map(|stored| stored.id).collect()
+}
+
+#[tokio::test]
+async fn test_payments() {
+    let server = start_server();
+    let client = sign_in("alice").await;
+
+    let mut bill = Bill::new("dinner".to_string(), Currency::USD);
+    bill.add_item(LineItem::from("pasta".to_string(), usd(2340), Some("alice".to_string()))).unwrap();
+    bill.add_item(LineItem::from("steak".to_string(), usd(3000), Some("bob".to_string()))).unwrap();
+    bill.set_payers(vec![Payer::new("carol".to_string(), usd(5340))]).unwrap();
+    let id: Uuid = client
+        .post("http://localhost:3000/bill/insert")
+        .json(&bill)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(!settled_bills(&client).await.contains(&id));
+
+    // "Alice paid me back $23.40"
+    let date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+    let mut payment = Payment::new("alice".to_string(), "carol".to_string(), usd(2340), date);
+    payment.note = Some("pasta".to_string());
+    payment.reference = Some(Reference::Bill(id));
+    let response = client.post("http://localhost:3000/payments").json(&payment).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let alice: Uuid = response.json().await.unwrap();
+    let url = format!("http://localhost:3000/payments/{}", alice);
+    let stored: Payment = client.get(&url).send().await.unwrap().json().await.unwrap();
+    assert_eq!(stored, payment);
+
+    let plan = settlement(&client, id).await;
+    assert_eq!(plan.transfers, vec![Transfer { from: "bob".to_string(), to: "carol".to_string(), amount: usd(3000) }]);
+    assert!(!plan.settled);
+
+    let mut invalid = Payment::new("bob".to_string(), "bob".to_string(), Money::new(3000, Currency::EUR), date);
+    invalid.reference = Some(Reference::Bill(id));
+    let response = client.post("http://localhost:3000/payments").json(&invalid).send().await.unwrap();
+    assert_eq!(response.status(), 422);
+    let body: ErrorBody = response.json().await.unwrap();
+    assert_eq!(body.errors[0].path, "to");
+
+    let mut payment = Payment::new("bob".to_string(), "carol".to_string(), usd(3000), date);
+    payment.reference = Some(Reference::Bill(id));
+    client.post("http://localhost:3000/payments").json(&payment).send().await.unwrap();
+    let plan = settlement(&client, id).await;
+    assert!(plan.transfers.is_empty());
+    assert!(plan.settled);
+    assert!(settled_bills(&client).await.contains(&id));
+
+    let payments: Vec<PaymentWithId> = client
+        .get(format!("http://localhost:3000/payments?bill={}", id))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(payments.len(), 2);
+
+    let response = client.delete(&url).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let response = client.get(&url).send().await.unwrap();
+    assert_eq!(response.status(), 404);
+    let body: ErrorBody = response.json().await.unwrap();
+    assert_eq!(body.code, "payment_not_found");
+    assert!(!settlement(&client, id).await.settled);
+
+    stop_server(server);
+}
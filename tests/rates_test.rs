@@ -0,0 +1,138 @@
+// Integration tests for exchange rates and settling in another currency
+use serde_json::json;
+use tokio::task;
+use uuid::Uuid;
+use billsplit::api::auth::Token;
+use billsplit::api::error::ErrorBody;
+use billsplit::models::bill::Bill;
+use billsplit::models::breakdown::Settlement;
+use billsplit::models::currency::{Currency, Money};
+use billsplit::models::exchange::RateTable;
+use billsplit::models::item::LineItem;
+use billsplit::models::user::Credentials;
+
+fn start_server() -> task::JoinHandle<()> {
+    task::spawn(async {
+        let mut config = billsplit::config::Config::new();
+        config.auth.admins = vec!["alice".to_string()];
+        billsplit::start_server(config).await;
+    })
+}
+
+fn eur(amount: i64) -> Money {
+    Money::new(amount, Currency::EUR)
+}
+
+fn stop_server(server: task::JoinHandle<()>) {
+    server.abort();
+}
+
+/// Signs up as `username` and returns a client that sends their token.
+async fn sign_in(username: &str) -> reqwest::Client {
+    let client = reqwest::Client::new();
+    let credentials = Credentials::new(username.to_string(), "correct horse".to_string());
+    client.post("http://localhost:3000/auth/register").json(&credentials).send().await.unwrap();
+    let token: Token = client
+        .post("http://localhost:3000/auth/login")
+        .json(&credentials)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(reqwest::header::AUTHORIZATION, format!("Bearer {}", token.token).parse().unwrap());
+    reqwest::Client::builder().default_headers(headers).build().unwrap()
+}
+
+#[tokio::test]
+async fn test_rates() {
+    let server = start_server();
+    let client = sign_in("alice").await;
+
+    let mut new_bill = Bill::new("museum".to_string(), Currency::EUR);
+    new_bill.add_item(LineItem::from("ticket".to_string(), eur(4250), Some("alice".to_string()))).unwrap();
+    let response = client
+        .post("http://localhost:3000/bill/insert")
+        .json(&new_bill)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let uuid: Uuid = response.json().await.unwrap();
+
+    // no rates have been loaded yet
+    let response = client
+        .get(format!("http://localhost:3000/bill/{}/for/alice/in/USD", uuid))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 422);
+
+    let rates = json!({
+        "as_of": "2024-03-01T12:00:00Z",
+        "rates": [{"from": "EUR", "to": "USD", "rate": "1.0842"}],
+    });
+
+    // only an admin may replace the table everyone shares
+    let response = sign_in("bob").await
+        .put("http://localhost:3000/rates")
+        .json(&rates)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 403);
+    let body: ErrorBody = response.json().await.unwrap();
+    assert_eq!(body.code, "forbidden");
+
+    let response = client
+        .put("http://localhost:3000/rates")
+        .json(&rates)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let response = client
+        .get("http://localhost:3000/rates")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let table: RateTable = response.json().await.unwrap();
+    assert_eq!(table.rates().len(), 1);
+
+    let response = client
+        .get(format!("http://localhost:3000/bill/{}/for/alice/in/USD", uuid))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let settlement: Settlement = response.json().await.unwrap();
+    assert_eq!(settlement.conversion.amount, eur(4250));
+    assert_eq!(settlement.conversion.converted, Money::new(4608, Currency::USD));
+    assert_eq!(settlement.conversion.as_of, table.as_of());
+
+    // the same pair twice is rejected
+    let rates = json!({
+        "as_of": "2024-03-01T12:00:00Z",
+        "rates": [{"from": "EUR", "to": "USD", "rate": "1.08"}, {"from": "USD", "to": "EUR", "rate": "0.92"}],
+    });
+    let response = client
+        .put("http://localhost:3000/rates")
+        .json(&rates)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 422);
+
+    let response = client
+        .get(format!("http://localhost:3000/bill/{}/for/alice/in/XYZ", uuid))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+
+    stop_server(server);
+}
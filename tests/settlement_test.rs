@@ -0,0 +1,88 @@
+// Integration tests for settling up a bill between payers and orderers
+use tokio::task;
+use uuid::Uuid;
+use billsplit::api::auth::Token;
+use billsplit::models::bill::Bill;
+use billsplit::models::currency::{Currency, Money};
+use billsplit::models::item::LineItem;
+use billsplit::models::payer::Payer;
+use billsplit::models::settlement::{SettlementPlan, Transfer};
+use billsplit::models::user::Credentials;
+
+fn start_server() -> task::JoinHandle<()> {
+    task::spawn(async {
+        let config = billsplit::config::Config::new();
+        billsplit::start_server(config).await;
+    })
+}
+
+fn stop_server(server: task::JoinHandle<()>) {
+    server.abort();
+}
+
+/// Signs up as `username` and returns a client that sends their token.
+async fn sign_in(username: &str) -> reqwest::Client {
+    let client = reqwest::Client::new();
+    let credentials = Credentials::new(username.to_string(), "correct horse".to_string());
+    client.post("http://localhost:3000/auth/register").json(&credentials).send().await.unwrap();
+    let token: Token = client
+        .post("http://localhost:3000/auth/login")
+        .json(&credentials)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(reqwest::header::AUTHORIZATION, format!("Bearer {}", token.token).parse().unwrap());
+    reqwest::Client::builder().default_headers(headers).build().unwrap()
+}
+
+fn usd(amount: i64) -> Money {
+    Money::new(amount, Currency::USD)
+}
+
+#[tokio::test]
+async fn test_settlement() {
+    let server = start_server();
+    let client = sign_in("alice").await;
+
+    let mut bill = Bill::new("dinner".to_string(), Currency::USD);
+    bill.add_item(LineItem::from("pasta".to_string(), usd(1200), Some("alice".to_string()))).unwrap();
+    bill.add_item(LineItem::from("steak".to_string(), usd(2400), Some("bob".to_string()))).unwrap();
+    bill.add_item(LineItem::from("salad".to_string(), usd(900), Some("carol".to_string()))).unwrap();
+    bill.set_payers(vec![Payer::new("bob".to_string(), usd(4500))]).unwrap();
+
+    let uuid: Uuid = client
+        .post("http://localhost:3000/bill/insert")
+        .json(&bill)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    let response = client
+        .get(format!("http://localhost:3000/bill/{}/settlement", uuid))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let plan: SettlementPlan = response.json().await.unwrap();
+    assert_eq!(plan, bill.get_settlement_plan().unwrap());
+    assert_eq!(plan.transfers, vec![
+        Transfer { from: "alice".to_string(), to: "bob".to_string(), amount: usd(1200) },
+        Transfer { from: "carol".to_string(), to: "bob".to_string(), amount: usd(900) },
+    ]);
+
+    let response = client
+        .get(format!("http://localhost:3000/bill/{}/settlement", Uuid::new_v4()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+
+    stop_server(server);
+}
@@ -0,0 +1,102 @@
+// Integration tests for rejecting invalid bills and items
+use tokio::task;
+use uuid::Uuid;
+use billsplit::api::auth::Token;
+use billsplit::api::error::ErrorBody;
+use billsplit::models::bill::Bill;
+use billsplit::models::currency::{Currency, Money};
+use billsplit::models::item::LineItem;
+use billsplit::models::user::Credentials;
+
+fn start_server() -> task::JoinHandle<()> {
+    task::spawn(async {
+        let config = billsplit::config::Config::new();
+        billsplit::start_server(config).await;
+    })
+}
+
+fn stop_server(server: task::JoinHandle<()>) {
+    server.abort();
+}
+
+/// Signs up as `username` and returns a client that sends their token.
+async fn sign_in(username: &str) -> reqwest::Client {
+    let client = reqwest::Client::new();
+    let credentials = Credentials::new(username.to_string(), "correct horse".to_string());
+    client.post("http://localhost:3000/auth/register").json(&credentials).send().await.unwrap();
+    let token: Token = client
+        .post("http://localhost:3000/auth/login")
+        .json(&credentials)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(reqwest::header::AUTHORIZATION, format!("Bearer {}", token.token).parse().unwrap());
+    reqwest::Client::builder().default_headers(headers).build().unwrap()
+}
+
+fn paths(body: &ErrorBody) -> Vec<&str> {
+    body.errors.iter().map(|error| error.path.as_str()).collect()
+}
+
+#[tokio::test]
+async fn test_validation() {
+    let server = start_server();
+    let client = sign_in("alice").await;
+
+    let response = client.post("http://localhost:3000/bill/new").json(&" ").send().await.unwrap();
+    assert_eq!(response.status(), 422);
+    let body: ErrorBody = response.json().await.unwrap();
+    assert_eq!(body.code, "validation_failed");
+    assert_eq!(paths(&body), vec!["name"]);
+
+    let bill = serde_json::json!({
+        "name": "dinner",
+        "currency": "USD",
+        "total": {"amount": "1.00", "currency": "USD"},
+        "items": {"3": {"name": "pasta", "price": {"amount": "20000000000.00", "currency": "USD"}}},
+        "counter": 3,
+    });
+    let response = client.post("http://localhost:3000/bill/insert").json(&bill).send().await.unwrap();
+    assert_eq!(response.status(), 422);
+    let body: ErrorBody = response.json().await.unwrap();
+    assert_eq!(paths(&body), vec!["items.3.price", "counter", "total"]);
+
+    let uuid: Uuid = client
+        .post("http://localhost:3000/bill/insert")
+        .json(&Bill::from("dinner".to_string(), Money::new(1000, Currency::USD)))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let url = format!("http://localhost:3000/bill/{}", uuid);
+
+    // items are checked against the whole bill, so this one would take the
+    // subtotal past the total
+    let item = LineItem::from("steak".to_string(), Money::new(1200, Currency::USD), None);
+    let response = client.post(format!("{}/items", url)).json(&item).send().await.unwrap();
+    assert_eq!(response.status(), 422);
+    let body: ErrorBody = response.json().await.unwrap();
+    assert_eq!(paths(&body), vec!["total"]);
+
+    let response = client
+        .patch(&url)
+        .header("Content-Type", "application/merge-patch+json")
+        .header("If-Match", "*")
+        .body(r#"{"name": ""}"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 422);
+
+    // nothing was changed by any of it
+    let response = client.get(&url).send().await.unwrap();
+    assert_eq!(response.headers()["etag"], "\"1\"");
+
+    stop_server(server);
+}